@@ -13,6 +13,7 @@ pub enum MigrationType {
 }
 
 pub enum FieldType {
+    Attachment,
     Reference,
     ReferenceWithCustomField(String),
     Type(String),
@@ -23,6 +24,7 @@ pub fn parse_field_type(ftype: &str) -> Result<FieldType> {
     let parts: Vec<&str> = ftype.split(':').collect();
 
     match parts.as_slice() {
+        ["attachment" | "attachments"] => Ok(FieldType::Attachment),
         ["references"] => Ok(FieldType::Reference),
         ["references", f] => Ok(FieldType::ReferenceWithCustomField((*f).to_string())),
         [t] => Ok(FieldType::Type((*t).to_string())),
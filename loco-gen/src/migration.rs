@@ -5,7 +5,9 @@ use rrgen::RRgen;
 use serde_json::json;
 
 use crate::{
-    infer, model::get_columns_and_references, render_template, AppInfo, GenerateResults, Result,
+    infer,
+    model::{get_attachments, get_columns_and_references},
+    render_template, AppInfo, GenerateResults, Result,
 };
 
 /// skipping some fields from the generated models.
@@ -27,7 +29,8 @@ pub fn generate(
         // NOTE: re-uses the 'new model' migration template!
         infer::MigrationType::CreateTable { table } => {
             let (columns, references) = get_columns_and_references(fields)?;
-            let vars = json!({"name": table, "ts": ts, "pkg_name": pkg_name, "is_link": false, "columns": columns, "references": references});
+            let attachments = get_attachments(fields)?;
+            let vars = json!({"name": table, "ts": ts, "pkg_name": pkg_name, "is_link": false, "columns": columns, "references": references, "attachments": attachments});
             render_template(rrgen, Path::new("model/model.t"), &vars)
         }
         infer::MigrationType::AddColumns { table } => {
@@ -35,6 +35,10 @@ pub fn get_columns_and_references(
         }
         let field_type = parse_field_type(ftype)?;
         match field_type {
+            crate::infer::FieldType::Attachment => {
+                // attachments live in the attachments table, see
+                // `get_attachments`
+            }
             crate::infer::FieldType::Reference => {
                 // (users, "")
                 references.push((fname.to_string(), String::new()));
@@ -90,6 +94,25 @@ pub fn get_columns_and_references(
     Ok((columns, references))
 }
 
+/// attachments are <name>, parsed from e.g.: model article cover:attachment
+///  the files are linked in the `attachments` table rather than a column
+///
+/// # Errors
+///
+/// when a field type cannot be parsed
+pub fn get_attachments(fields: &[(String, String)]) -> Result<Vec<String>> {
+    let mut attachments = Vec::new();
+    for (fname, ftype) in fields {
+        if matches!(
+            parse_field_type(ftype)?,
+            crate::infer::FieldType::Attachment
+        ) {
+            attachments.push(fname.to_string());
+        }
+    }
+    Ok(attachments)
+}
+
 pub fn generate(
     rrgen: &RRgen,
     name: &str,
@@ -101,9 +124,20 @@ pub fn generate(
     let ts = Utc::now();
 
     let (columns, references) = get_columns_and_references(fields)?;
+    let attachments = get_attachments(fields)?;
 
-    let vars = json!({"name": name, "ts": ts, "pkg_name": pkg_name, "is_link": is_link, "columns": columns, "references": references});
-    let gen_result = render_template(rrgen, Path::new("model"), &vars)?;
+    let vars = json!({"name": name, "ts": ts, "pkg_name": pkg_name, "is_link": is_link, "columns": columns, "references": references, "attachments": attachments});
+    let mut gen_result = render_template(rrgen, Path::new("model/model.t"), &vars)?;
+    let res = render_template(rrgen, Path::new("model/test.t"), &vars)?;
+    gen_result.rrgen.extend(res.rrgen);
+    gen_result.local_templates.extend(res.local_templates);
+    if !attachments.is_empty() {
+        // the model file is generated here rather than by `db entities`, to
+        // purge the attachments of deleted records
+        let res = render_template(rrgen, Path::new("model/attachments.t"), &vars)?;
+        gen_result.rrgen.extend(res.rrgen);
+        gen_result.local_templates.extend(res.local_templates);
+    }
 
     if std::env::var("SKIP_MIGRATION").is_err() {
         // generate the model files by migrating and re-running seaorm
@@ -210,6 +244,20 @@ mod tests {
         assert_eq!(res, (expected_columns, expected_references));
     }
 
+    #[test]
+    fn test_get_attachments_from_fields() {
+        let fields = [
+            to_field("title", "string"),
+            to_field("cover", "attachment"),
+            to_field("photos", "attachments"),
+        ];
+        let res = get_columns_and_references(&fields).expect("Failed to parse fields");
+        assert_eq!(res, (vec![to_field("title", "StringNull")], vec![]));
+
+        let attachments = get_attachments(&fields).expect("Failed to parse fields");
+        assert_eq!(attachments, vec!["cover".to_string(), "photos".to_string()]);
+    }
+
     #[test]
     fn validate_arity() {
         // field not expected arity, but given 2
@@ -32,6 +32,9 @@ pub fn generate(
 
         let field_type = parse_field_type(ftype)?;
         match field_type {
+            crate::infer::FieldType::Attachment => {
+                // stored in the attachments table, not as a column
+            }
             crate::infer::FieldType::Reference => {
                 // (users, "")
                 //references.push((fname.to_string(), String::new()));
@@ -28,7 +28,7 @@ impl {{struct_name}} {
             ctx,
             &welcome,
             mailer::Args {
-                to: to.to_string(),
+                to: to.into(),
                 locals: json!({
                   "message": msg,
                   "domain": ctx.config.server.full_url()
@@ -0,0 +1,59 @@
+{% set plural_snake = name | plural | snake_case -%}
+{% set model = name | plural | pascal_case -%}
+to: "src/models/{{plural_snake}}.rs"
+skip_exists: true
+message: "Model `{{model}}` with attachments `{{ attachments | join(sep="`, `") }}` was added."
+injections:
+- into: "src/models/mod.rs"
+  append: true
+  content: "pub mod {{plural_snake}};"
+---
+use loco_rs::model::{attachments, ModelResult};
+use sea_orm::entity::prelude::*;
+pub use super::_entities::{{plural_snake}}::{ActiveModel, Model, Entity};
+pub type {{model}} = Entity;
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(self, _db: &C, insert: bool) -> std::result::Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if !insert && self.updated_at.is_unchanged() {
+            let mut this = self;
+            this.updated_at = sea_orm::ActiveValue::Set(chrono::Utc::now().into());
+            Ok(this)
+        } else {
+            Ok(self)
+        }
+    }
+
+    async fn after_delete<C>(self, db: &C) -> std::result::Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        // the files are deleted by the `purge_attachments` task
+        attachments::mark_for_purge(db, &self).await?;
+        Ok(self)
+    }
+}
+
+// implement your read-oriented logic here
+impl Model {
+{%- for attachment in attachments %}{% if not loop.first %}
+{% endif %}
+    /// Lists the `{{attachment}}` attachments.
+    pub async fn {{attachment}}<C: ConnectionTrait>(
+        &self,
+        db: &C,
+    ) -> ModelResult<Vec<attachments::Model>> {
+        attachments::list_named(db, self, "{{attachment}}").await
+    }
+{%- endfor %}
+}
+
+// implement your write-oriented logic here
+impl ActiveModel {}
+
+// implement your custom finders, selectors oriented logic here
+impl Entity {}
@@ -22,6 +22,11 @@ pub struct Migration;
 #[async_trait::async_trait]
 impl MigrationTrait for Migration {
     async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        {% if attachments | length > 0 -%}
+        // attachments: {{ attachments | join(sep=", ") }}, the table is shared
+        // by all the models and kept on rollback
+        create_attachments_table(m).await?;
+        {% endif -%}
         create_table(m, "{{plural_snake}}",
             &[
             {% for column in columns -%}
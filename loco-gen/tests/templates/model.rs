@@ -76,6 +76,66 @@ fn can_generate() {
     );
 }
 
+#[test]
+fn can_generate_with_attachments() {
+    std::env::set_var("SKIP_MIGRATION", "");
+    configure_insta!();
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .add("migration/src/lib.rs", MIGRATION_SRC_LIB)
+        .add_empty("tests/models/mod.rs")
+        .add_empty("src/models/mod.rs")
+        .create()
+        .unwrap();
+
+    let rrgen = RRgen::with_working_dir(&tree_fs.root);
+    let component = Component::Model {
+        name: "movies".to_string(),
+        link: false,
+        fields: vec![
+            ("title".to_string(), "string".to_string()),
+            ("cover".to_string(), "attachment".to_string()),
+            ("photos".to_string(), "attachments".to_string()),
+        ],
+    };
+
+    let gen_result = generate(
+        &rrgen,
+        component,
+        &AppInfo {
+            app_name: "tester".to_string(),
+        },
+    )
+    .expect("Generation failed");
+
+    assert_eq!(
+        collect_messages(&gen_result),
+        r"* Migration for `movies` added! You can now apply it with `$ cargo loco db migrate`.
+* A test for model `Movies` was added. Run with `cargo test`.
+* Model `Movies` with attachments `cover`, `photos` was added.
+"
+    );
+
+    let migration_path = tree_fs.root.join("migration/src");
+    let migration_file = guess_file_by_time(&migration_path, "m{TIME}_movies.rs", 3)
+        .expect("Failed to find the generated migration file");
+
+    assert_snapshot!(
+        "generate[attachments_migration_file]",
+        fs::read_to_string(&migration_file).expect("Failed to read the migration file")
+    );
+
+    let models_path = tree_fs.root.join("src/models");
+    assert_snapshot!(
+        "generate[attachments_model]",
+        fs::read_to_string(models_path.join("movies.rs")).expect("Failed to read movies.rs")
+    );
+    assert_snapshot!(
+        "inject[models_mod]",
+        fs::read_to_string(models_path.join("mod.rs")).expect("Failed to read mod.rs")
+    );
+}
+
 #[test]
 fn fail_when_migration_lib_not_exists() {
     std::env::set_var("SKIP_MIGRATION", "");
@@ -10,6 +10,15 @@ use tracing::info;
 
 const I18N_DIR: &str = "assets/i18n";
 const I18N_SHARED: &str = "assets/i18n/shared.ftl";
+
+fn i18n_loader() -> Result<ArcLoader> {
+    ArcLoader::builder(&I18N_DIR, unic_langid::langid!("en-US"))
+        .shared_resources(Some(&[I18N_SHARED.into()]))
+        .customize(|bundle| bundle.set_use_isolating(false))
+        .build()
+        .map_err(|e| Error::string(&e.to_string()))
+}
+
 #[allow(clippy::module_name_repetitions)]
 pub struct ViewEngineInitializer;
 
@@ -19,15 +28,19 @@ impl Initializer for ViewEngineInitializer {
         "view-engine".to_string()
     }
 
+    async fn before_run(&self, _ctx: &AppContext) -> Result<()> {
+        // mailer templates use the same `t()` as the views, also in workers
+        if std::path::Path::new(I18N_DIR).exists() {
+            loco_rs::mailer::template::register_function("t", FluentLoader::new(i18n_loader()?));
+        }
+        Ok(())
+    }
+
     async fn after_routes(&self, router: AxumRouter, _ctx: &AppContext) -> Result<AxumRouter> {
         #[allow(unused_mut)]
         let mut tera_engine = engines::TeraView::build()?;
         if std::path::Path::new(I18N_DIR).exists() {
-            let arc = ArcLoader::builder(&I18N_DIR, unic_langid::langid!("en-US"))
-                .shared_resources(Some(&[I18N_SHARED.into()]))
-                .customize(|bundle| bundle.set_use_isolating(false))
-                .build()
-                .map_err(|e| Error::string(&e.to_string()))?;
+            let arc = i18n_loader()?;
             #[cfg(debug_assertions)]
             tera_engine
                 .tera
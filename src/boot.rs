@@ -23,7 +23,7 @@ use crate::{
     env_vars,
     environment::Environment,
     errors::Error,
-    mailer::{EmailSender, MailerWorker},
+    mailer::{delivery, dkim, EmailSender, MailerWorker},
     prelude::BackgroundWorker,
     scheduler::{self, Scheduler},
     storage::{self, Storage},
@@ -375,13 +375,19 @@ pub async fn create_context<H: Hooks>(
         None
     };
 
+    let storage = if let Some(cfg) = config.storage.as_ref() {
+        Storage::from_config(cfg)?
+    } else {
+        Storage::single(storage::drivers::null::new())
+    };
+
     let queue_provider = bgworker::create_queue_provider(&config).await?;
     let ctx = AppContext {
         environment: environment.clone(),
         #[cfg(feature = "with-db")]
         db,
         queue_provider,
-        storage: Storage::single(storage::drivers::null::new()).into(),
+        storage: storage.into(),
         cache: cache::Cache::new(cache::drivers::null::new()).into(),
         config,
         mailer,
@@ -571,6 +577,34 @@ pub fn list_middlewares<H: Hooks>(ctx: &AppContext) -> Vec<MiddlewareInfo> {
 /// Initializes an [`EmailSender`] based on the mailer configuration settings
 /// ([`config::Mailer`]).
 fn create_mailer(config: &config::Mailer) -> Result<Option<EmailSender>> {
+    let Some(sender) = create_email_sender(config)? else {
+        return Ok(None);
+    };
+    let sender = match &config.delivery_log {
+        None => sender,
+        Some(config::DeliveryLogConfig::Storage { prefix }) => {
+            sender.with_delivery_log(delivery::StorageDeliveryLog::new(prefix))
+        }
+        #[cfg(feature = "with-db")]
+        Some(config::DeliveryLogConfig::Database) => {
+            sender.with_delivery_log(delivery::DatabaseDeliveryLog)
+        }
+        #[allow(unreachable_patterns)]
+        Some(_) => {
+            return Err(Error::Message(
+                "mailer delivery log `Database` requires the `with-db` feature".to_string(),
+            ))
+        }
+    };
+    let sender = match &config.dkim {
+        Some(dkim_config) => sender.with_dkim(dkim::load(dkim_config)?),
+        None => sender,
+    };
+    Ok(Some(sender))
+}
+
+/// Initializes an [`EmailSender`] with the first enabled transport.
+fn create_email_sender(config: &config::Mailer) -> Result<Option<EmailSender>> {
     if config.stub {
         return Ok(Some(EmailSender::stub()));
     }
@@ -579,5 +613,15 @@ fn create_mailer(config: &config::Mailer) -> Result<Option<EmailSender>> {
             return Ok(Some(EmailSender::smtp(smtp)?));
         }
     }
+    if let Some(file) = config.file.as_ref() {
+        if file.enable {
+            return Ok(Some(EmailSender::file(&file.path)?));
+        }
+    }
+    if let Some(sendmail) = config.sendmail.as_ref() {
+        if sendmail.enable {
+            return Ok(Some(EmailSender::sendmail(sendmail.command.as_deref())));
+        }
+    }
     Ok(None)
 }
@@ -2,7 +2,11 @@
 //!
 //! This module implements a cache driver using an in-memory cache.
 use std::{
-    sync::Arc,
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
     time::{Duration, Instant},
 };
 
@@ -10,27 +14,135 @@ use async_trait::async_trait;
 use moka::{sync::Cache, Expiry};
 
 use super::CacheDriver;
-use crate::cache::CacheResult;
+use crate::cache::{CacheError, CacheResult, DriverStats};
 
 /// Creates a new instance of the in-memory cache driver, with a default Loco
-/// configuration.
+/// configuration, holding up to 32M entries.
 ///
 /// # Returns
 ///
 /// A boxed [`CacheDriver`] instance.
 #[must_use]
 pub fn new() -> Box<dyn CacheDriver> {
-    let cache: Cache<String, (Expiration, String)> = Cache::builder()
-        .max_capacity(32 * 1024 * 1024)
+    Box::new(build(None))
+}
+
+/// Creates a new instance of the in-memory cache driver bounded by the
+/// approximate size of its keys and values, in bytes, rather than by the
+/// number of entries. Its statistics report that size.
+///
+/// # Returns
+///
+/// A boxed [`CacheDriver`] instance.
+#[must_use]
+pub fn with_max_bytes(max_bytes: u64) -> Box<dyn CacheDriver> {
+    Box::new(build(Some(max_bytes)))
+}
+
+fn build(max_bytes: Option<u64>) -> Inmem {
+    let evictions = Arc::new(AtomicU64::new(0));
+    let listener_evictions = evictions.clone();
+    let tags = Arc::new(Mutex::new(TagIndex::default()));
+    let listener_tags = tags.clone();
+    let builder = match max_bytes {
+        Some(max_bytes) => Cache::builder().max_capacity(max_bytes).weigher(
+            |key: &String, value: &(Expiration, String)| {
+                u32::try_from(key.len() + value.1.len()).unwrap_or(u32::MAX)
+            },
+        ),
+        None => Cache::builder().max_capacity(32 * 1024 * 1024),
+    };
+    let cache: Cache<String, (Expiration, String)> = builder
         .expire_after(InMemExpiry)
+        .eviction_listener(move |key: Arc<String>, _value, cause| {
+            if cause.was_evicted() {
+                listener_evictions.fetch_add(1, Ordering::Relaxed);
+                if let Ok(mut index) = listener_tags.lock() {
+                    index.remove_key(&key);
+                }
+            }
+        })
         .build();
-    Inmem::from(cache)
+    Inmem {
+        cache,
+        tags,
+        evictions: Some(evictions),
+        weighted: max_bytes.is_some(),
+    }
 }
 
 /// Represents the in-memory cache driver.
 #[derive(Debug)]
 pub struct Inmem {
     cache: Cache<String, (Expiration, String)>,
+    /// Shared with the eviction listener of the cache built by [`new`]. The
+    /// lock is never held while calling into the cache, as the listener runs
+    /// on the calling thread.
+    tags: Arc<Mutex<TagIndex>>,
+    /// Number of entries evicted because they expired or the cache was full.
+    /// Only tracked when the cache is built by [`new`] or [`with_max_bytes`].
+    evictions: Option<Arc<AtomicU64>>,
+    /// Whether entries are weighed by their size in bytes, see
+    /// [`with_max_bytes`].
+    weighted: bool,
+}
+
+/// Keeps track of the keys attached to each tag, and the tags attached to
+/// each key, so both sides can be cleaned up when one of them goes away.
+///
+/// Keys expired or evicted by moka are removed by the eviction listener of
+/// the cache built by [`new`]. A cache given to [`Inmem::from`] has no such
+/// listener, so a tag may reference keys that no longer exist; invalidating
+/// them is a no-op.
+#[derive(Debug, Default)]
+struct TagIndex {
+    keys_by_tag: HashMap<String, HashSet<String>>,
+    tags_by_key: HashMap<String, HashSet<String>>,
+}
+
+impl TagIndex {
+    fn set(&mut self, key: &str, tags: &[&str]) {
+        self.remove_key(key);
+        if tags.is_empty() {
+            return;
+        }
+        for tag in tags {
+            self.keys_by_tag
+                .entry((*tag).to_string())
+                .or_default()
+                .insert(key.to_string());
+        }
+        self.tags_by_key.insert(
+            key.to_string(),
+            tags.iter().map(|tag| (*tag).to_string()).collect(),
+        );
+    }
+
+    fn remove_key(&mut self, key: &str) {
+        if let Some(tags) = self.tags_by_key.remove(key) {
+            for tag in tags {
+                if let Some(keys) = self.keys_by_tag.get_mut(&tag) {
+                    keys.remove(key);
+                    if keys.is_empty() {
+                        self.keys_by_tag.remove(&tag);
+                    }
+                }
+            }
+        }
+    }
+
+    fn take_tag(&mut self, tag: &str) -> HashSet<String> {
+        let keys = self.keys_by_tag.remove(tag).unwrap_or_default();
+        for key in &keys {
+            self.remove_key(key);
+        }
+        keys
+    }
+
+    fn clear(&mut self) {
+        self.keys_by_tag.clear();
+        self.tags_by_key.clear();
+    }
 }
 
 impl Inmem {
@@ -41,7 +153,18 @@ impl Inmem {
     /// A boxed [`CacheDriver`] instance.
     #[must_use]
     pub fn from(cache: Cache<String, (Expiration, String)>) -> Box<dyn CacheDriver> {
-        Box::new(Self { cache })
+        Box::new(Self {
+            cache,
+            tags: Arc::new(Mutex::new(TagIndex::default())),
+            evictions: None,
+            weighted: false,
+        })
+    }
+
+    fn tags(&self) -> CacheResult<std::sync::MutexGuard<'_, TagIndex>> {
+        self.tags
+            .lock()
+            .map_err(|err| CacheError::Any(err.to_string().into()))
     }
 }
 
@@ -75,6 +198,7 @@ impl CacheDriver for Inmem {
     ///
     /// Returns a `CacheError` if there is an error during the operation.
     async fn insert(&self, key: &str, value: &str) -> CacheResult<()> {
+        self.tags()?.remove_key(key);
         self.cache.insert(
             key.to_string(),
             (Expiration::Never, Arc::new(value).to_string()),
@@ -95,6 +219,7 @@ impl CacheDriver for Inmem {
         value: &str,
         duration: Duration,
     ) -> CacheResult<()> {
+        self.tags()?.remove_key(key);
         self.cache.insert(
             key.to_string(),
             (
@@ -105,25 +230,107 @@ impl CacheDriver for Inmem {
         Ok(())
     }
 
+    /// Inserts a key-value pair into the cache and associates it with the
+    /// given tags.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn insert_with_tags(
+        &self,
+        key: &str,
+        value: &str,
+        tags: &[&str],
+        duration: Option<Duration>,
+    ) -> CacheResult<()> {
+        let expiration = duration.map_or(Expiration::Never, Expiration::AfterDuration);
+        self.cache
+            .insert(key.to_string(), (expiration, value.to_string()));
+        self.tags()?.set(key, tags);
+        Ok(())
+    }
+
     /// Removes a key-value pair from the cache.
     ///
     /// # Errors
     ///
     /// Returns a `CacheError` if there is an error during the operation.
     async fn remove(&self, key: &str) -> CacheResult<()> {
+        self.tags()?.remove_key(key);
         self.cache.remove(key);
         Ok(())
     }
 
+    /// Removes all the keys that start with the given prefix.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn remove_prefix(&self, prefix: &str) -> CacheResult<()> {
+        let keys: Vec<Arc<String>> = self
+            .cache
+            .iter()
+            .filter(|(key, _)| key.starts_with(prefix))
+            .map(|(key, _)| key)
+            .collect();
+
+        let mut index = self.tags()?;
+        for key in &keys {
+            index.remove_key(key);
+        }
+        drop(index);
+        for key in keys {
+            self.cache.invalidate(key.as_str());
+        }
+        Ok(())
+    }
+
+    /// Removes all the keys that were inserted with the given tag.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn invalidate_tag(&self, tag: &str) -> CacheResult<()> {
+        let keys = self.tags()?.take_tag(tag);
+        for key in keys {
+            self.cache.invalidate(&key);
+        }
+        Ok(())
+    }
+
     /// Clears all key-value pairs from the cache.
     ///
     /// # Errors
     ///
     /// Returns a `CacheError` if there is an error during the operation.
     async fn clear(&self) -> CacheResult<()> {
+        self.tags()?.clear();
         self.cache.invalidate_all();
         Ok(())
     }
+
+    /// Returns the number of entries, the evictions when the cache is built
+    /// by [`new`] or [`with_max_bytes`], and the approximate size in bytes
+    /// when built by [`with_max_bytes`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn stats(&self) -> CacheResult<DriverStats> {
+        self.cache.run_pending_tasks();
+        Ok(DriverStats {
+            entry_count: Some(self.cache.entry_count()),
+            size: self.weighted.then(|| self.cache.weighted_size()),
+            evictions: self
+                .evictions
+                .as_ref()
+                .map(|evictions| evictions.load(Ordering::Relaxed)),
+        })
+    }
+
+    fn is_process_local(&self) -> bool {
+        true
+    }
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -203,4 +410,101 @@ mod tests {
             assert!(!mem.contains_key(key).await.unwrap());
         }
     }
+
+    #[tokio::test]
+    async fn can_remove_prefix() {
+        let mem = new();
+        assert!(mem.insert("user:1:profile", "loco").await.is_ok());
+        assert!(mem.insert("user:1:settings", "loco").await.is_ok());
+        assert!(mem.insert("user:2:profile", "loco").await.is_ok());
+
+        assert!(mem.remove_prefix("user:1:").await.is_ok());
+
+        assert!(!mem.contains_key("user:1:profile").await.unwrap());
+        assert!(!mem.contains_key("user:1:settings").await.unwrap());
+        assert!(mem.contains_key("user:2:profile").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn can_invalidate_tag() {
+        let mem = new();
+        assert!(mem
+            .insert_with_tags("profile", "loco", &["user:1"], None)
+            .await
+            .is_ok());
+        assert!(mem
+            .insert_with_tags("avatar", "loco", &["user:1", "images"], None)
+            .await
+            .is_ok());
+        assert!(mem
+            .insert_with_tags("logo", "loco", &["images"], None)
+            .await
+            .is_ok());
+
+        assert!(mem.invalidate_tag("user:1").await.is_ok());
+
+        assert!(!mem.contains_key("profile").await.unwrap());
+        assert!(!mem.contains_key("avatar").await.unwrap());
+        assert!(mem.contains_key("logo").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn reinsert_replaces_tags() {
+        let mem = new();
+        assert!(mem
+            .insert_with_tags("key", "loco", &["tag"], None)
+            .await
+            .is_ok());
+        assert!(mem.insert("key", "loco-2").await.is_ok());
+
+        assert!(mem.invalidate_tag("tag").await.is_ok());
+        assert_eq!(mem.get("key").await.unwrap(), Some("loco-2".to_string()));
+    }
+
+    #[tokio::test]
+    async fn can_get_stats() {
+        let mem = new();
+        assert!(mem.insert("key", "loco").await.is_ok());
+        assert!(mem.insert("key2", "loco").await.is_ok());
+
+        let stats = mem.stats().await.unwrap();
+        assert_eq!(stats.entry_count, Some(2));
+        assert_eq!(stats.size, None);
+        assert_eq!(stats.evictions, Some(0));
+
+        let mem = with_max_bytes(1024);
+        assert!(mem.insert("key", "loco").await.is_ok());
+        assert!(mem.insert("key2", "loco").await.is_ok());
+
+        let stats = mem.stats().await.unwrap();
+        assert_eq!(stats.entry_count, Some(2));
+        assert_eq!(stats.size, Some(15));
+    }
+
+    #[tokio::test]
+    async fn expired_keys_leave_the_tag_index() {
+        let mem = build(None);
+        assert!(mem
+            .insert_with_tags(
+                "session:1",
+                "loco",
+                &["user:1"],
+                Some(Duration::from_millis(10))
+            )
+            .await
+            .is_ok());
+        assert!(mem
+            .tags
+            .lock()
+            .unwrap()
+            .tags_by_key
+            .contains_key("session:1"));
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        mem.cache.run_pending_tasks();
+
+        let index = mem.tags.lock().unwrap();
+        assert!(index.tags_by_key.is_empty());
+        assert!(index.keys_by_tag.is_empty());
+    }
 }
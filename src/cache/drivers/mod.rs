@@ -5,11 +5,14 @@ use std::time::Duration;
 
 use async_trait::async_trait;
 
-use super::CacheResult;
+use super::{CacheError, CacheResult, DriverStats};
 
 #[cfg(feature = "cache_inmem")]
 pub mod inmem;
 pub mod null;
+#[cfg(feature = "cache_sqlt")]
+pub mod sqlite;
+pub mod two_tier;
 
 /// Trait representing a cache driver.
 #[async_trait]
@@ -52,6 +55,29 @@ pub trait CacheDriver: Sync + Send {
         duration: Duration,
     ) -> CacheResult<()>;
 
+    /// Inserts a key-value pair into the cache and associates it with the
+    /// given tags, optionally expiring after the specified duration.
+    ///
+    /// Re-inserting a key replaces the tags previously attached to it. The
+    /// default implementation ignores the tags and inserts the key.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`super::CacheError`] if there is an error during the
+    /// operation.
+    async fn insert_with_tags(
+        &self,
+        key: &str,
+        value: &str,
+        _tags: &[&str],
+        duration: Option<Duration>,
+    ) -> CacheResult<()> {
+        match duration {
+            Some(duration) => self.insert_with_expiry(key, value, duration).await,
+            None => self.insert(key, value).await,
+        }
+    }
+
     /// Removes a key-value pair from the cache.
     ///
     /// # Errors
@@ -60,6 +86,32 @@ pub trait CacheDriver: Sync + Send {
     /// operation.
     async fn remove(&self, key: &str) -> CacheResult<()>;
 
+    /// Removes all the keys that start with the given prefix. Not supported
+    /// by default.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`super::CacheError`] if there is an error during the
+    /// operation.
+    async fn remove_prefix(&self, _prefix: &str) -> CacheResult<()> {
+        Err(CacheError::Any(
+            "Operation not supported by this cache driver".into(),
+        ))
+    }
+
+    /// Removes all the keys that were inserted with the given tag. Not
+    /// supported by default.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`super::CacheError`] if there is an error during the
+    /// operation.
+    async fn invalidate_tag(&self, _tag: &str) -> CacheResult<()> {
+        Err(CacheError::Any(
+            "Operation not supported by this cache driver".into(),
+        ))
+    }
+
     /// Clears all key-value pairs from the cache.
     ///
     /// # Errors
@@ -67,4 +119,87 @@ pub trait CacheDriver: Sync + Send {
     /// Returns a [`super::CacheError`] if there is an error during the
     /// operation.
     async fn clear(&self) -> CacheResult<()>;
+
+    /// Returns the statistics the driver knows about its storage, such as the
+    /// number of entries. Drivers that cannot tell leave them empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`super::CacheError`] if there is an error during the
+    /// operation.
+    async fn stats(&self) -> CacheResult<DriverStats> {
+        Ok(DriverStats::default())
+    }
+
+    /// Returns `true` when the entries live in the memory of the current
+    /// process, out of reach of other processes such as the CLI.
+    fn is_process_local(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Mutex};
+
+    use super::*;
+
+    /// A driver implementing only the required methods, as external drivers
+    /// written before tags existed do.
+    #[derive(Default)]
+    struct Minimal {
+        entries: Mutex<HashMap<String, String>>,
+    }
+
+    #[async_trait]
+    impl CacheDriver for Minimal {
+        async fn contains_key(&self, key: &str) -> CacheResult<bool> {
+            Ok(self.entries.lock().unwrap().contains_key(key))
+        }
+
+        async fn get(&self, key: &str) -> CacheResult<Option<String>> {
+            Ok(self.entries.lock().unwrap().get(key).cloned())
+        }
+
+        async fn insert(&self, key: &str, value: &str) -> CacheResult<()> {
+            self.entries
+                .lock()
+                .unwrap()
+                .insert(key.to_string(), value.to_string());
+            Ok(())
+        }
+
+        async fn insert_with_expiry(
+            &self,
+            key: &str,
+            value: &str,
+            _duration: Duration,
+        ) -> CacheResult<()> {
+            self.insert(key, value).await
+        }
+
+        async fn remove(&self, key: &str) -> CacheResult<()> {
+            self.entries.lock().unwrap().remove(key);
+            Ok(())
+        }
+
+        async fn clear(&self) -> CacheResult<()> {
+            self.entries.lock().unwrap().clear();
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn tags_default_to_plain_inserts() {
+        let driver = Minimal::default();
+        assert!(driver
+            .insert_with_tags("key", "value", &["tag"], None)
+            .await
+            .is_ok());
+        assert_eq!(driver.get("key").await.unwrap(), Some("value".to_string()));
+
+        assert!(driver.invalidate_tag("tag").await.is_err());
+        assert!(driver.remove_prefix("k").await.is_err());
+        assert!(driver.contains_key("key").await.unwrap());
+    }
 }
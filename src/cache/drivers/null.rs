@@ -75,6 +75,24 @@ impl CacheDriver for Null {
         ))
     }
 
+    /// Inserts a key-value pair into the cache and associates it with the
+    /// given tags.
+    ///
+    /// # Errors
+    ///
+    /// Returns always error
+    async fn insert_with_tags(
+        &self,
+        _key: &str,
+        _value: &str,
+        _tags: &[&str],
+        _duration: Option<Duration>,
+    ) -> CacheResult<()> {
+        Err(CacheError::Any(
+            "Operation not supported by null cache".into(),
+        ))
+    }
+
     /// Removes a key-value pair from the cache.
     ///
     /// # Errors
@@ -86,6 +104,28 @@ impl CacheDriver for Null {
         ))
     }
 
+    /// Removes all the keys that start with the given prefix.
+    ///
+    /// # Errors
+    ///
+    /// Returns always error
+    async fn remove_prefix(&self, _prefix: &str) -> CacheResult<()> {
+        Err(CacheError::Any(
+            "Operation not supported by null cache".into(),
+        ))
+    }
+
+    /// Removes all the keys that were inserted with the given tag.
+    ///
+    /// # Errors
+    ///
+    /// Returns always error
+    async fn invalidate_tag(&self, _tag: &str) -> CacheResult<()> {
+        Err(CacheError::Any(
+            "Operation not supported by null cache".into(),
+        ))
+    }
+
     /// Clears all key-value pairs from the cache.
     ///
     /// # Errors
@@ -0,0 +1,550 @@
+//! # `SQLite` Cache Driver
+//!
+//! This module implements a persistent cache driver backed by a `SQLite`
+//! database, for single-server deployments and CLI tasks that need a cache
+//! that survives restarts without running a separate cache server.
+//!
+//! Expired entries are never returned, and are deleted by a background task
+//! that also evicts the least recently used entries when the total size of
+//! the stored values goes over the configured `max_size`.
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions},
+    ConnectOptions, Row, SqlitePool,
+};
+use tokio::task::JoinHandle;
+
+use super::CacheDriver;
+use crate::cache::{CacheError, CacheResult, DriverStats};
+
+/// `SQLite` cache driver configuration
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Config {
+    /// The `SQLite` connection URI, for example
+    /// `sqlite://cache.sqlite?mode=rwc`.
+    #[serde(default = "default_uri")]
+    pub uri: String,
+    /// Maximum number of connections in the pool.
+    #[serde(default = "default_max_connections")]
+    pub max_connections: u32,
+    /// Maximum total size of the stored keys and values, in bytes. When
+    /// exceeded, the least recently used entries are evicted.
+    #[serde(default)]
+    pub max_size: Option<u64>,
+    /// Interval between two runs of the background eviction, in seconds. Must
+    /// be at least 1.
+    #[serde(default = "default_eviction_interval")]
+    pub eviction_interval: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        serde_json::from_value(json!({})).unwrap()
+    }
+}
+
+fn default_uri() -> String {
+    "sqlite://loco_cache.sqlite?mode=rwc".to_string()
+}
+
+fn default_max_connections() -> u32 {
+    5
+}
+
+fn default_eviction_interval() -> u64 {
+    60
+}
+
+/// Creates a new instance of the `SQLite` cache driver, creating the cache
+/// tables when missing and starting the background eviction task.
+///
+/// # Errors
+///
+/// Returns a [`CacheError`] when `eviction_interval` is 0, or when the
+/// database cannot be opened or initialized.
+pub async fn new(config: &Config) -> CacheResult<Box<dyn CacheDriver>> {
+    if config.eviction_interval == 0 {
+        return Err(CacheError::Any(
+            "the sqlite cache `eviction_interval` must be at least 1 second".into(),
+        ));
+    }
+
+    let conn_opts: SqliteConnectOptions = config.uri.parse().map_err(to_cache_error)?;
+    let pool = SqlitePoolOptions::new()
+        .max_connections(config.max_connections)
+        .connect_with(conn_opts.disable_statement_logging())
+        .await
+        .map_err(to_cache_error)?;
+
+    initialize_database(&pool).await?;
+
+    let evictions = Arc::new(AtomicU64::new(0));
+    let eviction_task = {
+        let pool = pool.clone();
+        let evictions = evictions.clone();
+        let max_size = config.max_size;
+        let interval = Duration::from_secs(config.eviction_interval);
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                match evict(&pool, max_size).await {
+                    Ok(count) => {
+                        evictions.fetch_add(count, Ordering::Relaxed);
+                    }
+                    Err(err) => {
+                        tracing::error!(err = err.to_string(), "could not evict cache entries");
+                    }
+                }
+            }
+        })
+    };
+
+    Ok(Box::new(Sqlite {
+        pool,
+        max_size: config.max_size,
+        evictions,
+        eviction_task,
+    }))
+}
+
+/// Represents the `SQLite` cache driver.
+#[derive(Debug)]
+pub struct Sqlite {
+    pool: SqlitePool,
+    max_size: Option<u64>,
+    evictions: Arc<AtomicU64>,
+    eviction_task: JoinHandle<()>,
+}
+
+impl Sqlite {
+    /// Deletes the expired entries, and the least recently used ones when the
+    /// cache is larger than its maximum size. This runs periodically in the
+    /// background and rarely needs to be called directly.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`CacheError`] if there is an error during the operation.
+    pub async fn evict(&self) -> CacheResult<u64> {
+        let count = evict(&self.pool, self.max_size).await?;
+        self.evictions.fetch_add(count, Ordering::Relaxed);
+        Ok(count)
+    }
+}
+
+impl Drop for Sqlite {
+    fn drop(&mut self) {
+        self.eviction_task.abort();
+    }
+}
+
+fn to_cache_error(err: sqlx::Error) -> CacheError {
+    CacheError::Any(Box::new(err))
+}
+
+fn now_millis() -> i64 {
+    Utc::now().timestamp_millis()
+}
+
+fn expires_at(duration: Option<Duration>) -> Option<i64> {
+    duration.map(|duration| {
+        now_millis().saturating_add(i64::try_from(duration.as_millis()).unwrap_or(i64::MAX))
+    })
+}
+
+/// Creates the cache tables.
+///
+/// # Errors
+///
+/// Returns a [`CacheError`] if there is an error during the operation.
+async fn initialize_database(pool: &SqlitePool) -> CacheResult<()> {
+    sqlx::query(
+        r"
+        CREATE TABLE IF NOT EXISTS loco_cache (
+            key TEXT PRIMARY KEY NOT NULL,
+            value TEXT NOT NULL,
+            size INTEGER NOT NULL,
+            expires_at INTEGER NULL,
+            accessed_at INTEGER NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS loco_cache_tags (
+            tag TEXT NOT NULL,
+            key TEXT NOT NULL,
+            PRIMARY KEY (tag, key)
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_loco_cache_expires_at ON loco_cache(expires_at);
+        CREATE INDEX IF NOT EXISTS idx_loco_cache_accessed_at ON loco_cache(accessed_at);
+        CREATE INDEX IF NOT EXISTS idx_loco_cache_tags_key ON loco_cache_tags(key);
+        ",
+    )
+    .execute(pool)
+    .await
+    .map_err(to_cache_error)?;
+    Ok(())
+}
+
+async fn evict(pool: &SqlitePool, max_size: Option<u64>) -> CacheResult<u64> {
+    let mut tx = pool.begin().await.map_err(to_cache_error)?;
+
+    let mut count = sqlx::query("DELETE FROM loco_cache WHERE expires_at <= $1")
+        .bind(now_millis())
+        .execute(&mut *tx)
+        .await
+        .map_err(to_cache_error)?
+        .rows_affected();
+
+    if let Some(max_size) = max_size {
+        count += sqlx::query(
+            r"
+            DELETE FROM loco_cache WHERE key IN (
+                SELECT key FROM (
+                    SELECT key, SUM(size) OVER (ORDER BY accessed_at DESC, key) AS total
+                    FROM loco_cache
+                ) WHERE total > $1
+            )",
+        )
+        .bind(i64::try_from(max_size).unwrap_or(i64::MAX))
+        .execute(&mut *tx)
+        .await
+        .map_err(to_cache_error)?
+        .rows_affected();
+    }
+
+    if count > 0 {
+        sqlx::query("DELETE FROM loco_cache_tags WHERE key NOT IN (SELECT key FROM loco_cache)")
+            .execute(&mut *tx)
+            .await
+            .map_err(to_cache_error)?;
+    }
+
+    tx.commit().await.map_err(to_cache_error)?;
+    Ok(count)
+}
+
+#[async_trait]
+impl CacheDriver for Sqlite {
+    /// Checks if a key exists in the cache.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn contains_key(&self, key: &str) -> CacheResult<bool> {
+        let row = sqlx::query(
+            "SELECT 1 FROM loco_cache WHERE key = $1 AND (expires_at IS NULL OR expires_at > $2)",
+        )
+        .bind(key)
+        .bind(now_millis())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(to_cache_error)?;
+        Ok(row.is_some())
+    }
+
+    /// Retrieves a value from the cache based on the provided key.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn get(&self, key: &str) -> CacheResult<Option<String>> {
+        let now = now_millis();
+        let row = sqlx::query(
+            "UPDATE loco_cache SET accessed_at = $1 WHERE key = $2 AND (expires_at IS NULL OR \
+             expires_at > $1) RETURNING value",
+        )
+        .bind(now)
+        .bind(key)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(to_cache_error)?;
+
+        row.map(|row| row.try_get::<String, _>("value"))
+            .transpose()
+            .map_err(to_cache_error)
+    }
+
+    /// Inserts a key-value pair into the cache.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn insert(&self, key: &str, value: &str) -> CacheResult<()> {
+        self.insert_with_tags(key, value, &[], None).await
+    }
+
+    /// Inserts a key-value pair into the cache that expires after the
+    /// specified duration.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn insert_with_expiry(
+        &self,
+        key: &str,
+        value: &str,
+        duration: Duration,
+    ) -> CacheResult<()> {
+        self.insert_with_tags(key, value, &[], Some(duration)).await
+    }
+
+    /// Inserts a key-value pair into the cache and associates it with the
+    /// given tags.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn insert_with_tags(
+        &self,
+        key: &str,
+        value: &str,
+        tags: &[&str],
+        duration: Option<Duration>,
+    ) -> CacheResult<()> {
+        let mut tx = self.pool.begin().await.map_err(to_cache_error)?;
+
+        sqlx::query(
+            "INSERT OR REPLACE INTO loco_cache (key, value, size, expires_at, accessed_at) VALUES \
+             ($1, $2, $3, $4, $5)",
+        )
+        .bind(key)
+        .bind(value)
+        .bind(i64::try_from(key.len() + value.len()).unwrap_or(i64::MAX))
+        .bind(expires_at(duration))
+        .bind(now_millis())
+        .execute(&mut *tx)
+        .await
+        .map_err(to_cache_error)?;
+
+        sqlx::query("DELETE FROM loco_cache_tags WHERE key = $1")
+            .bind(key)
+            .execute(&mut *tx)
+            .await
+            .map_err(to_cache_error)?;
+
+        for tag in tags {
+            sqlx::query("INSERT OR IGNORE INTO loco_cache_tags (tag, key) VALUES ($1, $2)")
+                .bind(tag)
+                .bind(key)
+                .execute(&mut *tx)
+                .await
+                .map_err(to_cache_error)?;
+        }
+
+        tx.commit().await.map_err(to_cache_error)
+    }
+
+    /// Removes a key-value pair from the cache.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn remove(&self, key: &str) -> CacheResult<()> {
+        let mut tx = self.pool.begin().await.map_err(to_cache_error)?;
+        for query in [
+            "DELETE FROM loco_cache WHERE key = $1",
+            "DELETE FROM loco_cache_tags WHERE key = $1",
+        ] {
+            sqlx::query(query)
+                .bind(key)
+                .execute(&mut *tx)
+                .await
+                .map_err(to_cache_error)?;
+        }
+        tx.commit().await.map_err(to_cache_error)
+    }
+
+    /// Removes all the keys that start with the given prefix.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn remove_prefix(&self, prefix: &str) -> CacheResult<()> {
+        let mut tx = self.pool.begin().await.map_err(to_cache_error)?;
+        for query in [
+            "DELETE FROM loco_cache WHERE substr(key, 1, length($1)) = $1",
+            "DELETE FROM loco_cache_tags WHERE substr(key, 1, length($1)) = $1",
+        ] {
+            sqlx::query(query)
+                .bind(prefix)
+                .execute(&mut *tx)
+                .await
+                .map_err(to_cache_error)?;
+        }
+        tx.commit().await.map_err(to_cache_error)
+    }
+
+    /// Removes all the keys that were inserted with the given tag.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn invalidate_tag(&self, tag: &str) -> CacheResult<()> {
+        let mut tx = self.pool.begin().await.map_err(to_cache_error)?;
+        for query in [
+            "DELETE FROM loco_cache WHERE key IN (SELECT key FROM loco_cache_tags WHERE tag = $1)",
+            "DELETE FROM loco_cache_tags WHERE key IN (SELECT key FROM loco_cache_tags WHERE tag \
+             = $1)",
+        ] {
+            sqlx::query(query)
+                .bind(tag)
+                .execute(&mut *tx)
+                .await
+                .map_err(to_cache_error)?;
+        }
+        tx.commit().await.map_err(to_cache_error)
+    }
+
+    /// Clears all key-value pairs from the cache.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn clear(&self) -> CacheResult<()> {
+        let mut tx = self.pool.begin().await.map_err(to_cache_error)?;
+        for query in ["DELETE FROM loco_cache", "DELETE FROM loco_cache_tags"] {
+            sqlx::query(query)
+                .execute(&mut *tx)
+                .await
+                .map_err(to_cache_error)?;
+        }
+        tx.commit().await.map_err(to_cache_error)
+    }
+
+    /// Returns the number of entries, their size in bytes and the number of
+    /// evictions since the driver was created.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn stats(&self) -> CacheResult<DriverStats> {
+        let row = sqlx::query(
+            "SELECT COUNT(*) AS entry_count, COALESCE(SUM(size), 0) AS size FROM loco_cache WHERE \
+             expires_at IS NULL OR expires_at > $1",
+        )
+        .bind(now_millis())
+        .fetch_one(&self.pool)
+        .await
+        .map_err(to_cache_error)?;
+
+        let entry_count: i64 = row.try_get("entry_count").map_err(to_cache_error)?;
+        let size: i64 = row.try_get("size").map_err(to_cache_error)?;
+
+        Ok(DriverStats {
+            entry_count: u64::try_from(entry_count).ok(),
+            size: u64::try_from(size).ok(),
+            evictions: Some(self.evictions.load(Ordering::Relaxed)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    async fn new_sqlite(max_size: Option<u64>) -> Sqlite {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        initialize_database(&pool).await.unwrap();
+        Sqlite {
+            pool,
+            max_size,
+            evictions: Arc::new(AtomicU64::new(0)),
+            eviction_task: tokio::spawn(async {}),
+        }
+    }
+
+    #[tokio::test]
+    async fn cannot_evict_continuously() {
+        let config = Config {
+            uri: "sqlite::memory:".to_string(),
+            eviction_interval: 0,
+            ..Default::default()
+        };
+        assert!(new(&config).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn can_get_key_value() {
+        let cache = new_sqlite(None).await;
+        assert!(cache.insert("key", "loco").await.is_ok());
+        assert!(cache.contains_key("key").await.unwrap());
+        assert_eq!(cache.get("key").await.unwrap(), Some("loco".to_string()));
+
+        assert!(cache.insert("key", "loco-2").await.is_ok());
+        assert_eq!(cache.get("key").await.unwrap(), Some("loco-2".to_string()));
+
+        assert_eq!(cache.get("not-found").await.unwrap(), None);
+
+        assert!(cache.remove("key").await.is_ok());
+        assert!(!cache.contains_key("key").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn can_expire() {
+        let cache = new_sqlite(None).await;
+        assert!(cache
+            .insert_with_expiry("key", "loco", Duration::from_millis(1))
+            .await
+            .is_ok());
+        tokio::time::sleep(Duration::from_millis(5)).await;
+
+        assert_eq!(cache.get("key").await.unwrap(), None);
+        assert_eq!(cache.evict().await.unwrap(), 1);
+        assert_eq!(cache.stats().await.unwrap().evictions, Some(1));
+    }
+
+    #[tokio::test]
+    async fn can_evict_least_recently_used() {
+        let cache = new_sqlite(Some(16)).await;
+        assert!(cache.insert("key1", "loco").await.is_ok());
+        tokio::time::sleep(Duration::from_millis(2)).await;
+        assert!(cache.insert("key2", "loco").await.is_ok());
+        tokio::time::sleep(Duration::from_millis(2)).await;
+        assert!(cache.get("key1").await.unwrap().is_some());
+        tokio::time::sleep(Duration::from_millis(2)).await;
+        assert!(cache.insert("key3", "loco").await.is_ok());
+
+        assert_eq!(cache.evict().await.unwrap(), 1);
+        assert!(cache.contains_key("key1").await.unwrap());
+        assert!(!cache.contains_key("key2").await.unwrap());
+        assert!(cache.contains_key("key3").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn can_invalidate_tag_and_prefix() {
+        let cache = new_sqlite(None).await;
+        assert!(cache
+            .insert_with_tags("user:1:profile", "loco", &["user:1"], None)
+            .await
+            .is_ok());
+        assert!(cache.insert("user:1:settings", "loco").await.is_ok());
+        assert!(cache.insert("user:2:profile", "loco").await.is_ok());
+
+        assert!(cache.invalidate_tag("user:1").await.is_ok());
+        assert!(!cache.contains_key("user:1:profile").await.unwrap());
+        assert!(cache.contains_key("user:1:settings").await.unwrap());
+
+        assert!(cache.remove_prefix("user:1:").await.is_ok());
+        assert!(!cache.contains_key("user:1:settings").await.unwrap());
+        assert!(cache.contains_key("user:2:profile").await.unwrap());
+
+        let stats = cache.stats().await.unwrap();
+        assert_eq!(stats.entry_count, Some(1));
+        assert_eq!(stats.size, Some(18));
+    }
+}
@@ -0,0 +1,349 @@
+//! # Two-Tier Cache Driver
+//!
+//! This module implements a cache driver that composes two drivers: a fast,
+//! per-process `local` driver (usually [`super::inmem`]) in front of a
+//! `shared` driver that all the application processes use.
+//!
+//! ## Behavior per operation
+//!
+//! * `get`: Looks up the `local` driver first. On a miss, the value is read
+//!   from the `shared` driver and copied to the `local` driver with the local
+//!   TTL.
+//! * `insert`/`insert_with_expiry`/`insert_with_tags`: Writes through to the
+//!   `shared` driver, then to the `local` driver. Local entries never outlive
+//!   the local TTL, which bounds how stale another process can be.
+//! * `remove`/`remove_prefix`/`clear`: Applied to the `shared` driver, then
+//!   to the `local` driver.
+//! * `invalidate_tag`: Applied to the `shared` driver, then clears the whole
+//!   `local` driver. Entries copied from the `shared` driver by `get` do not
+//!   carry their tags, so they cannot be found by tag locally.
+//!
+//! Hits and misses are counted per tier and exposed with
+//! [`TwoTier::stats`].
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
+
+use super::CacheDriver;
+use crate::cache::{CacheResult, DriverStats};
+
+/// Creates a new two-tier cache driver.
+///
+/// # Returns
+///
+/// A boxed [`CacheDriver`] instance.
+#[must_use]
+pub fn new(
+    local: Box<dyn CacheDriver>,
+    shared: Box<dyn CacheDriver>,
+    local_ttl: Duration,
+) -> Box<dyn CacheDriver> {
+    Box::new(TwoTier::new(local, shared, local_ttl))
+}
+
+/// Hit and miss counters of a single tier.
+#[derive(Debug, Default)]
+pub struct TierStats {
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl TierStats {
+    /// Number of lookups that found a value in this tier.
+    #[must_use]
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// Number of lookups that did not find a value in this tier.
+    #[must_use]
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    fn record(&self, hit: bool) {
+        if hit {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Hit and miss counters of both tiers.
+#[derive(Debug, Default)]
+pub struct TwoTierStats {
+    pub local: TierStats,
+    pub shared: TierStats,
+}
+
+/// Represents the two-tier cache driver.
+pub struct TwoTier {
+    local: Box<dyn CacheDriver>,
+    shared: Box<dyn CacheDriver>,
+    local_ttl: Duration,
+    stats: Arc<TwoTierStats>,
+}
+
+impl TwoTier {
+    /// Constructs a new [`TwoTier`] instance.
+    ///
+    /// Keep the [`TwoTier::stats`] handle before boxing the driver to read
+    /// the counters later, for example from a metrics exporter.
+    #[must_use]
+    pub fn new(
+        local: Box<dyn CacheDriver>,
+        shared: Box<dyn CacheDriver>,
+        local_ttl: Duration,
+    ) -> Self {
+        Self {
+            local,
+            shared,
+            local_ttl,
+            stats: Arc::new(TwoTierStats::default()),
+        }
+    }
+
+    /// Returns the hit and miss counters of both tiers.
+    #[must_use]
+    pub fn stats(&self) -> Arc<TwoTierStats> {
+        self.stats.clone()
+    }
+
+    fn local_ttl(&self, duration: Option<Duration>) -> Duration {
+        duration.map_or(self.local_ttl, |duration| duration.min(self.local_ttl))
+    }
+}
+
+#[async_trait]
+impl CacheDriver for TwoTier {
+    /// Checks if a key exists in either tier.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if the shared driver fails.
+    async fn contains_key(&self, key: &str) -> CacheResult<bool> {
+        if self.local.contains_key(key).await.unwrap_or(false) {
+            return Ok(true);
+        }
+        self.shared.contains_key(key).await
+    }
+
+    /// Retrieves a value from the local tier, falling back to the shared tier
+    /// and populating the local tier on a local miss.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if the shared driver fails.
+    async fn get(&self, key: &str) -> CacheResult<Option<String>> {
+        match self.local.get(key).await {
+            Ok(Some(value)) => {
+                self.stats.local.record(true);
+                return Ok(Some(value));
+            }
+            Ok(None) => self.stats.local.record(false),
+            Err(err) => {
+                self.stats.local.record(false);
+                tracing::debug!(err = err.to_string(), key, "local cache lookup failed");
+            }
+        }
+
+        let value = self.shared.get(key).await?;
+        self.stats.shared.record(value.is_some());
+
+        if let Some(value) = value.as_ref() {
+            if let Err(err) = self
+                .local
+                .insert_with_expiry(key, value, self.local_ttl)
+                .await
+            {
+                tracing::debug!(err = err.to_string(), key, "could not populate local cache");
+            }
+        }
+
+        Ok(value)
+    }
+
+    /// Inserts a key-value pair into the shared tier, then the local tier.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn insert(&self, key: &str, value: &str) -> CacheResult<()> {
+        self.shared.insert(key, value).await?;
+        self.local
+            .insert_with_expiry(key, value, self.local_ttl(None))
+            .await
+    }
+
+    /// Inserts a key-value pair into the shared tier, then the local tier,
+    /// expiring after the specified duration.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn insert_with_expiry(
+        &self,
+        key: &str,
+        value: &str,
+        duration: Duration,
+    ) -> CacheResult<()> {
+        self.shared.insert_with_expiry(key, value, duration).await?;
+        self.local
+            .insert_with_expiry(key, value, self.local_ttl(Some(duration)))
+            .await
+    }
+
+    /// Inserts a tagged key-value pair into the shared tier, then the local
+    /// tier.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn insert_with_tags(
+        &self,
+        key: &str,
+        value: &str,
+        tags: &[&str],
+        duration: Option<Duration>,
+    ) -> CacheResult<()> {
+        self.shared
+            .insert_with_tags(key, value, tags, duration)
+            .await?;
+        self.local
+            .insert_with_tags(key, value, tags, Some(self.local_ttl(duration)))
+            .await
+    }
+
+    /// Removes a key-value pair from both tiers.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn remove(&self, key: &str) -> CacheResult<()> {
+        self.shared.remove(key).await?;
+        self.local.remove(key).await
+    }
+
+    /// Removes all the keys that start with the given prefix from both tiers.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn remove_prefix(&self, prefix: &str) -> CacheResult<()> {
+        self.shared.remove_prefix(prefix).await?;
+        self.local.remove_prefix(prefix).await
+    }
+
+    /// Removes all the keys that were inserted with the given tag from the
+    /// shared tier, and clears the local tier, which does not know the tags
+    /// of the entries it copied from the shared tier.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn invalidate_tag(&self, tag: &str) -> CacheResult<()> {
+        self.shared.invalidate_tag(tag).await?;
+        self.local.clear().await
+    }
+
+    /// Clears all key-value pairs from both tiers.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn clear(&self) -> CacheResult<()> {
+        self.shared.clear().await?;
+        self.local.clear().await
+    }
+
+    /// Returns the statistics of the shared tier, which holds every entry.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn stats(&self) -> CacheResult<DriverStats> {
+        self.shared.stats().await
+    }
+}
+
+#[cfg(all(test, feature = "cache_inmem"))]
+mod tests {
+
+    use super::*;
+    use crate::cache::drivers::inmem;
+
+    #[tokio::test]
+    async fn can_write_through_and_read_local() {
+        let driver = TwoTier::new(inmem::new(), inmem::new(), Duration::from_secs(5));
+        let stats = driver.stats();
+
+        assert!(driver.insert("key", "loco").await.is_ok());
+        assert_eq!(
+            driver.shared.get("key").await.unwrap(),
+            Some("loco".to_string())
+        );
+        assert_eq!(driver.get("key").await.unwrap(), Some("loco".to_string()));
+
+        assert_eq!(stats.local.hits(), 1);
+        assert_eq!(stats.shared.hits(), 0);
+    }
+
+    #[tokio::test]
+    async fn can_populate_local_from_shared() {
+        let driver = TwoTier::new(inmem::new(), inmem::new(), Duration::from_secs(5));
+        let stats = driver.stats();
+
+        assert!(driver.shared.insert("key", "loco").await.is_ok());
+
+        assert_eq!(driver.get("key").await.unwrap(), Some("loco".to_string()));
+        assert_eq!(stats.local.misses(), 1);
+        assert_eq!(stats.shared.hits(), 1);
+
+        assert_eq!(
+            driver.local.get("key").await.unwrap(),
+            Some("loco".to_string())
+        );
+        assert_eq!(driver.get("key").await.unwrap(), Some("loco".to_string()));
+        assert_eq!(stats.local.hits(), 1);
+
+        assert_eq!(driver.get("not-found").await.unwrap(), None);
+        assert_eq!(stats.shared.misses(), 1);
+    }
+
+    #[tokio::test]
+    async fn can_invalidate_both_tiers() {
+        let driver = TwoTier::new(inmem::new(), inmem::new(), Duration::from_secs(5));
+
+        assert!(driver
+            .insert_with_tags("key", "loco", &["tag"], None)
+            .await
+            .is_ok());
+        assert!(driver.invalidate_tag("tag").await.is_ok());
+
+        assert!(!driver.local.contains_key("key").await.unwrap());
+        assert!(!driver.shared.contains_key("key").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn invalidate_tag_drops_entries_copied_from_shared() {
+        let driver = TwoTier::new(inmem::new(), inmem::new(), Duration::from_secs(5));
+
+        // written by another process, then read by this one
+        assert!(driver
+            .shared
+            .insert_with_tags("key", "loco", &["tag"], None)
+            .await
+            .is_ok());
+        assert_eq!(driver.get("key").await.unwrap(), Some("loco".to_string()));
+
+        assert!(driver.invalidate_tag("tag").await.is_ok());
+        assert_eq!(driver.get("key").await.unwrap(), None);
+    }
+}
@@ -3,7 +3,13 @@
 //! This module provides a generic cache interface for various cache drivers.
 pub mod drivers;
 
-use std::{future::Future, time::Duration};
+use std::{
+    future::Future,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use serde::Serialize;
 
 use self::drivers::CacheDriver;
 use crate::Result as LocoResult;
@@ -18,17 +24,69 @@ pub enum CacheError {
 
 pub type CacheResult<T> = std::result::Result<T, CacheError>;
 
+/// Statistics reported by a [`CacheDriver`] about its storage. Fields are
+/// `None` when the driver cannot tell.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct DriverStats {
+    /// Number of entries currently stored.
+    pub entry_count: Option<u64>,
+    /// Approximate size of the stored entries, in bytes.
+    pub size: Option<u64>,
+    /// Number of entries removed by the driver because they expired or the
+    /// cache was full.
+    pub evictions: Option<u64>,
+}
+
+/// Cache statistics, combining the counters of this [`Cache`] instance with
+/// the [`DriverStats`] of its driver.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct CacheStats {
+    /// Number of lookups that found a value.
+    pub hits: u64,
+    /// Number of lookups that did not find a value.
+    pub misses: u64,
+    /// Number of inserted values.
+    pub inserts: u64,
+    #[serde(flatten)]
+    pub driver: DriverStats,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    inserts: AtomicU64,
+}
+
+impl Counters {
+    fn lookup(&self, hit: bool) {
+        if hit {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn insert(&self) {
+        self.inserts.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
 /// Represents a cache instance
 pub struct Cache {
     /// The cache driver used for underlying operations
     pub driver: Box<dyn CacheDriver>,
+    counters: Counters,
 }
 
 impl Cache {
     /// Creates a new cache instance with the specified cache driver.
     #[must_use]
     pub fn new(driver: Box<dyn CacheDriver>) -> Self {
-        Self { driver }
+        Self {
+            driver,
+            counters: Counters::default(),
+        }
     }
 
     /// Checks if a key exists in the cache.
@@ -65,7 +123,9 @@ impl Cache {
     /// A [`CacheResult`] containing an `Option` representing the retrieved
     /// value.
     pub async fn get(&self, key: &str) -> CacheResult<Option<String>> {
-        self.driver.get(key).await
+        let value = self.driver.get(key).await?;
+        self.counters.lookup(value.is_some());
+        Ok(value)
     }
 
     /// Inserts a key-value pair into the cache.
@@ -84,7 +144,9 @@ impl Cache {
     ///
     /// A [`CacheResult`] indicating the success of the operation.
     pub async fn insert(&self, key: &str, value: &str) -> CacheResult<()> {
-        self.driver.insert(key, value).await
+        self.driver.insert(key, value).await?;
+        self.counters.insert();
+        Ok(())
     }
 
     /// Inserts a key-value pair into the cache with an expiry after
@@ -110,7 +172,64 @@ impl Cache {
         value: &str,
         duration: Duration,
     ) -> CacheResult<()> {
-        self.driver.insert_with_expiry(key, value, duration).await
+        self.driver.insert_with_expiry(key, value, duration).await?;
+        self.counters.insert();
+        Ok(())
+    }
+
+    /// Inserts a key-value pair into the cache and attaches the given tags
+    /// to it, so it can later be removed with [`Cache::invalidate_tag`].
+    ///
+    /// # Example
+    /// ```
+    /// use loco_rs::cache::{self, CacheResult};
+    ///
+    /// pub async fn insert_with_tags() -> CacheResult<()> {
+    ///     let cache = cache::Cache::new(cache::drivers::inmem::new());
+    ///     cache.insert_with_tags("user:1:profile", "value", &["user:1"]).await
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// A [`CacheResult`] indicating the success of the operation.
+    pub async fn insert_with_tags(&self, key: &str, value: &str, tags: &[&str]) -> CacheResult<()> {
+        self.driver.insert_with_tags(key, value, tags, None).await?;
+        self.counters.insert();
+        Ok(())
+    }
+
+    /// Inserts a key-value pair into the cache with an expiry after the
+    /// provided duration and attaches the given tags to it.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use loco_rs::cache::{self, CacheResult};
+    ///
+    /// pub async fn insert_with_tags_and_expiry() -> CacheResult<()> {
+    ///     let cache = cache::Cache::new(cache::drivers::inmem::new());
+    ///     cache
+    ///         .insert_with_tags_and_expiry("key", "value", &["user:1"], Duration::from_secs(300))
+    ///         .await
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// A [`CacheResult`] indicating the success of the operation.
+    pub async fn insert_with_tags_and_expiry(
+        &self,
+        key: &str,
+        value: &str,
+        tags: &[&str],
+        duration: Duration,
+    ) -> CacheResult<()> {
+        self.driver
+            .insert_with_tags(key, value, tags, Some(duration))
+            .await?;
+        self.counters.insert();
+        Ok(())
     }
 
     /// Retrieves the value associated with the given key from the cache,
@@ -138,11 +257,11 @@ impl Cache {
     where
         F: Future<Output = LocoResult<String>> + Send,
     {
-        if let Some(value) = self.driver.get(key).await? {
+        if let Some(value) = self.get(key).await? {
             Ok(value)
         } else {
             let value = f.await?;
-            self.driver.insert(key, &value).await?;
+            self.insert(key, &value).await?;
             Ok(value)
         }
     }
@@ -178,13 +297,11 @@ impl Cache {
     where
         F: Future<Output = LocoResult<String>> + Send,
     {
-        if let Some(value) = self.driver.get(key).await? {
+        if let Some(value) = self.get(key).await? {
             Ok(value)
         } else {
             let value = f.await?;
-            self.driver
-                .insert_with_expiry(key, &value, duration)
-                .await?;
+            self.insert_with_expiry(key, &value, duration).await?;
             Ok(value)
         }
     }
@@ -208,6 +325,69 @@ impl Cache {
         self.driver.remove(key).await
     }
 
+    /// Removes all the keys that start with the given prefix.
+    ///
+    /// # Example
+    /// ```
+    /// use loco_rs::cache::{self, CacheResult};
+    ///
+    /// pub async fn remove_prefix() -> CacheResult<()> {
+    ///     let cache = cache::Cache::new(cache::drivers::inmem::new());
+    ///     cache.remove_prefix("user:1:").await
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// A [`CacheResult`] indicating the success of the operation.
+    pub async fn remove_prefix(&self, prefix: &str) -> CacheResult<()> {
+        self.driver.remove_prefix(prefix).await
+    }
+
+    /// Removes all the keys that were inserted with the given tag.
+    ///
+    /// # Example
+    /// ```
+    /// use loco_rs::cache::{self, CacheResult};
+    ///
+    /// pub async fn invalidate_tag() -> CacheResult<()> {
+    ///     let cache = cache::Cache::new(cache::drivers::inmem::new());
+    ///     cache.invalidate_tag("user:1").await
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// A [`CacheResult`] indicating the success of the operation.
+    pub async fn invalidate_tag(&self, tag: &str) -> CacheResult<()> {
+        self.driver.invalidate_tag(tag).await
+    }
+
+    /// Returns the hits, misses and inserts counted by this instance, along
+    /// with the statistics reported by the driver.
+    ///
+    /// # Example
+    /// ```
+    /// use loco_rs::cache::{self, CacheResult, CacheStats};
+    ///
+    /// pub async fn stats() -> CacheResult<CacheStats> {
+    ///     let cache = cache::Cache::new(cache::drivers::inmem::new());
+    ///     cache.stats().await
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// A [`CacheResult`] containing the cache statistics.
+    pub async fn stats(&self) -> CacheResult<CacheStats> {
+        Ok(CacheStats {
+            hits: self.counters.hits.load(Ordering::Relaxed),
+            misses: self.counters.misses.load(Ordering::Relaxed),
+            inserts: self.counters.inserts.load(Ordering::Relaxed),
+            driver: self.driver.stats().await?,
+        })
+    }
+
     /// Clears all key-value pairs from the cache.
     ///
     /// # Example
@@ -252,4 +432,48 @@ mod tests {
             Some("loco-cache-value".to_string())
         );
     }
+
+    #[tokio::test]
+    async fn can_invalidate_tag() {
+        let app_ctx = tests_cfg::app::get_app_context().await;
+
+        app_ctx
+            .cache
+            .insert_with_tags("user:1:profile", "profile", &["user:1"])
+            .await
+            .unwrap();
+        app_ctx
+            .cache
+            .insert_with_tags("user:2:profile", "profile", &["user:2"])
+            .await
+            .unwrap();
+
+        app_ctx.cache.invalidate_tag("user:1").await.unwrap();
+
+        assert_eq!(app_ctx.cache.get("user:1:profile").await.unwrap(), None);
+        assert_eq!(
+            app_ctx.cache.get("user:2:profile").await.unwrap(),
+            Some("profile".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn can_track_stats() {
+        let app_ctx = tests_cfg::app::get_app_context().await;
+
+        app_ctx.cache.insert("key", "value").await.unwrap();
+        app_ctx.cache.get("key").await.unwrap();
+        app_ctx.cache.get("not-found").await.unwrap();
+        app_ctx
+            .cache
+            .get_or_insert("key2", async { Ok("value".to_string()) })
+            .await
+            .unwrap();
+
+        let stats = app_ctx.cache.stats().await.unwrap();
+        assert_eq!(stats.hits, 1);
+        assert_eq!(stats.misses, 2);
+        assert_eq!(stats.inserts, 2);
+        assert_eq!(stats.driver.entry_count, Some(2));
+    }
 }
@@ -30,11 +30,13 @@ cfg_if::cfg_if! {
     feature = "with-db"
 ))]
 use std::process::exit;
-use std::{collections::BTreeMap, path::PathBuf};
+use std::{collections::BTreeMap, io::Write, path::PathBuf};
 
 use clap::{ArgAction, Parser, Subcommand, ValueHint};
 use colored::Colorize;
 use duct::cmd;
+use futures_util::{StreamExt, TryStreamExt};
+use tokio_util::io::ReaderStream;
 
 #[cfg(any(feature = "bg_redis", feature = "bg_pg", feature = "bg_sqlt"))]
 use crate::bgworker::JobStatus;
@@ -47,7 +49,9 @@ use crate::{
     config::Config,
     controller,
     environment::{resolve_from_env, Environment, DEFAULT_ENVIRONMENT},
-    logger, task, Error,
+    logger,
+    storage::StorageError,
+    task, Error,
 };
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -123,6 +127,16 @@ enum Commands {
         #[command(subcommand)]
         command: JobsCommands,
     },
+    /// Inspect and manage the application cache
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommands,
+    },
+    /// Inspect and manage the application storage
+    Storage {
+        #[command(subcommand)]
+        command: StorageCommands,
+    },
     /// Run the scheduler
     Scheduler {
         /// Run a specific job by its name.
@@ -604,6 +618,99 @@ enum JobsCommands {
     },
 }
 
+#[derive(Subcommand)]
+enum CacheCommands {
+    /// Show hits, misses, inserts, evictions, entry count and size.
+    Stats,
+    /// Print the value stored under the given key.
+    Get {
+        /// The cache key.
+        key: String,
+    },
+    /// Delete the given key.
+    Delete {
+        /// The cache key.
+        key: String,
+    },
+    /// Delete all the keys.
+    Clear,
+}
+
+#[derive(Subcommand)]
+enum StorageCommands {
+    /// List the objects under the given prefix.
+    Ls {
+        /// The path prefix.
+        #[arg(default_value = "")]
+        prefix: PathBuf,
+        /// Use the given store instead of the storage strategy.
+        #[arg(short, long)]
+        store: Option<String>,
+    },
+    /// Print the content of an object.
+    Cat {
+        /// The object path.
+        path: PathBuf,
+        /// Use the given store instead of the storage strategy, printing the
+        /// content as stored (e.g. encrypted).
+        #[arg(short, long)]
+        store: Option<String>,
+    },
+    /// Upload a local file.
+    Put {
+        /// The local file to upload.
+        #[arg(value_hint = ValueHint::FilePath)]
+        file: PathBuf,
+        /// The object path, the file name when not given.
+        path: Option<PathBuf>,
+        /// Use the given store instead of the storage strategy.
+        #[arg(short, long)]
+        store: Option<String>,
+    },
+    /// Delete an object.
+    Rm {
+        /// The object path.
+        path: PathBuf,
+        /// Use the given store instead of the storage strategy.
+        #[arg(short, long)]
+        store: Option<String>,
+    },
+    /// Copy an object.
+    Cp {
+        /// The source path.
+        from: PathBuf,
+        /// The destination path.
+        to: PathBuf,
+        /// Use the given store instead of the storage strategy.
+        #[arg(short, long)]
+        store: Option<String>,
+    },
+    /// Move an object.
+    Mv {
+        /// The source path.
+        from: PathBuf,
+        /// The destination path.
+        to: PathBuf,
+        /// Use the given store instead of the storage strategy.
+        #[arg(short, long)]
+        store: Option<String>,
+    },
+    /// Compare the objects under a prefix across stores, and copy the
+    /// missing or diverging ones with `--repair`.
+    Sync {
+        /// The path prefix.
+        #[arg(default_value = "")]
+        prefix: PathBuf,
+        /// The stores to compare, the first one being the source of truth.
+        /// Defaults to all the stores.
+        #[arg(long, value_delimiter = ',')]
+        stores: Vec<String>,
+        /// Copy the missing and diverging objects.
+        #[arg(long, action)]
+        repair: bool,
+    },
+}
+
 /// Parse a single key-value pair
 fn parse_key_val<T, U>(
     s: &str,
@@ -748,6 +855,14 @@ pub async fn main<H: Hooks, M: MigratorTrait>() -> crate::Result<()> {
             let app_context = create_context::<H>(&environment, config).await?;
             run_task::<H>(&app_context, name.as_ref(), &vars).await?;
         }
+        Commands::Cache { command } => {
+            let app_context = create_context::<H>(&environment, config).await?;
+            handle_cache_command(command, &app_context).await?;
+        }
+        Commands::Storage { command } => {
+            let app_context = create_context::<H>(&environment, config).await?;
+            handle_storage_command(command, &app_context).await?;
+        }
         Commands::Scheduler {
             name,
             config_path,
@@ -885,6 +1000,14 @@ pub async fn main<H: Hooks>() -> crate::Result<()> {
         Commands::Jobs { command } => {
             handle_job_command::<H>(command, &environment, config).await?
         }
+        Commands::Cache { command } => {
+            let app_context = create_context::<H>(&environment, config).await?;
+            handle_cache_command(command, &app_context).await?;
+        }
+        Commands::Storage { command } => {
+            let app_context = create_context::<H>(&environment, config).await?;
+            handle_storage_command(command, &app_context).await?;
+        }
         Commands::Scheduler {
             name,
             config_path,
@@ -1031,6 +1154,158 @@ fn create_root_span(environment: &Environment) -> tracing::Span {
     tracing::span!(tracing::Level::DEBUG, "app", environment = %environment)
 }
 
+async fn handle_cache_command(command: CacheCommands, ctx: &AppContext) -> crate::Result<()> {
+    if ctx.cache.driver.is_process_local() {
+        return Err(Error::string(
+            "the configured cache driver keeps its entries in the memory of each app process, \
+             which the CLI cannot reach; use a shared driver such as redis to manage it from \
+             the CLI",
+        ));
+    }
+
+    match command {
+        CacheCommands::Stats => {
+            let stats = ctx.cache.stats().await?;
+            let or_unknown =
+                |value: Option<u64>| value.map_or_else(|| "-".to_string(), |v| v.to_string());
+            println!(
+                "{:<12} {}",
+                "evictions".bold(),
+                or_unknown(stats.driver.evictions)
+            );
+            println!(
+                "{:<12} {}",
+                "entries".bold(),
+                or_unknown(stats.driver.entry_count)
+            );
+            println!("{:<12} {}", "size".bold(), or_unknown(stats.driver.size));
+            println!(
+                "{}",
+                "hits, misses and inserts are counted by each app process and are not shown"
+                    .dimmed()
+            );
+        }
+        CacheCommands::Get { key } => match ctx.cache.get(&key).await? {
+            Some(value) => println!("{value}"),
+            None => println!("{}", format!("key `{key}` not found").red()),
+        },
+        CacheCommands::Delete { key } => {
+            ctx.cache.remove(&key).await?;
+            println!("key `{key}` deleted");
+        }
+        CacheCommands::Clear => {
+            ctx.cache.clear().await?;
+            println!("cache cleared");
+        }
+    }
+    Ok(())
+}
+
+async fn handle_storage_command(command: StorageCommands, ctx: &AppContext) -> crate::Result<()> {
+    let storage = &ctx.storage;
+    match command {
+        StorageCommands::Ls { prefix, store } => {
+            let list = match store {
+                Some(store) => storage.as_store_err(&store)?.list(&prefix).await?,
+                None => storage.list(&prefix).await?,
+            };
+            let objects: Vec<_> = list.try_collect().await?;
+            for object in &objects {
+                let last_modified = object
+                    .last_modified
+                    .map_or_else(|| "-".to_string(), |date| date.to_rfc3339());
+                println!(
+                    "{:>12} {:<25} {}",
+                    object.size,
+                    last_modified,
+                    object.path.display()
+                );
+            }
+            println!("{}", format!("{} object(s)", objects.len()).dimmed());
+        }
+        StorageCommands::Cat { path, store } => {
+            let mut stream = match store {
+                Some(store) => {
+                    storage
+                        .as_store_err(&store)?
+                        .download_stream(&path, None)
+                        .await?
+                }
+                None => storage.download_stream(&path, None).await?,
+            };
+            let mut stdout = std::io::stdout();
+            while let Some(chunk) = stream.try_next().await? {
+                stdout.write_all(&chunk)?;
+            }
+            stdout.flush()?;
+        }
+        StorageCommands::Put { file, path, store } => {
+            let path = match path {
+                Some(path) => path,
+                None => PathBuf::from(file.file_name().ok_or_else(|| {
+                    Error::string(&format!("`{}` is not a file", file.display()))
+                })?),
+            };
+            let reader = tokio::fs::File::open(&file).await?;
+            match store {
+                Some(store) => {
+                    let stream = ReaderStream::new(reader)
+                        .map_err(|err| StorageError::Any(Box::new(err)))
+                        .boxed();
+                    storage
+                        .as_store_err(&store)?
+                        .upload_stream(&path, stream)
+                        .await?;
+                }
+                None => storage.upload_reader(&path, reader).await?,
+            }
+            println!("uploaded `{}` to `{}`", file.display(), path.display());
+        }
+        StorageCommands::Rm { path, store } => {
+            match store {
+                Some(store) => storage.as_store_err(&store)?.delete(&path).await?,
+                None => storage.delete(&path).await?,
+            }
+            println!("`{}` deleted", path.display());
+        }
+        StorageCommands::Cp { from, to, store } => {
+            match store {
+                Some(store) => storage.as_store_err(&store)?.copy(&from, &to).await?,
+                None => storage.copy(&from, &to).await?,
+            }
+            println!("`{}` copied to `{}`", from.display(), to.display());
+        }
+        StorageCommands::Mv { from, to, store } => {
+            match store {
+                Some(store) => storage.as_store_err(&store)?.rename(&from, &to).await?,
+                None => storage.rename(&from, &to).await?,
+            }
+            println!("`{}` moved to `{}`", from.display(), to.display());
+        }
+        StorageCommands::Sync {
+            prefix,
+            stores,
+            repair,
+        } => {
+            let stores = if stores.is_empty() {
+                storage.stores.keys().cloned().collect()
+            } else {
+                stores
+            };
+            let report = if repair {
+                storage.resync(&prefix, &stores).await?
+            } else {
+                storage.verify(&prefix, &stores).await?
+            };
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            if !repair && !report.is_synced() {
+                println!("{}", "stores are out of sync, run with --repair".yellow());
+            }
+        }
+    }
+    Ok(())
+}
+
 #[cfg(any(feature = "bg_redis", feature = "bg_pg", feature = "bg_sqlt"))]
 async fn handle_job_command<H: Hooks>(
     command: JobsCommands,
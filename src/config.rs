@@ -32,7 +32,13 @@ use serde::{Deserialize, Serialize};
 use serde_json::json;
 use tracing::info;
 
-use crate::{controller::middleware, environment::Environment, logger, scheduler, Error, Result};
+use crate::{
+    controller::middleware,
+    environment::Environment,
+    logger, scheduler,
+    storage::strategies::{backup, mirror},
+    Error, Result,
+};
 
 static DEFAULT_FOLDER: OnceLock<PathBuf> = OnceLock::new();
 
@@ -54,6 +60,7 @@ pub struct Config {
     #[serde(default)]
     pub workers: Workers,
     pub mailer: Option<Mailer>,
+    pub storage: Option<StorageConfig>,
     pub initializers: Option<Initializers>,
 
     /// Custom app settings
@@ -466,14 +473,263 @@ pub enum WorkerMode {
 ///     port: 1025
 ///     secure: false
 /// ```
+///
+/// Instead of SMTP, mails can be written to `.eml` files, or piped to
+/// `sendmail`:
+/// ```yaml
+/// mailer:
+///   file:
+///     enable: true
+///     path: tmp/mails
+/// ```
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Mailer {
     pub smtp: Option<SmtpMailer>,
 
+    /// Write mails as `.eml` files to a folder.
+    pub file: Option<FileMailer>,
+
+    /// Pipe mails to the `sendmail` command.
+    pub sendmail: Option<SendmailMailer>,
+
+    /// Record every delivery, sent or failed.
+    pub delivery_log: Option<DeliveryLogConfig>,
+
+    /// Sign outgoing mails with DKIM.
+    pub dkim: Option<DkimConfig>,
+
     #[serde(default)]
     pub stub: bool,
 }
 
+/// Storage configuration
+///
+/// Declares named stores and the strategy used to build
+/// `AppContext::storage`. Credentials are usually read from the environment,
+/// either with `get_env` or, for S3, by omitting them and letting the driver
+/// read the standard `AWS_*` variables.
+///
+/// Example (production):
+/// ```yaml
+/// # config/production.yaml
+/// storage:
+///   stores:
+///     local:
+///       kind: Local
+///       root: storage
+///     s3:
+///       kind: S3
+///       bucket: my-bucket
+///       region: us-east-1
+///       key_id: {{ get_env(name="AWS_ACCESS_KEY_ID") }}
+///       secret_key: {{ get_env(name="AWS_SECRET_ACCESS_KEY") }}
+///   strategy:
+///     kind: Mirror
+///     primary: local
+///     secondaries:
+///       - s3
+///     failure_mode: AllowMirrorFailure
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct StorageConfig {
+    /// The stores, by name.
+    pub stores: BTreeMap<String, StoreConfig>,
+
+    /// The strategy used across the stores. Can be omitted when there is a
+    /// single store.
+    pub strategy: Option<StorageStrategyConfig>,
+
+    /// Signing of temporary URLs for stores without native presigning.
+    pub signed_urls: Option<SignedUrlsConfig>,
+
+    /// Image variants, by name. Requires the `storage_image` feature
+    #[serde(default)]
+    pub variants: BTreeMap<String, ImageVariantConfig>,
+}
+
+/// An image variant, see `storage::variants`.
+///
+/// Variants are stored next to the original image, e.g. `products/1.jpg`
+/// gets a `products/1.thumb.webp` variant.
+///
+/// Example (development):
+/// ```yaml
+/// storage:
+///   variants:
+///     thumb:
+///       width: 200
+///       height: 200
+///       fit: Cover
+///       format: WebP
+///     banner:
+///       width: 1200
+///       crop:
+///         x: 0
+///         y: 0
+///         width: 1200
+///         height: 400
+///       format: Jpeg
+///       quality: 80
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ImageVariantConfig {
+    /// The target width. When only one of `width` and `height` is given, the
+    /// aspect ratio is kept.
+    pub width: Option<u32>,
+    /// The target height.
+    pub height: Option<u32>,
+    /// How the image fits the target size.
+    #[serde(default)]
+    pub fit: ImageFit,
+    /// A region cropped from the original before resizing.
+    pub crop: Option<ImageCrop>,
+    /// The output format. Defaults to the format of the original.
+    pub format: Option<ImageFormat>,
+    /// The JPEG quality, from 1 to 100.
+    pub quality: Option<u8>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub enum ImageFit {
+    /// Scales the image to fit within the target size, keeping its aspect
+    /// ratio.
+    #[default]
+    Contain,
+    /// Scales and crops the image to fill the target size.
+    Cover,
+    /// Stretches the image to the target size.
+    Fill,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ImageCrop {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    WebP,
+}
+
+/// Signed URLs configuration, see `storage::signed_url`.
+///
+/// Example (development):
+/// ```yaml
+/// storage:
+///   signed_urls:
+///     secret: {{ get_env(name="STORAGE_SIGNING_SECRET") }}
+///     base_url: http://localhost:5150/_storage
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SignedUrlsConfig {
+    /// The HMAC secret used to sign the URLs.
+    pub secret: String,
+    /// The public URL where `storage::signed_url::routes` are mounted.
+    pub base_url: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "kind")]
+pub enum StoreConfig {
+    /// Local file system store
+    Local(LocalStoreConfig),
+    /// In-memory store, mostly useful for tests
+    Mem,
+    /// AWS S3 (or S3 compatible) store. Requires the `storage_aws_s3` feature
+    S3(S3StoreConfig),
+    /// Azure blob store. Requires the `storage_azure` feature
+    Azure(AzureStoreConfig),
+    /// Google cloud store. Requires the `storage_gcp` feature
+    Gcp(GcpStoreConfig),
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LocalStoreConfig {
+    /// The folder under which all the paths are stored.
+    pub root: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct S3StoreConfig {
+    pub bucket: String,
+    pub region: String,
+    /// Access key id. When omitted with `secret_key`, credentials are loaded
+    /// from the environment.
+    pub key_id: Option<String>,
+    pub secret_key: Option<String>,
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AzureStoreConfig {
+    pub container: String,
+    pub account_name: String,
+    pub access_key: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct GcpStoreConfig {
+    pub bucket: String,
+    /// Path to the service account credentials file.
+    pub credential_path: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "kind")]
+pub enum StorageStrategyConfig {
+    /// All the operations go to a single store.
+    Single { store: String },
+    /// See [`mirror::MirrorStrategy`].
+    Mirror {
+        primary: String,
+        #[serde(default)]
+        secondaries: Vec<String>,
+        failure_mode: mirror::FailureMode,
+        /// Upload content found in a secondary back to the stores that
+        /// failed to return it.
+        #[serde(default)]
+        read_repair: bool,
+    },
+    /// See [`backup::BackupStrategy`].
+    Backup {
+        primary: String,
+        #[serde(default)]
+        secondaries: Vec<String>,
+        failure_mode: backup::FailureMode,
+    },
+    /// Encrypts the content of the inner strategy with AES-256-GCM. Requires
+    /// the `storage_encryption` feature.
+    ///
+    /// ```yaml
+    /// strategy:
+    ///   kind: Encrypted
+    ///   key_id: "2024-10"
+    ///   keys:
+    ///     "2024-10": {{ get_env(name="STORAGE_KEY_2024_10") }}
+    ///     "2024-01": {{ get_env(name="STORAGE_KEY_2024_01") }}
+    ///   strategy:
+    ///     kind: Mirror
+    ///     primary: local
+    ///     secondaries:
+    ///       - s3
+    ///     failure_mode: MirrorAll
+    /// ```
+    Encrypted {
+        /// The id of the key used to encrypt new content.
+        key_id: String,
+        /// The 32 bytes keys, hex encoded, by id. Content encrypted with any
+        /// of them can be decrypted.
+        keys: BTreeMap<String, String>,
+        /// The strategy storing the encrypted content.
+        strategy: Box<StorageStrategyConfig>,
+    },
+}
+
 /// Initializers configuration
 ///
 /// Example (development): To configure settings for oauth2 or custom view
@@ -504,6 +760,82 @@ pub struct SmtpMailer {
     pub hello_name: Option<String>,
 }
 
+/// File mailer configuration structure.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FileMailer {
+    pub enable: bool,
+    /// The folder the `.eml` files are written to.
+    pub path: PathBuf,
+}
+
+/// Sendmail mailer configuration structure.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SendmailMailer {
+    pub enable: bool,
+    /// The sendmail command, `sendmail` from the `PATH` by default.
+    pub command: Option<String>,
+}
+
+/// DKIM signing configuration.
+///
+/// ```yaml
+/// mailer:
+///   dkim:
+///     selector: mail
+///     domain: example.com
+///     private_key_path: config/dkim.pem
+/// ```
+///
+/// The public key is published in the `mail._domainkey.example.com` TXT
+/// record.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DkimConfig {
+    /// The selector, the first label of the DNS record.
+    pub selector: String,
+    /// The signing domain, usually the domain of the `From` address.
+    pub domain: String,
+    /// An RSA key in PKCS#1 PEM, or a base64 encoded Ed25519 key.
+    pub private_key_path: PathBuf,
+    #[serde(default)]
+    pub algorithm: DkimAlgorithm,
+}
+
+/// The DKIM signing algorithm.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DkimAlgorithm {
+    #[default]
+    Rsa,
+    Ed25519,
+}
+
+/// Where the mailer records deliveries.
+///
+/// ```yaml
+/// mailer:
+///   delivery_log:
+///     kind: Storage
+///     prefix: mail_deliveries
+/// ```
+///
+/// `kind: Database` writes to the `mail_deliveries` table instead, created by
+/// `loco_rs::schema::create_mail_deliveries_table` in a migration.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "kind")]
+pub enum DeliveryLogConfig {
+    /// JSON files in the application storage, under `prefix`.
+    Storage {
+        #[serde(default = "default_delivery_log_prefix")]
+        prefix: String,
+    },
+    /// Rows of the `mail_deliveries` table.
+    Database,
+}
+
+fn default_delivery_log_prefix() -> String {
+    "mail_deliveries".to_string()
+}
+
 /// Authentication details for the mailer
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct MailerAuth {
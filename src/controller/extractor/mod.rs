@@ -1 +1,2 @@
+pub mod upload;
 pub mod validate;
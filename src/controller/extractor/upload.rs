@@ -0,0 +1,473 @@
+//! # File Upload Extractor
+//!
+//! [`Upload`] reads a `multipart/form-data` request and streams every file
+//! part straight into `AppContext::storage` under a generated key, without
+//! buffering the files in memory.
+//!
+//! The content type of each file is sniffed from its first bytes rather than
+//! trusted from the client, and checked against the allowed types of the
+//! [`UploadPolicy`] together with the size limits. Files with no known
+//! signature are only taken as `text/plain` when the client declared a text
+//! type and the content is valid UTF-8.
+//!
+//! ```rust
+//! use loco_rs::{controller::extractor::upload::Upload, prelude::*};
+//!
+//! async fn upload(upload: Upload) -> Result<Response> {
+//!     format::json(upload.files)
+//! }
+//! ```
+//!
+//! Note that the whole request is still subject to the request body limit
+//! (see the `limit_payload` middleware).
+use std::{collections::BTreeMap, marker::PhantomData, path::PathBuf};
+
+use axum::{
+    extract::{FromRequest, Multipart, Request},
+    http::StatusCode,
+};
+use bytes::{Bytes, BytesMut};
+use futures_util::{stream, StreamExt};
+use serde::Serialize;
+use tokio::sync::mpsc;
+
+use crate::{
+    app::AppContext,
+    controller::ErrorDetail,
+    storage::{drivers::BytesStream, StorageError, StorageResult},
+    Error,
+};
+
+/// The number of bytes read before sniffing the content type of a file.
+const SNIFF_LEN: usize = 8192;
+
+/// Limits and naming of the files accepted by [`Upload`].
+///
+/// Implement it on your own type to change the defaults:
+///
+/// ```rust
+/// use loco_rs::controller::extractor::upload::{Upload, UploadPolicy};
+///
+/// pub struct Avatar;
+///
+/// impl UploadPolicy for Avatar {
+///     fn max_file_size() -> u64 {
+///         1024 * 1024
+///     }
+///
+///     fn max_files() -> usize {
+///         1
+///     }
+///
+///     fn allowed_content_types() -> &'static [&'static str] {
+///         &["image/png", "image/jpeg", "image/webp"]
+///     }
+///
+///     fn prefix() -> &'static str {
+///         "avatars"
+///     }
+/// }
+///
+/// type AvatarUpload = Upload<Avatar>;
+/// ```
+pub trait UploadPolicy: Send + Sync {
+    /// The maximum size of a single file, in bytes. Defaults to 10MB.
+    #[must_use]
+    fn max_file_size() -> u64 {
+        10 * 1024 * 1024
+    }
+
+    /// The maximum number of files in a request. Defaults to 10.
+    #[must_use]
+    fn max_files() -> usize {
+        10
+    }
+
+    /// The allowed content types, such as `image/png` or `image/*`. An empty
+    /// list, the default, allows every type.
+    #[must_use]
+    fn allowed_content_types() -> &'static [&'static str] {
+        &[]
+    }
+
+    /// The folder of the generated keys. Defaults to `uploads`.
+    #[must_use]
+    fn prefix() -> &'static str {
+        "uploads"
+    }
+
+    /// Generates the storage key of an uploaded file. Defaults to
+    /// `<prefix>/<uuid>.<extension>`, the extension being derived from the
+    /// sniffed content type when known.
+    #[must_use]
+    fn key(_file_name: &str, extension: Option<&str>) -> PathBuf {
+        let name = uuid::Uuid::new_v4().to_string();
+        let name = match extension {
+            Some(ext) => format!("{name}.{ext}"),
+            None => name,
+        };
+        PathBuf::from(Self::prefix()).join(name)
+    }
+}
+
+/// The default [`UploadPolicy`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultUploadPolicy;
+
+impl UploadPolicy for DefaultUploadPolicy {}
+
+/// The metadata of a file stored by [`Upload`].
+#[derive(Debug, Clone, Serialize)]
+pub struct UploadedFile {
+    /// The name of the form field.
+    pub field_name: String,
+    /// The file name sent by the client.
+    pub file_name: String,
+    /// The storage key the file was stored under.
+    pub key: PathBuf,
+    /// The sniffed content type.
+    pub content_type: String,
+    /// The size in bytes.
+    pub size: u64,
+}
+
+/// Extracts a `multipart/form-data` request, storing its files.
+///
+/// Fields without a file name are collected as text in `fields`, and are
+/// subject to the same size limit as the files.
+#[derive(Debug)]
+pub struct Upload<P: UploadPolicy = DefaultUploadPolicy> {
+    pub files: Vec<UploadedFile>,
+    pub fields: BTreeMap<String, String>,
+    policy: PhantomData<P>,
+}
+
+impl<P: UploadPolicy> FromRequest<AppContext> for Upload<P> {
+    type Rejection = Error;
+
+    async fn from_request(req: Request, ctx: &AppContext) -> Result<Self, Self::Rejection> {
+        let mut multipart = Multipart::from_request(req, ctx)
+            .await
+            .map_err(|err| Error::BadRequest(err.body_text()))?;
+
+        let mut files = vec![];
+        let mut fields = BTreeMap::new();
+        let res = read_parts::<P>(&mut multipart, ctx, &mut files, &mut fields).await;
+
+        if let Err(err) = res {
+            // don't keep the files of a rejected request
+            for file in &files {
+                if let Err(err) = ctx.storage.delete(&file.key).await {
+                    tracing::warn!(err = err.to_string(), key = %file.key.display(), "could not delete uploaded file");
+                }
+            }
+            return Err(err);
+        }
+
+        Ok(Self {
+            files,
+            fields,
+            policy: PhantomData,
+        })
+    }
+}
+
+async fn read_parts<P: UploadPolicy>(
+    multipart: &mut Multipart,
+    ctx: &AppContext,
+    files: &mut Vec<UploadedFile>,
+    fields: &mut BTreeMap<String, String>,
+) -> crate::Result<()> {
+    while let Some(mut field) = multipart
+        .next_field()
+        .await
+        .map_err(|err| Error::BadRequest(err.body_text()))?
+    {
+        let field_name = field.name().unwrap_or_default().to_string();
+        let Some(file_name) = field.file_name().map(ToString::to_string) else {
+            let max_size = P::max_file_size();
+            let mut value = BytesMut::new();
+            while let Some(chunk) = field
+                .chunk()
+                .await
+                .map_err(|err| Error::BadRequest(err.body_text()))?
+            {
+                value.extend_from_slice(&chunk);
+                if value.len() as u64 > max_size {
+                    return Err(too_large(max_size));
+                }
+            }
+            let value = String::from_utf8(value.to_vec()).map_err(|_| {
+                Error::BadRequest(format!("field `{field_name}` is not valid UTF-8"))
+            })?;
+            fields.insert(field_name, value);
+            continue;
+        };
+        let declared_type = field.content_type().map(ToString::to_string);
+
+        if files.len() >= P::max_files() {
+            return Err(Error::BadRequest(format!(
+                "too many files, up to {} are allowed",
+                P::max_files()
+            )));
+        }
+
+        let mut head = BytesMut::new();
+        while head.len() < SNIFF_LEN {
+            match field
+                .chunk()
+                .await
+                .map_err(|err| Error::BadRequest(err.body_text()))?
+            {
+                Some(chunk) => head.extend_from_slice(&chunk),
+                None => break,
+            }
+        }
+
+        let (content_type, extension) = sniff(&head, declared_type.as_deref());
+        if !is_allowed(&content_type, P::allowed_content_types()) {
+            return Err(Error::CustomError(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                ErrorDetail::new(
+                    "unsupported_media_type",
+                    format!("file type `{content_type}` is not allowed").as_str(),
+                ),
+            ));
+        }
+
+        let key = P::key(&file_name, extension);
+        let max_size = P::max_file_size();
+        let (tx, rx) = mpsc::channel::<StorageResult<Bytes>>(8);
+
+        let read = async move {
+            let mut size = head.len() as u64;
+            let mut next = Some(head.freeze());
+            while let Some(chunk) = next.take() {
+                if size > max_size {
+                    let _ = tx.send(Err(upload_aborted())).await;
+                    return Err(too_large(max_size));
+                }
+                if tx.send(Ok(chunk)).await.is_err() {
+                    // the store stopped reading, its error is reported
+                    return Ok(size);
+                }
+                match field.chunk().await {
+                    Ok(Some(chunk)) => {
+                        size += chunk.len() as u64;
+                        next = Some(chunk);
+                    }
+                    Ok(None) => {}
+                    Err(err) => {
+                        let _ = tx.send(Err(upload_aborted())).await;
+                        return Err(Error::BadRequest(err.body_text()));
+                    }
+                }
+            }
+            Ok(size)
+        };
+        let write = ctx.storage.upload_stream(&key, receiver_stream(rx));
+
+        let (size, written) = tokio::join!(read, write);
+        let size = size?;
+        written?;
+
+        files.push(UploadedFile {
+            field_name,
+            file_name,
+            key,
+            content_type,
+            size,
+        });
+    }
+
+    Ok(())
+}
+
+fn receiver_stream(rx: mpsc::Receiver<StorageResult<Bytes>>) -> BytesStream {
+    stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|chunk| (chunk, rx))
+    })
+    .boxed()
+}
+
+/// Sniffs the content type and extension of a file from its first bytes.
+///
+/// Content without a known signature is `text/plain` only when the client
+/// declared a text type and it is valid UTF-8, `application/octet-stream`
+/// otherwise.
+fn sniff(head: &[u8], declared_type: Option<&str>) -> (String, Option<&'static str>) {
+    if let Some(kind) = infer::get(head) {
+        return (kind.mime_type().to_string(), Some(kind.extension()));
+    }
+    let declared_text = declared_type.is_some_and(|declared| {
+        declared
+            .trim()
+            .get(..5)
+            .is_some_and(|kind| kind.eq_ignore_ascii_case("text/"))
+    });
+    match std::str::from_utf8(head) {
+        Ok(_) if declared_text => ("text/plain".to_string(), Some("txt")),
+        // a multi-byte character may be cut at the end of the sniffed bytes
+        Err(err) if declared_text && err.error_len().is_none() => {
+            ("text/plain".to_string(), Some("txt"))
+        }
+        _ => ("application/octet-stream".to_string(), None),
+    }
+}
+
+fn is_allowed(content_type: &str, allowed: &[&str]) -> bool {
+    allowed.is_empty()
+        || allowed.iter().any(|allowed| {
+            allowed.strip_suffix("/*").map_or_else(
+                || *allowed == content_type,
+                |kind| content_type.split('/').next() == Some(kind),
+            )
+        })
+}
+
+fn too_large(max_size: u64) -> Error {
+    Error::CustomError(
+        StatusCode::PAYLOAD_TOO_LARGE,
+        ErrorDetail::new(
+            "payload_too_large",
+            format!("files are limited to {max_size} bytes").as_str(),
+        ),
+    )
+}
+
+fn upload_aborted() -> StorageError {
+    StorageError::Any("upload aborted".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::header};
+
+    use super::*;
+    use crate::tests_cfg;
+
+    struct ImagesOnly;
+
+    impl UploadPolicy for ImagesOnly {
+        fn allowed_content_types() -> &'static [&'static str] {
+            &["image/*"]
+        }
+    }
+
+    type Part<'a> = (&'a str, Option<(&'a str, &'a str)>, &'a [u8]);
+
+    fn multipart_request(parts: &[Part<'_>]) -> Request {
+        let mut body = vec![];
+        for (name, file, content) in parts {
+            body.extend_from_slice(b"--BOUNDARY\r\n");
+            let disposition = file.map_or_else(
+                || format!("Content-Disposition: form-data; name=\"{name}\"\r\n\r\n"),
+                |(file_name, content_type)| {
+                    format!(
+                        "Content-Disposition: form-data; name=\"{name}\"; \
+                         filename=\"{file_name}\"\r\nContent-Type: {content_type}\r\n\r\n"
+                    )
+                },
+            );
+            body.extend_from_slice(disposition.as_bytes());
+            body.extend_from_slice(content);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(b"--BOUNDARY--\r\n");
+
+        Request::builder()
+            .method("POST")
+            .header(
+                header::CONTENT_TYPE,
+                "multipart/form-data; boundary=BOUNDARY",
+            )
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn can_upload_to_storage() {
+        let ctx = tests_cfg::app::get_app_context().await;
+        let req = multipart_request(&[
+            ("title", None, b"notes"),
+            (
+                "file",
+                Some(("notes.txt", "text/plain")),
+                b"loco file upload",
+            ),
+        ]);
+
+        let upload = Upload::<DefaultUploadPolicy>::from_request(req, &ctx)
+            .await
+            .unwrap();
+
+        assert_eq!(upload.fields.get("title"), Some(&"notes".to_string()));
+        assert_eq!(upload.files.len(), 1);
+        let file = &upload.files[0];
+        assert_eq!(file.file_name, "notes.txt");
+        assert_eq!(file.content_type, "text/plain");
+        assert_eq!(file.size, 16);
+        assert!(file.key.starts_with("uploads"));
+
+        let stored: String = ctx.storage.download(&file.key).await.unwrap();
+        assert_eq!(stored, "loco file upload");
+    }
+
+    #[tokio::test]
+    async fn cannot_upload_disallowed_type() {
+        let ctx = tests_cfg::app::get_app_context().await;
+        let req = multipart_request(&[("file", Some(("fake.png", "image/png")), b"not an image")]);
+
+        let res = Upload::<ImagesOnly>::from_request(req, &ctx).await;
+        assert!(matches!(
+            res,
+            Err(Error::CustomError(StatusCode::UNSUPPORTED_MEDIA_TYPE, _))
+        ));
+    }
+
+    struct Small;
+
+    impl UploadPolicy for Small {
+        fn max_file_size() -> u64 {
+            8
+        }
+    }
+
+    #[tokio::test]
+    async fn cannot_send_large_fields() {
+        let ctx = tests_cfg::app::get_app_context().await;
+        let req = multipart_request(&[("title", None, b"a title longer than the limit")]);
+
+        let res = Upload::<Small>::from_request(req, &ctx).await;
+        assert!(matches!(
+            res,
+            Err(Error::CustomError(StatusCode::PAYLOAD_TOO_LARGE, _))
+        ));
+    }
+
+    #[test]
+    fn can_sniff_content_type() {
+        let png = [0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0, 0, 0, 0];
+        let binary = ("application/octet-stream".to_string(), None);
+        assert_eq!(
+            sniff(&png, Some("text/plain")),
+            ("image/png".to_string(), Some("png"))
+        );
+        assert_eq!(
+            sniff(b"loco", Some("text/plain")),
+            ("text/plain".to_string(), Some("txt"))
+        );
+        // the client claims an image, the content has no image signature
+        assert_eq!(sniff(b"loco", Some("image/png")), binary);
+        assert_eq!(sniff(b"loco", None), binary);
+        assert_eq!(sniff(&[0xff, 0x00, 0xfe], Some("text/plain")), binary);
+    }
+
+    #[test]
+    fn can_check_allowed_types() {
+        assert!(is_allowed("image/png", &[]));
+        assert!(is_allowed("image/png", &["image/png"]));
+        assert!(is_allowed("image/png", &["image/*"]));
+        assert!(!is_allowed("text/plain", &["image/*", "application/pdf"]));
+    }
+}
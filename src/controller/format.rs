@@ -37,6 +37,7 @@ use crate::{
         views::{self, ViewRenderer},
         Json,
     },
+    storage::drivers::BytesStream,
     Result,
 };
 
@@ -160,6 +161,39 @@ pub fn redirect(to: &str) -> Result<Response> {
     Ok(Redirect::to(to).into_response())
 }
 
+/// Returns a response streaming the given storage content, without buffering
+/// it in memory, with the given `Content-Type` and, when known, the
+/// `Content-Length`. Both are usually read from the object metadata.
+///
+/// # Example:
+///
+/// ```rust
+/// use std::path::Path;
+/// use loco_rs::prelude::*;
+///
+/// async fn download(State(ctx): State<AppContext>) -> Result<Response> {
+///    let path = Path::new("report.pdf");
+///    let meta = ctx.storage.stat(path).await?;
+///    let stream = ctx.storage.download_stream(path, None).await?;
+///    format::stream(
+///        stream,
+///        meta.content_type.as_deref().unwrap_or("application/pdf"),
+///        Some(meta.size),
+///    )
+/// }
+/// ```
+///
+/// # Errors
+///
+/// When the content type is not a valid header value.
+pub fn stream(
+    stream: BytesStream,
+    content_type: &str,
+    content_length: Option<u64>,
+) -> Result<Response> {
+    RenderBuilder::new().stream(stream, content_type, content_length)
+}
+
 /// Render template located by `key`
 ///
 /// # Errors
@@ -273,6 +307,27 @@ impl RenderBuilder {
             .body(Body::from(content.to_string()))?)
     }
 
+    /// Finalize and return a response streaming the given storage content,
+    /// with the given `Content-Type` and, when known, the `Content-Length`.
+    ///
+    /// # Errors
+    ///
+    /// When the content type is not a valid header value.
+    pub fn stream(
+        self,
+        stream: BytesStream,
+        content_type: &str,
+        content_length: Option<u64>,
+    ) -> Result<Response> {
+        let mut response = self
+            .response
+            .header(header::CONTENT_TYPE, HeaderValue::from_str(content_type)?);
+        if let Some(content_length) = content_length {
+            response = response.header(header::CONTENT_LENGTH, content_length);
+        }
+        Ok(response.body(Body::from_stream(stream))?)
+    }
+
     /// Finalize and return an empty response
     ///
     /// # Errors
@@ -404,6 +459,28 @@ mod tests {
         Some(response.headers().get(header)?.to_str().ok()?.to_string())
     }
 
+    #[tokio::test]
+    async fn stream_response_format() {
+        use futures_util::StreamExt;
+
+        let content: BytesStream = futures_util::stream::iter(vec![
+            Ok(bytes::Bytes::from("lo")),
+            Ok(bytes::Bytes::from("co")),
+        ])
+        .boxed();
+        let response = stream(content, "text/plain", Some(4)).unwrap();
+
+        assert_eq!(
+            get_header_from_response(&response, "content-type"),
+            Some("text/plain".to_string())
+        );
+        assert_eq!(
+            get_header_from_response(&response, "content-length"),
+            Some("4".to_string())
+        );
+        assert_eq!(response_body_to_string(response).await, "loco");
+    }
+
     #[tokio::test]
     async fn empty_response_format() {
         let response: Response<Body> = empty().unwrap();
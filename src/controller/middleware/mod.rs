@@ -19,6 +19,7 @@ pub mod logger;
 pub mod powered_by;
 pub mod remote_ip;
 pub mod request_id;
+pub mod response_cache;
 pub mod secure_headers;
 pub mod static_assets;
 pub mod timeout;
@@ -74,6 +75,17 @@ pub fn default_middleware_stack(ctx: &AppContext) -> Vec<Box<dyn MiddlewareLayer
     let middlewares = &ctx.config.server.middlewares;
 
     vec![
+        // Response Cache middleware with a default if none
+        Box::new(response_cache::new(
+            &middlewares
+                .response_cache
+                .clone()
+                .unwrap_or_else(|| response_cache::Config {
+                    enable: false,
+                    ..Default::default()
+                }),
+            &ctx.cache,
+        )),
         // Limit Payload middleware with a default if none
         Box::new(middlewares.limit_payload.clone().unwrap_or_default()),
         // CORS middleware with a default if none
@@ -205,4 +217,7 @@ pub struct Config {
 
     /// Request ID
     pub request_id: Option<request_id::RequestId>,
+
+    /// Server side caching of whole responses
+    pub response_cache: Option<response_cache::Config>,
 }
@@ -0,0 +1,545 @@
+//! Response Cache Middleware
+//!
+//! This middleware caches whole `GET` responses server-side in
+//! [`AppContext::cache`]. Responses are keyed on the request method, path,
+//! query string and a configurable list of request headers, and are kept for
+//! the configured TTL unless the handler returns a `Cache-Control` header
+//! that says otherwise:
+//!
+//! * `no-store`, `no-cache` and `private` responses are never cached.
+//! * `s-maxage` or `max-age` replace the configured TTL.
+//!
+//! Only `200 OK` responses with a known, UTF-8 body within `max_body_size`
+//! and without a `Set-Cookie` header are cached. Responses with `Vary: *`,
+//! or varying on a request header missing from `vary_headers`, are not
+//! cached either.
+//!
+//! Requests carrying credentials, an `Authorization` or `Cookie` header,
+//! bypass the cache unless that header is listed in `vary_headers`, which
+//! gives each credential its own entry.
+//!
+//! By default every matching response is cached. When `opt_in` is enabled,
+//! only routes registered with [`Routes::response_cache`] are cached.
+//!
+//! Cached entries are tagged with their path, so controllers can drop them
+//! after a change with [`invalidate_path`], [`invalidate_path_prefix`] or
+//! [`invalidate_all`].
+//!
+//! [`Routes::response_cache`]: crate::controller::Routes::response_cache
+
+use std::{
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use axum::{
+    body::{Body, HttpBody},
+    extract::{Request, State},
+    http::{
+        header::{AUTHORIZATION, CACHE_CONTROL, COOKIE, SET_COOKIE, VARY},
+        HeaderMap, HeaderName, HeaderValue, Method, StatusCode,
+    },
+    middleware::Next,
+    response::Response,
+    Router as AXRouter,
+};
+use futures_util::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tower::{Layer, Service};
+
+use crate::{app::AppContext, cache::Cache, controller::middleware::MiddlewareLayer, Result};
+
+/// Prefix of every key stored by the response cache.
+const KEY_PREFIX: &str = "response_cache:";
+
+/// Response cache middleware configuration
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Config {
+    #[serde(default)]
+    pub enable: bool,
+    /// Cache only the routes registered with
+    /// [`crate::controller::Routes::response_cache`].
+    #[serde(default)]
+    pub opt_in: bool,
+    /// Default time to live of a cached response, in seconds.
+    #[serde(default = "default_ttl")]
+    pub ttl: u64,
+    /// Request headers that are part of the cache key, for example `accept`
+    /// or `accept-language`.
+    #[serde(default = "default_vary_headers")]
+    pub vary_headers: Vec<String>,
+    /// Responses with a larger body are not cached, in bytes.
+    #[serde(default = "default_max_body_size")]
+    pub max_body_size: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        serde_json::from_value(json!({})).unwrap()
+    }
+}
+
+fn default_ttl() -> u64 {
+    60
+}
+
+fn default_vary_headers() -> Vec<String> {
+    vec!["accept".to_string()]
+}
+
+fn default_max_body_size() -> u64 {
+    1024 * 1024
+}
+
+/// [`Middleware`] struct responsible for caching HTTP responses.
+pub struct Middleware {
+    config: Config,
+    cache: Arc<Cache>,
+}
+
+/// Creates a new instance of [`Middleware`] by cloning the [`Config`]
+/// configuration and the application cache.
+#[must_use]
+pub fn new(config: &Config, cache: &Arc<Cache>) -> Middleware {
+    Middleware {
+        config: config.clone(),
+        cache: cache.clone(),
+    }
+}
+
+impl MiddlewareLayer for Middleware {
+    /// Returns the name of the middleware
+    fn name(&self) -> &'static str {
+        "response_cache"
+    }
+
+    /// Returns whether the middleware is enabled or not
+    fn is_enabled(&self) -> bool {
+        self.config.enable
+    }
+
+    fn config(&self) -> serde_json::Result<serde_json::Value> {
+        serde_json::to_value(&self.config)
+    }
+
+    /// Applies the response cache middleware to the application router.
+    fn apply(&self, app: AXRouter<AppContext>) -> Result<AXRouter<AppContext>> {
+        let state = ResponseCacheState {
+            config: Arc::new(self.config.clone()),
+            cache: self.cache.clone(),
+        };
+        Ok(app.layer(axum::middleware::from_fn_with_state(
+            state,
+            response_cache_middleware,
+        )))
+    }
+}
+
+#[derive(Clone)]
+struct ResponseCacheState {
+    config: Arc<Config>,
+    cache: Arc<Cache>,
+}
+
+/// Marks a response as cacheable, attached by the route level layer
+/// registered with [`crate::controller::Routes::response_cache`].
+#[derive(Debug, Clone, Copy)]
+struct CacheableRoute {
+    ttl: Option<Duration>,
+}
+
+/// A cached response, as stored in [`AppContext::cache`].
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl CachedResponse {
+    fn into_response(self) -> Response {
+        let mut response = Response::new(Body::from(self.body));
+        *response.status_mut() = StatusCode::from_u16(self.status).unwrap_or(StatusCode::OK);
+        for (name, value) in self.headers {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(&value),
+            ) {
+                response.headers_mut().append(name, value);
+            }
+        }
+        response
+    }
+}
+
+/// What the handler allows us to do with its response, based on its
+/// `Cache-Control` header.
+enum Directive {
+    Skip,
+    Ttl(Option<Duration>),
+}
+
+fn cache_control_directive(headers: &HeaderMap) -> Directive {
+    let Some(value) = headers.get(CACHE_CONTROL).and_then(|v| v.to_str().ok()) else {
+        return Directive::Ttl(None);
+    };
+
+    let mut max_age = None;
+    let mut s_maxage = None;
+    for directive in value.split(',').map(|d| d.trim().to_ascii_lowercase()) {
+        match directive.split_once('=') {
+            None if matches!(directive.as_str(), "no-store" | "no-cache" | "private") => {
+                return Directive::Skip;
+            }
+            Some(("max-age", secs)) => max_age = secs.trim_matches('"').parse::<u64>().ok(),
+            Some(("s-maxage", secs)) => s_maxage = secs.trim_matches('"').parse::<u64>().ok(),
+            _ => {}
+        }
+    }
+
+    match s_maxage.or(max_age) {
+        Some(0) => Directive::Skip,
+        secs => Directive::Ttl(secs.map(Duration::from_secs)),
+    }
+}
+
+fn cache_key(config: &Config, request: &Request) -> String {
+    let uri = request.uri();
+    let vary = config
+        .vary_headers
+        .iter()
+        .map(|name| {
+            let value = request
+                .headers()
+                .get(name.as_str())
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default();
+            format!("{}={value}", name.to_ascii_lowercase())
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+
+    format!(
+        "{KEY_PREFIX}{}|{}|{}|{vary}",
+        uri.path(),
+        request.method(),
+        uri.query().unwrap_or_default()
+    )
+}
+
+fn varies_on(config: &Config, name: &str) -> bool {
+    config
+        .vary_headers
+        .iter()
+        .any(|header| header.eq_ignore_ascii_case(name))
+}
+
+/// Returns `true` when the request carries credentials that are not part of
+/// the cache key, so its response must not be shared.
+fn has_credentials(config: &Config, headers: &HeaderMap) -> bool {
+    [AUTHORIZATION, COOKIE]
+        .iter()
+        .any(|name| headers.contains_key(name) && !varies_on(config, name.as_str()))
+}
+
+/// Returns `true` when the response `Vary` header is `*`, or names a request
+/// header that is not part of the cache key.
+fn varies_beyond_key(config: &Config, headers: &HeaderMap) -> bool {
+    headers
+        .get_all(VARY)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .any(|name| name == "*" || !varies_on(config, name))
+}
+
+fn path_tag(path: &str) -> String {
+    format!("{KEY_PREFIX}path:{path}")
+}
+
+async fn response_cache_middleware(
+    State(state): State<ResponseCacheState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if request.method() != Method::GET || has_credentials(&state.config, request.headers()) {
+        return next.run(request).await;
+    }
+
+    let key = cache_key(&state.config, &request);
+    let path = request.uri().path().to_string();
+
+    match state.cache.get(&key).await {
+        Ok(Some(cached)) => match serde_json::from_str::<CachedResponse>(&cached) {
+            Ok(cached) => return cached.into_response(),
+            Err(err) => tracing::debug!(
+                err = err.to_string(),
+                key = key.as_str(),
+                "invalid cached response"
+            ),
+        },
+        Ok(None) => {}
+        Err(err) => tracing::debug!(
+            err = err.to_string(),
+            key = key.as_str(),
+            "could not read response cache"
+        ),
+    }
+
+    let response = next.run(request).await;
+    let route = response.extensions().get::<CacheableRoute>().copied();
+
+    if response.status() != StatusCode::OK
+        || response.headers().contains_key(SET_COOKIE)
+        || varies_beyond_key(&state.config, response.headers())
+        || (state.config.opt_in && route.is_none())
+    {
+        return response;
+    }
+
+    let ttl = match cache_control_directive(response.headers()) {
+        Directive::Skip => return response,
+        Directive::Ttl(ttl) => ttl
+            .or_else(|| route.and_then(|r| r.ttl))
+            .unwrap_or_else(|| Duration::from_secs(state.config.ttl)),
+    };
+
+    match response.body().size_hint().exact() {
+        Some(size) if size <= state.config.max_body_size => {}
+        _ => return response,
+    }
+
+    let (parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            tracing::error!(
+                err = err.to_string(),
+                key = key.as_str(),
+                "could not read response body"
+            );
+            return Response::from_parts(parts, Body::empty());
+        }
+    };
+
+    if let Ok(body) = std::str::from_utf8(&bytes) {
+        let cached = CachedResponse {
+            status: parts.status.as_u16(),
+            headers: parts
+                .headers
+                .iter()
+                .filter_map(|(name, value)| {
+                    value
+                        .to_str()
+                        .ok()
+                        .map(|value| (name.to_string(), value.to_string()))
+                })
+                .collect(),
+            body: body.to_string(),
+        };
+
+        match serde_json::to_string(&cached) {
+            Ok(value) => {
+                if let Err(err) = state
+                    .cache
+                    .insert_with_tags_and_expiry(&key, &value, &[&path_tag(&path)], ttl)
+                    .await
+                {
+                    tracing::debug!(
+                        err = err.to_string(),
+                        key = key.as_str(),
+                        "could not store response"
+                    );
+                }
+            }
+            Err(err) => tracing::debug!(
+                err = err.to_string(),
+                key = key.as_str(),
+                "could not serialize response"
+            ),
+        }
+    }
+
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+/// Removes the cached responses of the given request path, for every method,
+/// query string and header variation.
+///
+/// # Errors
+///
+/// When the cache driver fails to remove the entries.
+pub async fn invalidate_path(ctx: &AppContext, path: &str) -> Result<()> {
+    Ok(ctx.cache.invalidate_tag(&path_tag(path)).await?)
+}
+
+/// Removes the cached responses of every request path that starts with the
+/// given prefix, for example `/api/notes`.
+///
+/// # Errors
+///
+/// When the cache driver fails to remove the entries.
+pub async fn invalidate_path_prefix(ctx: &AppContext, prefix: &str) -> Result<()> {
+    Ok(ctx
+        .cache
+        .remove_prefix(&format!("{KEY_PREFIX}{prefix}"))
+        .await?)
+}
+
+/// Removes every cached response.
+///
+/// # Errors
+///
+/// When the cache driver fails to remove the entries.
+pub async fn invalidate_all(ctx: &AppContext) -> Result<()> {
+    Ok(ctx.cache.remove_prefix(KEY_PREFIX).await?)
+}
+
+/// Route level layer marking the responses of a route as cacheable, see
+/// [`crate::controller::Routes::response_cache`].
+#[derive(Debug, Clone, Copy)]
+pub struct RouteLayer {
+    ttl: Option<Duration>,
+}
+
+impl RouteLayer {
+    /// Marks the route as cacheable, optionally overriding the configured TTL.
+    #[must_use]
+    pub fn new(ttl: Option<Duration>) -> Self {
+        Self { ttl }
+    }
+}
+
+impl<S> Layer<S> for RouteLayer {
+    type Service = RouteMiddleware<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RouteMiddleware {
+            inner,
+            ttl: self.ttl,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RouteMiddleware<S> {
+    inner: S,
+    ttl: Option<Duration>,
+}
+
+impl<S> Service<Request<Body>> for RouteMiddleware<S>
+where
+    S: Service<Request, Response = Response> + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let ttl = self.ttl;
+        let future = self.inner.call(request);
+        Box::pin(async move {
+            let mut response = future.await?;
+            response.extensions_mut().insert(CacheableRoute { ttl });
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(cache_control: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(CACHE_CONTROL, HeaderValue::from_str(cache_control).unwrap());
+        headers
+    }
+
+    #[test]
+    fn can_read_cache_control() {
+        assert!(matches!(
+            cache_control_directive(&HeaderMap::new()),
+            Directive::Ttl(None)
+        ));
+        assert!(matches!(
+            cache_control_directive(&headers("no-store")),
+            Directive::Skip
+        ));
+        assert!(matches!(
+            cache_control_directive(&headers("public, Private")),
+            Directive::Skip
+        ));
+        assert!(matches!(
+            cache_control_directive(&headers("max-age=0")),
+            Directive::Skip
+        ));
+        assert!(matches!(
+            cache_control_directive(&headers("public, max-age=30")),
+            Directive::Ttl(Some(ttl)) if ttl == Duration::from_secs(30)
+        ));
+        assert!(matches!(
+            cache_control_directive(&headers("max-age=30, s-maxage=120")),
+            Directive::Ttl(Some(ttl)) if ttl == Duration::from_secs(120)
+        ));
+    }
+
+    #[test]
+    fn can_build_cache_key() {
+        let config = Config::default();
+        let request = Request::builder()
+            .uri("/notes?page=2")
+            .header("accept", "application/json")
+            .body(Body::empty())
+            .unwrap();
+
+        assert_eq!(
+            cache_key(&config, &request),
+            "response_cache:/notes|GET|page=2|accept=application/json"
+        );
+    }
+
+    #[test]
+    fn bypasses_requests_with_credentials() {
+        let config = Config::default();
+        let mut headers = HeaderMap::new();
+        assert!(!has_credentials(&config, &headers));
+
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer a"));
+        assert!(has_credentials(&config, &headers));
+
+        let config = Config {
+            vary_headers: vec!["Accept".to_string(), "Authorization".to_string()],
+            ..Default::default()
+        };
+        assert!(!has_credentials(&config, &headers));
+
+        headers.insert(COOKIE, HeaderValue::from_static("session=1"));
+        assert!(has_credentials(&config, &headers));
+    }
+
+    #[test]
+    fn honours_response_vary() {
+        let config = Config::default();
+        let mut headers = HeaderMap::new();
+        assert!(!varies_beyond_key(&config, &headers));
+
+        headers.insert(VARY, HeaderValue::from_static("Accept"));
+        assert!(!varies_beyond_key(&config, &headers));
+
+        headers.insert(VARY, HeaderValue::from_static("accept, accept-language"));
+        assert!(varies_beyond_key(&config, &headers));
+
+        headers.insert(VARY, HeaderValue::from_static("*"));
+        assert!(varies_beyond_key(&config, &headers));
+    }
+}
@@ -3,7 +3,7 @@ use std::convert::Infallible;
 use axum::{extract::Request, response::IntoResponse, routing::Route};
 use tower::{Layer, Service};
 
-use super::describe;
+use super::{describe, middleware::response_cache};
 use crate::app::AppContext;
 #[derive(Clone, Default, Debug)]
 pub struct Routes {
@@ -155,4 +155,29 @@ impl Routes {
                 .collect(),
         }
     }
+
+    /// Marks the routes as cacheable by the `response_cache` middleware,
+    /// optionally overriding its configured time to live.
+    ///
+    /// When the middleware is configured with `opt_in: true`, only the
+    /// routes marked this way are cached.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use loco_rs::prelude::*;
+    ///
+    /// async fn list() -> Result<Response> {
+    ///     format::json("Ok")
+    /// }
+    /// Routes::new()
+    ///     .prefix("notes")
+    ///     .add("/", get(list))
+    ///     .response_cache(Some(Duration::from_secs(300)));
+    /// ```
+    #[must_use]
+    pub fn response_cache(self, ttl: Option<std::time::Duration>) -> Self {
+        self.layer(response_cache::RouteLayer::new(ttl))
+    }
 }
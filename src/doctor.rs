@@ -1,11 +1,13 @@
 use std::{
     collections::{BTreeMap, HashMap},
     fs,
+    path::Path,
     process::Command,
     sync::OnceLock,
 };
 
 use colored::Colorize;
+use futures_util::StreamExt;
 use regex::Regex;
 use semver::Version;
 use serde::Deserialize;
@@ -13,7 +15,9 @@ use serde::Deserialize;
 use crate::{
     bgworker,
     config::{self, Config},
-    depcheck, Error, Result,
+    depcheck, mailer,
+    storage::Storage,
+    Error, Result,
 };
 
 const SEAORM_INSTALLED: &str = "SeaORM CLI is installed";
@@ -23,6 +27,10 @@ const SEAORM_NOT_FIX: &str = r"To fix, run:
 const QUEUE_CONN_OK: &str = "queue connection: success";
 const QUEUE_CONN_FAILED: &str = "queue connection: failed";
 const QUEUE_NOT_CONFIGURED: &str = "queue not configured?";
+const STORAGE_OK: &str = "storage: success";
+const STORAGE_FAILED: &str = "storage: failed";
+const DKIM_OK: &str = "mailer DKIM key: success";
+const DKIM_FAILED: &str = "mailer DKIM key: failed";
 
 // versions health
 const MIN_SEAORMCLI_VER: &str = "1.1.0";
@@ -92,6 +100,8 @@ pub enum Resource {
     SeaOrmCLI,
     Database,
     Queue,
+    Storage,
+    Dkim,
     Deps,
     PublishedLocoVersion,
 }
@@ -174,6 +184,14 @@ pub async fn run_all(config: &Config, production: bool) -> Result<BTreeMap<Resou
         checks.insert(Resource::Queue, check_queue(config).await);
     }
 
+    if config.storage.is_some() {
+        checks.insert(Resource::Storage, check_storage(config).await);
+    }
+
+    if config.mailer.as_ref().is_some_and(|m| m.dkim.is_some()) {
+        checks.insert(Resource::Dkim, check_dkim(config));
+    }
+
     if !production {
         checks.insert(Resource::Deps, check_deps()?);
         checks.insert(Resource::SeaOrmCLI, check_seaorm_cli()?);
@@ -283,6 +301,79 @@ pub async fn check_queue(config: &Config) -> Check {
     }
 }
 
+/// Checks that the configured storage can be built and that every store can
+/// be listed.
+pub async fn check_storage(config: &Config) -> Check {
+    let Some(storage_config) = config.storage.as_ref() else {
+        return Check {
+            status: CheckStatus::NotConfigure,
+            message: "storage not configured".to_string(),
+            description: None,
+        };
+    };
+
+    let storage = match Storage::from_config(storage_config) {
+        Ok(storage) => storage,
+        Err(err) => {
+            return Check {
+                status: CheckStatus::NotOk,
+                message: STORAGE_FAILED.to_string(),
+                description: Some(err.to_string()),
+            }
+        }
+    };
+
+    let mut errors = vec![];
+    for (name, store) in &storage.stores {
+        let res = match store.list(Path::new("")).await {
+            Ok(mut objects) => objects.next().await.transpose().map(|_| ()),
+            Err(err) => Err(err),
+        };
+        if let Err(err) = res {
+            errors.push(format!("{name}: {err}"));
+        }
+    }
+
+    if errors.is_empty() {
+        Check {
+            status: CheckStatus::Ok,
+            message: STORAGE_OK.to_string(),
+            description: None,
+        }
+    } else {
+        Check {
+            status: CheckStatus::NotOk,
+            message: STORAGE_FAILED.to_string(),
+            description: Some(errors.join("\n")),
+        }
+    }
+}
+
+/// Checks that the DKIM private key of the mailer loads.
+#[must_use]
+pub fn check_dkim(config: &Config) -> Check {
+    let Some(dkim) = config.mailer.as_ref().and_then(|m| m.dkim.as_ref()) else {
+        return Check {
+            status: CheckStatus::NotConfigure,
+            message: "mailer DKIM not configured".to_string(),
+            description: None,
+        };
+    };
+
+    match mailer::dkim::load(dkim) {
+        Ok(_) => Check {
+            status: CheckStatus::Ok,
+            message: DKIM_OK.to_string(),
+            description: None,
+        },
+        Err(err) => Check {
+            status: CheckStatus::NotOk,
+            message: DKIM_FAILED.to_string(),
+            description: Some(err.to_string()),
+        },
+    }
+}
+
 /// Checks the presence and version of `SeaORM` CLI.
 /// # Panics
 /// On illegal regex
@@ -0,0 +1,211 @@
+//! Email attachments and inline (`cid:`) parts.
+//!
+//! An [`Attachment`] holds its content either as bytes, or as a path in the
+//! application storage which is downloaded by the [`super::MailerWorker`]
+//! right before sending. Both serialize to JSON so they survive the queue
+//! round-trip; bytes are encoded as base64.
+//!
+//! ```rust,ignore
+//! use loco_rs::mailer::{Args, Attachment};
+//!
+//! let args = Args {
+//!     to: "user@example.com".into(),
+//!     attachments: vec![
+//!         Attachment::from_bytes("invoice.pdf", pdf).with_content_type("application/pdf"),
+//!         // referenced from the html template as <img src="cid:logo">
+//!         Attachment::from_storage("logo.png", "assets/logo.png").inline("logo"),
+//!     ],
+//!     ..Default::default()
+//! };
+//! ```
+
+use std::path::PathBuf;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use lettre::message::{header::ContentType, SinglePart};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{storage::Storage, Error, Result};
+
+/// The content type used when none is given and it cannot be inferred.
+const DEFAULT_CONTENT_TYPE: &str = "application/octet-stream";
+
+/// Where the content of an [`Attachment`] comes from.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "source", rename_all = "snake_case")]
+pub enum AttachmentContent {
+    /// The content itself, base64 encoded when serialized.
+    Bytes {
+        #[serde(serialize_with = "to_base64", deserialize_with = "from_base64")]
+        data: Vec<u8>,
+    },
+    /// A path in the application storage.
+    Storage { path: PathBuf },
+}
+
+/// A file attached to an email, or an inline part referenced from the HTML
+/// body by its content id.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Attachment {
+    /// The file name shown to the recipient.
+    pub filename: String,
+    /// The MIME type, inferred from the content when `None`.
+    pub content_type: Option<String>,
+    /// The content id of an inline part, referenced as `cid:<content_id>`.
+    pub content_id: Option<String>,
+    /// The content of the attachment.
+    pub content: AttachmentContent,
+}
+
+impl Attachment {
+    /// Creates an attachment from its content.
+    #[must_use]
+    pub fn from_bytes(filename: impl Into<String>, data: impl Into<Vec<u8>>) -> Self {
+        Self {
+            filename: filename.into(),
+            content_type: None,
+            content_id: None,
+            content: AttachmentContent::Bytes { data: data.into() },
+        }
+    }
+
+    /// Creates an attachment downloaded from the application storage when
+    /// the email is sent.
+    #[must_use]
+    pub fn from_storage(filename: impl Into<String>, path: impl Into<PathBuf>) -> Self {
+        Self {
+            filename: filename.into(),
+            content_type: None,
+            content_id: None,
+            content: AttachmentContent::Storage { path: path.into() },
+        }
+    }
+
+    /// Sets the MIME type of the attachment.
+    #[must_use]
+    pub fn with_content_type(mut self, content_type: impl Into<String>) -> Self {
+        self.content_type = Some(content_type.into());
+        self
+    }
+
+    /// Makes the attachment an inline part, referenced from the HTML body as
+    /// `cid:<content_id>`.
+    #[must_use]
+    pub fn inline(mut self, content_id: impl Into<String>) -> Self {
+        self.content_id = Some(content_id.into());
+        self
+    }
+
+    /// Returns `true` for inline parts.
+    #[must_use]
+    pub const fn is_inline(&self) -> bool {
+        self.content_id.is_some()
+    }
+
+    /// Downloads the content of a storage attachment, turning it into a
+    /// bytes attachment.
+    ///
+    /// # Errors
+    ///
+    /// When the content cannot be downloaded from the storage.
+    pub async fn resolve(&mut self, storage: &Storage) -> Result<()> {
+        if let AttachmentContent::Storage { path } = &self.content {
+            let data: Vec<u8> = storage.download(path).await?;
+            self.content = AttachmentContent::Bytes { data };
+        }
+        Ok(())
+    }
+
+    /// Builds the MIME part of the attachment.
+    ///
+    /// # Errors
+    ///
+    /// When the content was not resolved from the storage, or the content
+    /// type is invalid.
+    pub(crate) fn to_part(&self) -> Result<SinglePart> {
+        let AttachmentContent::Bytes { data } = &self.content else {
+            return Err(Error::Message(format!(
+                "attachment `{}` was not downloaded from the storage",
+                self.filename
+            )));
+        };
+
+        let content_type = self.content_type.as_deref().unwrap_or_else(|| {
+            infer::get(data).map_or(DEFAULT_CONTENT_TYPE, |kind| kind.mime_type())
+        });
+        let content_type = ContentType::parse(content_type).map_err(|err| {
+            Error::Message(format!(
+                "invalid content type `{content_type}` for attachment `{}`: {err}",
+                self.filename
+            ))
+        })?;
+
+        let attachment = match &self.content_id {
+            Some(content_id) => lettre::message::Attachment::new_inline(content_id.clone()),
+            None => lettre::message::Attachment::new(self.filename.clone()),
+        };
+        Ok(attachment.body(data.clone(), content_type))
+    }
+}
+
+fn to_base64<S: Serializer>(data: &[u8], serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_str(&STANDARD.encode(data))
+}
+
+fn from_base64<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Vec<u8>, D::Error> {
+    let encoded = String::deserialize(deserializer)?;
+    STANDARD.decode(encoded).map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use bytes::Bytes;
+
+    use super::*;
+    use crate::storage::drivers;
+
+    #[test]
+    fn can_serialize_round_trip() {
+        let attachments = vec![
+            Attachment::from_bytes("invoice.pdf", b"%PDF-1.4".to_vec())
+                .with_content_type("application/pdf"),
+            Attachment::from_storage("logo.png", "assets/logo.png").inline("logo"),
+        ];
+
+        let json = serde_json::to_value(&attachments).unwrap();
+        assert_eq!(json[0]["content"]["source"], "bytes");
+        assert_eq!(json[0]["content"]["data"], "JVBERi0xLjQ=");
+        assert_eq!(json[1]["content"]["source"], "storage");
+
+        let restored: Vec<Attachment> = serde_json::from_value(json).unwrap();
+        assert_eq!(restored, attachments);
+    }
+
+    #[tokio::test]
+    async fn can_resolve_from_storage() {
+        let storage = Storage::single(drivers::mem::new());
+        storage
+            .upload(Path::new("assets/logo.png"), &Bytes::from("logo"))
+            .await
+            .unwrap();
+
+        let mut attachment = Attachment::from_storage("logo.png", "assets/logo.png");
+        assert!(attachment.to_part().is_err());
+
+        attachment.resolve(&storage).await.unwrap();
+        assert_eq!(
+            attachment.content,
+            AttachmentContent::Bytes {
+                data: b"logo".to_vec()
+            }
+        );
+        assert!(attachment.to_part().is_ok());
+
+        let mut missing = Attachment::from_storage("missing.png", "assets/missing.png");
+        assert!(missing.resolve(&storage).await.is_err());
+    }
+}
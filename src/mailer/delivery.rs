@@ -0,0 +1,253 @@
+//! # Delivery Hooks
+//!
+//! [`Interceptor`]s run in the [`super::MailerWorker`] before an email is
+//! sent, to rewrite recipients or add headers. A [`DeliveryLog`] records
+//! every delivery, sent or failed, after the fact.
+//!
+//! Both are set on the [`super::EmailSender`], usually in
+//! `Hooks::after_context`:
+//!
+//! ```rust,ignore
+//! async fn after_context(mut ctx: AppContext) -> Result<AppContext> {
+//!     if ctx.environment == Environment::Any("staging".to_string()) {
+//!         ctx.mailer = ctx.mailer.map(|mailer| {
+//!             mailer.with_interceptor(RedirectRecipients::new("qa@example.com"))
+//!         });
+//!     }
+//!     Ok(ctx)
+//! }
+//! ```
+//!
+//! The delivery log is configured with `mailer.delivery_log`, see
+//! [`crate::config::DeliveryLogConfig`].
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{Email, Recipients};
+use crate::{app::AppContext, Result};
+
+/// Changes an email before it is sent.
+#[async_trait]
+pub trait Interceptor: Send + Sync + std::fmt::Debug {
+    /// Changes the email. Returning an error fails the delivery.
+    async fn intercept(&self, ctx: &AppContext, email: &mut Email) -> Result<()>;
+}
+
+/// Sends every email to the given recipients instead of the original ones,
+/// which are kept in the `X-Original-To` header. Useful in staging.
+#[derive(Debug, Clone)]
+pub struct RedirectRecipients {
+    to: Recipients,
+}
+
+impl RedirectRecipients {
+    #[must_use]
+    pub fn new(to: impl Into<Recipients>) -> Self {
+        Self { to: to.into() }
+    }
+}
+
+#[async_trait]
+impl Interceptor for RedirectRecipients {
+    async fn intercept(&self, _ctx: &AppContext, email: &mut Email) -> Result<()> {
+        let original = email
+            .to
+            .iter()
+            .chain(&email.cc)
+            .chain(&email.bcc)
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(", ");
+        email.headers.insert("X-Original-To".to_string(), original);
+        email.to = self.to.clone();
+        email.cc = Recipients::new();
+        email.bcc = Recipients::new();
+        Ok(())
+    }
+}
+
+/// The outcome of a delivery.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryOutcome {
+    Sent,
+    Failed,
+}
+
+impl DeliveryOutcome {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Sent => "sent",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+/// A delivery recorded by a [`DeliveryLog`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeliveryRecord {
+    /// The `Message-ID` header, when the message could be built.
+    pub message_id: Option<String>,
+    pub from: Option<String>,
+    pub to: Recipients,
+    pub cc: Recipients,
+    pub bcc: Recipients,
+    pub subject: String,
+    pub outcome: DeliveryOutcome,
+    /// The error of a failed delivery.
+    pub error: Option<String>,
+    pub delivered_at: DateTime<Utc>,
+}
+
+impl DeliveryRecord {
+    /// Builds the record of a delivery from the email, as sent after the
+    /// interceptors, and the result of sending it.
+    #[must_use]
+    pub fn new(email: &Email, result: &Result<Option<String>>) -> Self {
+        let (message_id, outcome, error) = match result {
+            Ok(message_id) => (message_id.clone(), DeliveryOutcome::Sent, None),
+            Err(err) => (None, DeliveryOutcome::Failed, Some(err.to_string())),
+        };
+        Self {
+            message_id,
+            from: email.from.clone(),
+            to: email.to.clone(),
+            cc: email.cc.clone(),
+            bcc: email.bcc.clone(),
+            subject: email.subject.clone(),
+            outcome,
+            error,
+            delivered_at: Utc::now(),
+        }
+    }
+}
+
+/// Persists delivery records.
+#[async_trait]
+pub trait DeliveryLog: Send + Sync + std::fmt::Debug {
+    /// Records a delivery.
+    async fn record(&self, ctx: &AppContext, record: &DeliveryRecord) -> Result<()>;
+}
+
+/// Writes each delivery record as a JSON file to the application storage,
+/// at `<prefix>/<date>/<uuid>.json`.
+#[derive(Debug, Clone)]
+pub struct StorageDeliveryLog {
+    prefix: PathBuf,
+}
+
+impl StorageDeliveryLog {
+    #[must_use]
+    pub fn new(prefix: impl Into<PathBuf>) -> Self {
+        Self {
+            prefix: prefix.into(),
+        }
+    }
+
+    /// Returns the storage path of a record.
+    #[must_use]
+    pub fn path(&self, record: &DeliveryRecord) -> PathBuf {
+        self.prefix
+            .join(record.delivered_at.format("%Y-%m-%d").to_string())
+            .join(format!("{}.json", uuid::Uuid::new_v4()))
+    }
+}
+
+#[async_trait]
+impl DeliveryLog for StorageDeliveryLog {
+    async fn record(&self, ctx: &AppContext, record: &DeliveryRecord) -> Result<()> {
+        let content = Bytes::from(serde_json::to_vec(record)?);
+        ctx.storage.upload(&self.path(record), &content).await?;
+        Ok(())
+    }
+}
+
+/// Inserts each delivery record in the `mail_deliveries` table, see
+/// [`crate::schema::create_mail_deliveries_table`].
+#[cfg(feature = "with-db")]
+#[derive(Debug, Clone, Default)]
+pub struct DatabaseDeliveryLog;
+
+#[cfg(feature = "with-db")]
+#[async_trait]
+impl DeliveryLog for DatabaseDeliveryLog {
+    async fn record(&self, ctx: &AppContext, record: &DeliveryRecord) -> Result<()> {
+        crate::model::mail_deliveries::create(&ctx.db, record).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use futures_util::TryStreamExt;
+
+    use super::*;
+    use crate::{storage::Storage, tests_cfg};
+
+    fn email() -> Email {
+        Email {
+            from: Some("test@framework.com".to_string()),
+            to: "user1@framework.com".into(),
+            cc: "user2@framework.com".into(),
+            subject: "Email Subject".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn can_redirect_recipients() {
+        let ctx = tests_cfg::app::get_app_context().await;
+        let mut email = email();
+
+        RedirectRecipients::new("qa@framework.com")
+            .intercept(&ctx, &mut email)
+            .await
+            .unwrap();
+
+        assert_eq!(email.to, Recipients::from("qa@framework.com"));
+        assert!(email.cc.is_empty());
+        assert_eq!(
+            email.headers.get("X-Original-To").map(String::as_str),
+            Some("user1@framework.com, user2@framework.com")
+        );
+    }
+
+    #[tokio::test]
+    async fn can_record_to_storage() {
+        let mut ctx = tests_cfg::app::get_app_context().await;
+        ctx.storage = Storage::single(crate::storage::drivers::mem::new()).into();
+        let log = StorageDeliveryLog::new("mail_deliveries");
+
+        let record = DeliveryRecord::new(&email(), &Ok(Some("<id@framework.com>".to_string())));
+        assert_eq!(record.outcome, DeliveryOutcome::Sent);
+        log.record(&ctx, &record).await.unwrap();
+
+        let records: Vec<_> = ctx
+            .storage
+            .list(Path::new("mail_deliveries/"))
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(records.len(), 1);
+
+        let content: String = ctx.storage.download(&records[0].path).await.unwrap();
+        let stored: DeliveryRecord = serde_json::from_str(&content).unwrap();
+        assert_eq!(stored, record);
+
+        let failed = DeliveryRecord::new(
+            &email(),
+            &Err(crate::Error::Message("connection refused".to_string())),
+        );
+        assert_eq!(failed.outcome, DeliveryOutcome::Failed);
+        assert_eq!(failed.error.as_deref(), Some("connection refused"));
+    }
+}
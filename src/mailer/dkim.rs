@@ -0,0 +1,98 @@
+//! DKIM signing of outgoing messages, configured with `mailer.dkim`, see
+//! [`crate::config::DkimConfig`].
+
+use lettre::message::dkim::{DkimConfig, DkimSigningAlgorithm, DkimSigningKey};
+
+use crate::{config, Error, Result};
+
+/// Reads the private key and builds the signing configuration.
+///
+/// # Errors
+///
+/// When the private key cannot be read or parsed.
+pub fn load(config: &config::DkimConfig) -> Result<DkimConfig> {
+    let path = &config.private_key_path;
+    let key = std::fs::read_to_string(path).map_err(|err| {
+        Error::Message(format!(
+            "cannot read DKIM private key `{}`: {err}",
+            path.display()
+        ))
+    })?;
+
+    let algorithm = match config.algorithm {
+        config::DkimAlgorithm::Rsa => DkimSigningAlgorithm::Rsa,
+        config::DkimAlgorithm::Ed25519 => DkimSigningAlgorithm::Ed25519,
+    };
+    let key = DkimSigningKey::new(key.trim(), algorithm).map_err(|err| {
+        Error::Message(format!(
+            "invalid DKIM private key `{}`: {err}",
+            path.display()
+        ))
+    })?;
+
+    Ok(DkimConfig::default_config(
+        config.selector.clone(),
+        config.domain.clone(),
+        key,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use tree_fs::TreeBuilder;
+
+    use super::*;
+    use crate::mailer::{Email, EmailSender};
+
+    fn config(path: std::path::PathBuf) -> config::DkimConfig {
+        config::DkimConfig {
+            selector: "mail".to_string(),
+            domain: "framework.com".to_string(),
+            private_key_path: path,
+            algorithm: config::DkimAlgorithm::Ed25519,
+        }
+    }
+
+    #[tokio::test]
+    async fn can_sign_messages() {
+        let tree_fs = TreeBuilder::default()
+            .drop(true)
+            .add("dkim.key", "AQIDBAUGBwgJCgsMDQ4PEBESExQVFhcYGRobHB0eHyA=\n")
+            .create()
+            .unwrap();
+
+        let sender =
+            EmailSender::stub().with_dkim(load(&config(tree_fs.root.join("dkim.key"))).unwrap());
+        sender
+            .mail(&Email {
+                from: Some("test@framework.com".to_string()),
+                to: "user1@framework.com".into(),
+                subject: "Email Subject".to_string(),
+                text: "Welcome".to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let message = &sender.transport.as_stub().unwrap().messages()[0].1;
+        assert!(message.contains("DKIM-Signature: "));
+        assert!(message.contains("a=ed25519-sha256"));
+        assert!(message.contains("d=framework.com"));
+        assert!(message.contains("s=mail"));
+    }
+
+    #[test]
+    fn fails_on_missing_or_invalid_key() {
+        let tree_fs = TreeBuilder::default()
+            .drop(true)
+            .add("dkim.key", "not a key")
+            .create()
+            .unwrap();
+
+        let err = load(&config(tree_fs.root.join("missing.key"))).unwrap_err();
+        assert!(err.to_string().starts_with("cannot read DKIM private key"));
+
+        let err = load(&config(tree_fs.root.join("dkim.key"))).unwrap_err();
+        assert!(err.to_string().starts_with("invalid DKIM private key"));
+    }
+}
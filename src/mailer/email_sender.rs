@@ -1,32 +1,38 @@
-//! This module defines an [`EmailSender`] responsible for sending emails using
-//! either the SMTP protocol. It includes an asynchronous method `mail` for
-//! sending emails with options like sender, recipient, subject, and content.
+//! This module defines an [`EmailSender`] responsible for sending emails
+//! through a [`MailTransport`] (SMTP, file, sendmail, stub or a custom one).
+//! It includes an asynchronous method `mail` for sending emails with options
+//! like sender, recipient, subject, and content.
+
+use std::{path::Path, sync::Arc};
 
 use lettre::{
-    message::MultiPart,
+    message::{
+        dkim::DkimConfig,
+        header::{HeaderName, HeaderValue},
+        MultiPart,
+    },
     transport::smtp::{authentication::Credentials, extension::ClientId},
-    AsyncTransport, Message, Tokio1Executor, Transport,
+    Message, Tokio1Executor,
 };
 use tracing::error;
 
-use super::{Email, Result, DEFAULT_FROM_SENDER};
-use crate::{config, errors::Error};
-
-/// An enumeration representing the possible transport methods for sending
-/// emails.
-#[derive(Clone, Debug)]
-pub enum EmailTransport {
-    /// SMTP (Simple Mail Transfer Protocol) transport.
-    Smtp(lettre::AsyncSmtpTransport<lettre::Tokio1Executor>),
-    /// Test/stub transport for testing purposes.
-    Test(lettre::transport::stub::StubTransport),
-}
+#[cfg(feature = "testing")]
+use super::transport::SentMail;
+use super::{
+    delivery::{DeliveryLog, DeliveryRecord, Interceptor},
+    transport::{FileTransport, MailTransport, SendmailTransport, SmtpTransport, StubTransport},
+    Email, Result, DEFAULT_FROM_SENDER,
+};
+use crate::{app::AppContext, config, Error};
 
 /// A structure representing the email sender, encapsulating the chosen
-/// transport method.
+/// transport, the interceptors run before sending and the delivery log.
 #[derive(Clone, Debug)]
 pub struct EmailSender {
-    pub transport: EmailTransport,
+    pub transport: Arc<dyn MailTransport>,
+    interceptors: Vec<Arc<dyn Interceptor>>,
+    delivery_log: Option<Arc<dyn DeliveryLog>>,
+    dkim: Option<Arc<DkimConfig>>,
 }
 
 #[cfg(feature = "testing")]
@@ -37,6 +43,62 @@ pub struct Deliveries {
 }
 
 impl EmailSender {
+    /// Creates a new `EmailSender` using the given transport.
+    #[must_use]
+    pub fn new(transport: impl MailTransport + 'static) -> Self {
+        Self {
+            transport: Arc::new(transport),
+            interceptors: Vec::new(),
+            delivery_log: None,
+            dkim: None,
+        }
+    }
+
+    /// Adds an interceptor, run by the mailer worker before sending, after
+    /// the interceptors added before it.
+    #[must_use]
+    pub fn with_interceptor(mut self, interceptor: impl Interceptor + 'static) -> Self {
+        self.interceptors.push(Arc::new(interceptor));
+        self
+    }
+
+    /// Signs the messages with DKIM before handing them to the transport,
+    /// see [`super::dkim::load`].
+    #[must_use]
+    pub fn with_dkim(mut self, dkim: DkimConfig) -> Self {
+        self.dkim = Some(Arc::new(dkim));
+        self
+    }
+
+    /// Returns the interceptors, in the order they run.
+    #[must_use]
+    pub fn interceptors(&self) -> &[Arc<dyn Interceptor>] {
+        &self.interceptors
+    }
+
+    /// Sets the log recording every delivery of the mailer worker.
+    #[must_use]
+    pub fn with_delivery_log(mut self, delivery_log: impl DeliveryLog + 'static) -> Self {
+        self.delivery_log = Some(Arc::new(delivery_log));
+        self
+    }
+
+    /// Records a delivery in the delivery log, if any. Failing to record
+    /// is logged, and does not fail the delivery.
+    pub async fn log_delivery(
+        &self,
+        ctx: &AppContext,
+        email: &Email,
+        result: &Result<Option<String>>,
+    ) {
+        if let Some(delivery_log) = &self.delivery_log {
+            let record = DeliveryRecord::new(email, result);
+            if let Err(err) = delivery_log.record(ctx, &record).await {
+                error!(err = err.to_string(), "mail delivery log error");
+            }
+        }
+    }
+
     /// Creates a new `EmailSender` using the SMTP transport method based on the
     /// provided SMTP configuration.
     ///
@@ -65,35 +127,63 @@ impl EmailSender {
             email_builder = email_builder.hello_name(ClientId::Domain(hello_name.clone()));
         }
 
-        Ok(Self {
-            transport: EmailTransport::Smtp(email_builder.build()),
-        })
+        Ok(Self::new(SmtpTransport(email_builder.build())))
+    }
+
+    /// Creates a new `EmailSender` writing `.eml` files to the given folder.
+    ///
+    /// # Errors
+    ///
+    /// When the folder cannot be created
+    pub fn file(path: &Path) -> Result<Self> {
+        Ok(Self::new(FileTransport::new(path)?))
+    }
+
+    /// Creates a new `EmailSender` piping messages to `sendmail`, or to the
+    /// given command.
+    #[must_use]
+    pub fn sendmail(command: Option<&str>) -> Self {
+        Self::new(SendmailTransport::new(command))
     }
 
     #[must_use]
     pub fn stub() -> Self {
-        Self {
-            transport: EmailTransport::Test(lettre::transport::stub::StubTransport::new_ok()),
-        }
+        Self::new(StubTransport::default())
     }
 
     #[cfg(feature = "testing")]
     #[must_use]
     pub fn deliveries(&self) -> Deliveries {
-        if let EmailTransport::Test(stub) = &self.transport {
+        if let Some(stub) = self.transport.as_stub() {
+            let messages = stub.messages();
             return Deliveries {
-                count: stub.messages().len(),
-                messages: stub
-                    .messages()
-                    .iter()
-                    .map(|(_, content)| content.to_string())
-                    .collect(),
+                count: messages.len(),
+                messages: messages.into_iter().map(|(_, content)| content).collect(),
             };
         }
 
         Deliveries::default()
     }
 
+    /// Returns the messages sent through the stub transport, oldest first.
+    /// Empty for other transports.
+    #[cfg(feature = "testing")]
+    #[must_use]
+    pub fn sent_mails(&self) -> Vec<SentMail> {
+        self.transport
+            .as_stub()
+            .map(StubTransport::mails)
+            .unwrap_or_default()
+    }
+
+    /// Forgets the messages sent through the stub transport.
+    #[cfg(feature = "testing")]
+    pub fn clear_deliveries(&self) {
+        if let Some(stub) = self.transport.as_stub() {
+            stub.clear();
+        }
+    }
+
     /// Sends an email using the configured transport method.
     ///
     /// # Errors
@@ -101,30 +191,66 @@ impl EmailSender {
     /// When email doesn't send successfully or has an error to build the
     /// message
     pub async fn mail(&self, email: &Email) -> Result<()> {
-        let content = MultiPart::alternative_plain_html(email.text.clone(), email.html.clone());
-        let mut builder = Message::builder()
-            .from(
-                email
-                    .from
-                    .clone()
-                    .unwrap_or_else(|| DEFAULT_FROM_SENDER.to_string())
-                    .parse()?,
-            )
-            .to(email.to.parse()?);
-
-        if let Some(bcc) = &email.bcc {
-            builder = builder.bcc(bcc.parse()?);
+        self.deliver(email).await.map(|_| ())
+    }
+
+    /// Sends an email using the configured transport method, and returns the
+    /// `Message-ID` header of the sent message.
+    ///
+    /// # Errors
+    ///
+    /// When email doesn't send successfully or has an error to build the
+    /// message
+    pub async fn deliver(&self, email: &Email) -> Result<Option<String>> {
+        let mut content = MultiPart::alternative_plain_html(email.text.clone(), email.html.clone());
+
+        let (inline, attached): (Vec<_>, Vec<_>) =
+            email.attachments.iter().partition(|a| a.is_inline());
+        if !inline.is_empty() {
+            let mut related = MultiPart::related().multipart(content);
+            for attachment in inline {
+                related = related.singlepart(attachment.to_part()?);
+            }
+            content = related;
+        }
+        if !attached.is_empty() {
+            let mut mixed = MultiPart::mixed().multipart(content);
+            for attachment in attached {
+                mixed = mixed.singlepart(attachment.to_part()?);
+            }
+            content = mixed;
+        }
+        let mut builder = Message::builder().from(
+            email
+                .from
+                .clone()
+                .unwrap_or_else(|| DEFAULT_FROM_SENDER.to_string())
+                .parse()?,
+        );
+
+        for to in email.to.mailboxes("to")? {
+            builder = builder.to(to);
         }
 
-        if let Some(cc) = &email.cc {
-            builder = builder.cc(cc.parse()?);
+        for bcc in email.bcc.mailboxes("bcc")? {
+            builder = builder.bcc(bcc);
+        }
+
+        for cc in email.cc.mailboxes("cc")? {
+            builder = builder.cc(cc);
         }
 
         if let Some(reply_to) = &email.reply_to {
             builder = builder.reply_to(reply_to.parse()?);
         }
 
-        let msg = builder
+        for (name, value) in &email.headers {
+            let name = HeaderName::new_from_ascii(name.clone())
+                .map_err(|err| Error::Message(format!("invalid header `{name}`: {err}")))?;
+            builder = builder.raw_header(HeaderValue::new(name, value.clone()));
+        }
+
+        let mut msg = builder
             .subject(email.subject.clone())
             .multipart(content)
             .map_err(|error| {
@@ -132,16 +258,13 @@ impl EmailSender {
                 error
             })?;
 
-        match &self.transport {
-            EmailTransport::Smtp(xp) => {
-                xp.send(msg).await?;
-            }
-            EmailTransport::Test(xp) => {
-                xp.send(&msg)
-                    .map_err(|e| Error::Message(format!("sending email error: {e}")))?;
-            }
-        };
-        Ok(())
+        if let Some(dkim) = &self.dkim {
+            msg.sign(dkim);
+        }
+
+        let message_id = msg.headers().get_raw("Message-ID").map(ToString::to_string);
+        self.transport.send(email, &msg).await?;
+        Ok(message_id)
     }
 }
 
@@ -149,17 +272,16 @@ impl EmailSender {
 mod tests {
 
     use insta::{assert_debug_snapshot, with_settings};
-    use lettre::transport::stub::StubTransport;
+
+    use std::collections::BTreeMap;
 
     use super::*;
+    use crate::mailer::{Attachment, Recipients};
 
     #[tokio::test]
     async fn can_send_email() {
-        let stub = StubTransport::new_ok();
-
-        let sender = EmailSender {
-            transport: EmailTransport::Test(stub.clone()),
-        };
+        let stub = StubTransport::default();
+        let sender = EmailSender::new(stub.clone());
 
         let html = r"
 ;<html>
@@ -170,13 +292,15 @@ mod tests {
 
         let data = Email {
             from: Some("test@framework.com".to_string()),
-            to: "user1@framework.com".to_string(),
+            to: "user1@framework.com".into(),
             reply_to: None,
             subject: "Email Subject".to_string(),
             text: "Welcome".to_string(),
             html: html.to_string(),
-            bcc: None,
-            cc: None,
+            bcc: Recipients::new(),
+            cc: Recipients::new(),
+            attachments: vec![],
+            headers: BTreeMap::new(),
         };
         assert!(sender.mail(&data).await.is_ok());
 
@@ -187,4 +311,95 @@ mod tests {
             assert_debug_snapshot!(stub.messages());
         });
     }
+
+    #[tokio::test]
+    async fn can_send_email_with_attachments() {
+        let stub = StubTransport::default();
+        let sender = EmailSender::new(stub.clone());
+
+        let data = Email {
+            from: Some("test@framework.com".to_string()),
+            to: "user1@framework.com".into(),
+            subject: "Invoice".to_string(),
+            text: "Your invoice".to_string(),
+            html: r#"<img src="cid:logo"> Your invoice"#.to_string(),
+            attachments: vec![
+                Attachment::from_bytes("invoice.pdf", b"%PDF-1.4".to_vec())
+                    .with_content_type("application/pdf"),
+                Attachment::from_bytes("logo.png", b"logo".to_vec())
+                    .with_content_type("image/png")
+                    .inline("logo"),
+            ],
+            ..Default::default()
+        };
+        assert!(sender.mail(&data).await.is_ok());
+
+        let messages = stub.messages();
+        let (_, message) = messages.first().unwrap();
+        assert!(message.contains("multipart/mixed"));
+        assert!(message.contains("multipart/related"));
+        assert!(message.contains("multipart/alternative"));
+        assert!(message.contains("Content-Disposition: attachment; filename=\"invoice.pdf\""));
+        assert!(message.contains("Content-ID: <logo>"));
+        assert!(message.contains("Content-Disposition: inline"));
+
+        let unresolved = Email {
+            to: "user1@framework.com".into(),
+            attachments: vec![Attachment::from_storage("logo.png", "assets/logo.png")],
+            ..Default::default()
+        };
+        assert!(sender.mail(&unresolved).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn can_send_email_to_many() {
+        let stub = StubTransport::default();
+        let sender = EmailSender::new(stub.clone());
+
+        let data = Email {
+            from: Some("test@framework.com".to_string()),
+            to: Recipients::from(vec!["user1@framework.com", "user2@framework.com"]),
+            cc: Recipients::new().add_named("Jane Doe", "jane@framework.com"),
+            subject: "Email Subject".to_string(),
+            text: "Welcome".to_string(),
+            ..Default::default()
+        };
+        assert!(sender.mail(&data).await.is_ok());
+
+        let messages = stub.messages();
+        let (envelope, message) = messages.first().unwrap();
+        assert_eq!(envelope.to().len(), 3);
+        assert!(message.contains("To: user1@framework.com, user2@framework.com"));
+        assert!(message.contains("Jane Doe"));
+        assert!(message.contains("<jane@framework.com>"));
+    }
+
+    #[tokio::test]
+    async fn can_send_email_with_headers() {
+        let stub = StubTransport::default();
+        let sender = EmailSender::new(stub.clone());
+
+        let mut data = Email {
+            from: Some("test@framework.com".to_string()),
+            to: "user1@framework.com".into(),
+            subject: "Email Subject".to_string(),
+            ..Default::default()
+        };
+        data.headers.insert(
+            "List-Unsubscribe".to_string(),
+            "<https://example.com/unsubscribe>".to_string(),
+        );
+
+        let message_id = sender.deliver(&data).await.unwrap();
+        assert!(message_id.is_some());
+
+        let messages = stub.messages();
+        let (_, message) = messages.first().unwrap();
+        assert!(message.contains("List-Unsubscribe: <https://example.com/unsubscribe>"));
+        assert!(message.contains(&format!("Message-ID: {}", message_id.unwrap())));
+
+        data.headers
+            .insert("Invalid Header".to_string(), "value".to_string());
+        assert!(sender.mail(&data).await.is_err());
+    }
 }
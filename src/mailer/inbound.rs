@@ -0,0 +1,581 @@
+//! # Inbound Emails
+//!
+//! Parses raw RFC 822 messages into an [`InboundEmail`] and routes it, by
+//! recipient address, to the worker registered for it. Messages arrive from
+//! a mail relay posting to the webhook served by [`InboundRouter::routes`],
+//! or from a maildir folder read by the [`MaildirTask`].
+//!
+//! Routes are regular expressions matched, case-insensitively, against each
+//! recipient; the first matching route wins. The envelope recipient headers,
+//! `Delivered-To` and `X-Original-To`, are only trustworthy when the relay
+//! sets them: otherwise they come from the sender, who can use them to reach
+//! any route.
+//!
+//! ```rust,ignore
+//! let inbound = InboundRouter::new()
+//!     .route::<SupportReplyWorker>(r"^support\+.*@example\.com$")?
+//!     .route::<CatchAllWorker>(".*")?
+//!     .with_secret(&std::env::var("INBOUND_EMAIL_SECRET")?);
+//!
+//! // in `Hooks::routes`
+//! AppRoutes::with_default_routes().add_route(inbound.clone().routes()?)
+//!
+//! // in `Hooks::register_tasks`
+//! tasks.register(MaildirTask::new(inbound));
+//! ```
+//!
+//! Handler workers are regular background workers taking the email as
+//! arguments:
+//!
+//! ```rust,ignore
+//! #[async_trait]
+//! impl BackgroundWorker<InboundEmail> for SupportReplyWorker {
+//!     fn build(ctx: &AppContext) -> Self {
+//!         Self { ctx: ctx.clone() }
+//!     }
+//!
+//!     async fn perform(&self, email: InboundEmail) -> Result<()> {
+//!         // attach `email.text` to the ticket found from `email.to`
+//!         Ok(())
+//!     }
+//! }
+//! ```
+use std::{borrow::Cow, collections::BTreeMap, path::Path, sync::Arc};
+
+use async_trait::async_trait;
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{header::AUTHORIZATION, HeaderMap},
+    routing::post,
+};
+use chrono::{DateTime, Utc};
+use futures_util::future::BoxFuture;
+use mail_parser::{Addr, Address, MessageParser, MimeHeaders};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+use super::Attachment;
+use crate::{
+    app::AppContext,
+    bgworker::BackgroundWorker,
+    controller::{format, Routes},
+    task::{Task, TaskInfo, Vars},
+    Error, Result,
+};
+
+/// The prefix of the inbound email webhook.
+pub const ROUTE_PREFIX: &str = "_inbound_emails";
+
+/// Headers holding the envelope recipient, which is not in `To` or `Cc`
+/// for `Bcc` deliveries. They are written by the receiving relay, but a
+/// sender can set them too when the relay does not overwrite them.
+const ENVELOPE_HEADERS: [&str; 2] = ["Delivered-To", "X-Original-To"];
+
+/// A received email.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct InboundEmail {
+    pub message_id: Option<String>,
+    /// The `In-Reply-To` header, to thread replies.
+    pub in_reply_to: Option<String>,
+    /// The address of the `From` header, without display name.
+    pub from: Option<String>,
+    /// The addresses of the `To` header.
+    pub to: Vec<String>,
+    pub cc: Vec<String>,
+    pub reply_to: Option<String>,
+    pub subject: String,
+    pub text: String,
+    pub html: String,
+    pub attachments: Vec<Attachment>,
+    /// The headers, the first value of each.
+    pub headers: BTreeMap<String, String>,
+    pub received_at: DateTime<Utc>,
+}
+
+impl InboundEmail {
+    /// Parses a raw RFC 822 message.
+    ///
+    /// # Errors
+    ///
+    /// When the message has no headers.
+    pub fn parse(raw: &[u8]) -> Result<Self> {
+        let message = MessageParser::default()
+            .parse(raw)
+            .filter(|message| !message.headers().is_empty())
+            .ok_or_else(|| Error::BadRequest("cannot parse inbound email".to_string()))?;
+
+        let mut headers = BTreeMap::new();
+        for (name, value) in message.headers_raw() {
+            headers
+                .entry(name.to_string())
+                .or_insert_with(|| value.trim().to_string());
+        }
+
+        let attachments = message
+            .attachments()
+            .map(|part| {
+                let filename = part.attachment_name().unwrap_or("attachment").to_string();
+                let mut attachment = Attachment::from_bytes(filename, part.contents().to_vec());
+                if let Some(content_type) = part.content_type() {
+                    attachment = attachment.with_content_type(match content_type.subtype() {
+                        Some(subtype) => format!("{}/{subtype}", content_type.ctype()),
+                        None => content_type.ctype().to_string(),
+                    });
+                }
+                if let Some(content_id) = part.content_id() {
+                    attachment = attachment.inline(content_id);
+                }
+                attachment
+            })
+            .collect();
+
+        Ok(Self {
+            message_id: message.message_id().map(ToString::to_string),
+            in_reply_to: message.in_reply_to().as_text().map(ToString::to_string),
+            from: message.from().and_then(first_address),
+            to: message.to().map(addresses).unwrap_or_default(),
+            cc: message.cc().map(addresses).unwrap_or_default(),
+            reply_to: message.reply_to().and_then(first_address),
+            subject: message.subject().unwrap_or_default().to_string(),
+            text: message
+                .body_text(0)
+                .map(Cow::into_owned)
+                .unwrap_or_default(),
+            html: message
+                .body_html(0)
+                .map(Cow::into_owned)
+                .unwrap_or_default(),
+            attachments,
+            headers,
+            received_at: Utc::now(),
+        })
+    }
+
+    /// Returns the addresses the email was delivered to: the `To` and `Cc`
+    /// headers, then the envelope recipient headers. The latter are only
+    /// trustworthy when the relay sets them.
+    #[must_use]
+    pub fn recipients(&self) -> Vec<&str> {
+        self.to
+            .iter()
+            .chain(&self.cc)
+            .map(String::as_str)
+            .chain(
+                ENVELOPE_HEADERS
+                    .iter()
+                    .filter_map(|name| self.headers.get(*name))
+                    .map(|value| value.trim_matches(|c| c == '<' || c == '>')),
+            )
+            .collect()
+    }
+}
+
+fn addresses(address: &Address<'_>) -> Vec<String> {
+    address
+        .iter()
+        .filter_map(Addr::address)
+        .map(ToString::to_string)
+        .collect()
+}
+
+fn first_address(address: &Address<'_>) -> Option<String> {
+    address
+        .first()
+        .and_then(Addr::address)
+        .map(ToString::to_string)
+}
+
+type Enqueue =
+    Arc<dyn Fn(AppContext, InboundEmail) -> BoxFuture<'static, Result<()>> + Send + Sync>;
+
+#[derive(Clone)]
+struct Route {
+    pattern: Regex,
+    worker: String,
+    enqueue: Enqueue,
+}
+
+impl std::fmt::Debug for Route {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Route")
+            .field("pattern", &self.pattern.as_str())
+            .field("worker", &self.worker)
+            .finish()
+    }
+}
+
+/// Routes inbound emails to handler workers by recipient address.
+#[derive(Debug, Clone, Default)]
+pub struct InboundRouter {
+    routes: Vec<Route>,
+    secret: Option<String>,
+}
+
+impl InboundRouter {
+    /// Creates a router without routes.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Routes the emails sent to an address matching the pattern to the
+    /// worker `W`. Routes are tried in the order they are added.
+    ///
+    /// # Errors
+    ///
+    /// When the pattern is not a valid regular expression.
+    pub fn route<W>(mut self, pattern: &str) -> Result<Self>
+    where
+        W: BackgroundWorker<InboundEmail> + 'static,
+    {
+        let pattern = RegexBuilder::new(pattern)
+            .case_insensitive(true)
+            .build()
+            .map_err(|err| {
+                Error::Message(format!("invalid inbound email route `{pattern}`: {err}"))
+            })?;
+        self.routes.push(Route {
+            pattern,
+            worker: W::class_name(),
+            enqueue: Arc::new(
+                |ctx: AppContext, email: InboundEmail| -> BoxFuture<'static, Result<()>> {
+                    Box::pin(async move { W::perform_later(&ctx, email).await })
+                },
+            ),
+        });
+        Ok(self)
+    }
+
+    /// Requires the webhook requests to carry an `Authorization: Bearer
+    /// <secret>` header. The webhook cannot be served without a secret.
+    #[must_use]
+    pub fn with_secret(mut self, secret: &str) -> Self {
+        self.secret = Some(secret.to_string());
+        self
+    }
+
+    /// Returns the name of the worker the email is routed to.
+    #[must_use]
+    pub fn worker_for(&self, email: &InboundEmail) -> Option<&str> {
+        self.find(email).map(|route| route.worker.as_str())
+    }
+
+    fn find(&self, email: &InboundEmail) -> Option<&Route> {
+        let recipients = email.recipients();
+        self.routes.iter().find(|route| {
+            recipients
+                .iter()
+                .any(|recipient| route.pattern.is_match(recipient))
+        })
+    }
+
+    /// Enqueues the email to the worker of the first matching route, and
+    /// returns the name of that worker. Unrouted emails are logged and
+    /// dropped.
+    ///
+    /// # Errors
+    ///
+    /// When the email cannot be enqueued.
+    pub async fn dispatch(&self, ctx: &AppContext, email: InboundEmail) -> Result<Option<String>> {
+        let Some(route) = self.find(&email) else {
+            tracing::warn!(
+                message_id = ?email.message_id,
+                recipients = ?email.recipients(),
+                "no route for inbound email"
+            );
+            return Ok(None);
+        };
+        tracing::debug!(
+            message_id = ?email.message_id,
+            worker = %route.worker,
+            "routing inbound email"
+        );
+        (route.enqueue)(ctx.clone(), email).await?;
+        Ok(Some(route.worker.clone()))
+    }
+
+    /// Parses and dispatches a raw message.
+    ///
+    /// # Errors
+    ///
+    /// When the message cannot be parsed or enqueued.
+    pub async fn receive(&self, ctx: &AppContext, raw: &[u8]) -> Result<Option<String>> {
+        self.dispatch(ctx, InboundEmail::parse(raw)?).await
+    }
+
+    /// Dispatches the messages of the `new` folder of a maildir, then moves
+    /// them to its `cur` folder. Messages failing to parse or enqueue are
+    /// logged and left in place. Returns the number of processed messages.
+    ///
+    /// # Errors
+    ///
+    /// When the folders cannot be read or the messages cannot be moved.
+    pub async fn process_maildir(&self, ctx: &AppContext, path: &Path) -> Result<usize> {
+        let new = path.join("new");
+        let cur = path.join("cur");
+        std::fs::create_dir_all(&cur)?;
+
+        let mut files = std::fs::read_dir(&new)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        files.retain(|file| file.is_file());
+        files.sort();
+
+        let mut processed = 0;
+        for file in files {
+            let raw = std::fs::read(&file)?;
+            if let Err(err) = self.receive(ctx, &raw).await {
+                tracing::error!(file = %file.display(), err = %err, "cannot process inbound email");
+                continue;
+            }
+            let name = file
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            std::fs::rename(&file, cur.join(format!("{name}:2,S")))?;
+            processed += 1;
+        }
+        Ok(processed)
+    }
+
+    /// Defines the webhook route, `POST /_inbound_emails` with the raw
+    /// message as body.
+    ///
+    /// # Errors
+    ///
+    /// When no secret is set with [`InboundRouter::with_secret`], as anyone
+    /// could then post emails to the workers.
+    pub fn routes(self) -> Result<Routes> {
+        if self.secret.as_deref().unwrap_or_default().is_empty() {
+            return Err(Error::Message(
+                "the inbound email webhook requires a secret, see `InboundRouter::with_secret`"
+                    .to_string(),
+            ));
+        }
+        let router = Arc::new(self);
+        Ok(Routes::at(ROUTE_PREFIX).add(
+            "/",
+            post(
+                move |State(ctx): State<AppContext>, headers: HeaderMap, body: Bytes| async move {
+                    router.authorize(&headers)?;
+                    let worker = router.receive(&ctx, &body).await?;
+                    format::json(serde_json::json!({ "worker": worker }))
+                },
+            ),
+        ))
+    }
+
+    fn authorize(&self, headers: &HeaderMap) -> Result<()> {
+        let Some(secret) = self.secret.as_deref().filter(|secret| !secret.is_empty()) else {
+            return Err(Error::Unauthorized(
+                "no inbound email secret is set".to_string(),
+            ));
+        };
+        let token = headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        match token {
+            Some(token) if constant_time_eq(token.as_bytes(), secret.as_bytes()) => Ok(()),
+            _ => Err(Error::Unauthorized(
+                "invalid inbound email secret".to_string(),
+            )),
+        }
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Processes the inbound emails of a maildir folder, given as the `path`
+/// variable: `cargo loco task inbound_maildir path=/var/mail/support`.
+#[derive(Debug, Clone)]
+pub struct MaildirTask {
+    router: InboundRouter,
+}
+
+impl MaildirTask {
+    #[must_use]
+    pub const fn new(router: InboundRouter) -> Self {
+        Self { router }
+    }
+}
+
+#[async_trait]
+impl Task for MaildirTask {
+    fn task(&self) -> TaskInfo {
+        TaskInfo {
+            name: "inbound_maildir".to_string(),
+            detail: "Route the inbound emails of a maildir folder to their workers".to_string(),
+        }
+    }
+
+    async fn run(&self, app_context: &AppContext, vars: &Vars) -> Result<()> {
+        let path = vars.cli_arg("path")?;
+        let processed = self
+            .router
+            .process_maildir(app_context, Path::new(path))
+            .await?;
+        tracing::info!(processed, path = %path, "processed inbound emails");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use tree_fs::TreeBuilder;
+
+    use super::*;
+    use crate::{mailer::AttachmentContent, tests_cfg};
+
+    const RAW: &str = "From: Jane Doe <jane@example.com>\r
+To: Support <support+1234@example.com>\r
+Cc: team@example.com\r
+Subject: Re: Ticket 1234\r
+Message-ID: <reply-1@example.com>\r
+In-Reply-To: <ticket-1234@example.com>\r
+MIME-Version: 1.0\r
+Content-Type: multipart/mixed; boundary=\"b1\"\r
+\r
+--b1\r
+Content-Type: text/plain; charset=utf-8\r
+\r
+Thanks, it works now.\r
+--b1\r
+Content-Type: text/plain; name=\"log.txt\"\r
+Content-Disposition: attachment; filename=\"log.txt\"\r
+\r
+all good\r
+--b1--\r
+";
+
+    static RECEIVED: Mutex<Vec<InboundEmail>> = Mutex::new(Vec::new());
+
+    struct SupportWorker;
+
+    #[async_trait]
+    impl BackgroundWorker<InboundEmail> for SupportWorker {
+        fn build(_ctx: &AppContext) -> Self {
+            Self
+        }
+
+        async fn perform(&self, email: InboundEmail) -> Result<()> {
+            RECEIVED.lock().unwrap().push(email);
+            Ok(())
+        }
+    }
+
+    struct BillingWorker;
+
+    #[async_trait]
+    impl BackgroundWorker<InboundEmail> for BillingWorker {
+        fn build(_ctx: &AppContext) -> Self {
+            Self
+        }
+
+        async fn perform(&self, _email: InboundEmail) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn router() -> InboundRouter {
+        InboundRouter::new()
+            .route::<BillingWorker>(r"^billing@example\.com$")
+            .unwrap()
+            .route::<SupportWorker>(r"^support\+\d+@example\.com$")
+            .unwrap()
+    }
+
+    #[test]
+    fn can_parse_raw_messages() {
+        let email = InboundEmail::parse(RAW.as_bytes()).unwrap();
+
+        assert_eq!(email.message_id.as_deref(), Some("reply-1@example.com"));
+        assert_eq!(
+            email.in_reply_to.as_deref(),
+            Some("ticket-1234@example.com")
+        );
+        assert_eq!(email.from.as_deref(), Some("jane@example.com"));
+        assert_eq!(email.to, vec!["support+1234@example.com".to_string()]);
+        assert_eq!(email.cc, vec!["team@example.com".to_string()]);
+        assert_eq!(email.subject, "Re: Ticket 1234");
+        assert_eq!(email.text.trim(), "Thanks, it works now.");
+        assert_eq!(email.attachments.len(), 1);
+        assert_eq!(email.attachments[0].filename, "log.txt");
+        assert_eq!(
+            email.attachments[0].content,
+            AttachmentContent::Bytes {
+                data: b"all good".to_vec()
+            }
+        );
+
+        assert!(InboundEmail::parse(b"").is_err());
+    }
+
+    #[test]
+    fn can_route_by_recipient() {
+        let router = router();
+        let mut email = InboundEmail::parse(RAW.as_bytes()).unwrap();
+        assert_eq!(router.worker_for(&email), Some("SupportWorker"));
+
+        email.to = vec!["someone@example.com".to_string()];
+        assert_eq!(router.worker_for(&email), None);
+
+        email.headers.insert(
+            "Delivered-To".to_string(),
+            "<Billing@example.com>".to_string(),
+        );
+        assert_eq!(router.worker_for(&email), Some("BillingWorker"));
+
+        assert!(InboundRouter::new().route::<SupportWorker>("(").is_err());
+    }
+
+    #[tokio::test]
+    async fn can_process_maildir() {
+        let ctx = tests_cfg::app::get_app_context().await;
+        let tree_fs = TreeBuilder::default()
+            .drop(true)
+            .add("mail/new/1.eml", RAW)
+            .add("mail/new/2.eml", "")
+            .create()
+            .unwrap();
+        let maildir = tree_fs.root.join("mail");
+
+        let processed = router().process_maildir(&ctx, &maildir).await.unwrap();
+
+        assert_eq!(processed, 1);
+        assert!(maildir.join("cur/1.eml:2,S").exists());
+        assert!(maildir.join("new/2.eml").exists());
+        assert!(RECEIVED
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|email| email.subject == "Re: Ticket 1234"));
+    }
+
+    #[test]
+    fn can_authorize_webhook() {
+        let router = router().with_secret("s3cret");
+        let mut headers = HeaderMap::new();
+        assert!(router.authorize(&headers).is_err());
+
+        headers.insert(AUTHORIZATION, "Bearer s3cret".parse().unwrap());
+        assert!(router.authorize(&headers).is_ok());
+    }
+
+    #[test]
+    fn cannot_serve_webhook_without_secret() {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, "Bearer ".parse().unwrap());
+        assert!(router().authorize(&headers).is_err());
+        assert!(router().with_secret("").authorize(&headers).is_err());
+
+        assert!(router().routes().is_err());
+        assert!(router().with_secret("").routes().is_err());
+        assert!(router().with_secret("s3cret").routes().is_ok());
+    }
+}
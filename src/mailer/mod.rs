@@ -2,17 +2,29 @@
 //! trait and its implementation, `Email` structure, and the `MailerWorker` for
 //! asynchronous email processing.
 
+mod attachment;
+pub mod delivery;
+pub mod dkim;
 mod email_sender;
-mod template;
+pub mod inbound;
+pub mod preview;
+mod recipients;
+pub mod template;
+pub mod transport;
+
+use std::collections::BTreeMap;
 
 use async_trait::async_trait;
+pub use attachment::{Attachment, AttachmentContent};
 pub use email_sender::EmailSender;
 use include_dir::Dir;
+pub use recipients::Recipients;
 use serde::{Deserialize, Serialize};
 use tracing::error;
+pub use transport::MailTransport;
 
 use self::template::Template;
-use super::{app::AppContext, Result};
+use super::{app::AppContext, Error, Result};
 use crate::prelude::BackgroundWorker;
 
 pub const DEFAULT_FROM_SENDER: &str = "System <system@example.com>";
@@ -22,11 +34,17 @@ pub const DEFAULT_FROM_SENDER: &str = "System <system@example.com>";
 #[derive(Debug, Clone, Default)]
 pub struct Args {
     pub from: Option<String>,
-    pub to: String,
+    pub to: Recipients,
     pub reply_to: Option<String>,
     pub locals: serde_json::Value,
-    pub bcc: Option<String>,
-    pub cc: Option<String>,
+    pub bcc: Recipients,
+    pub cc: Recipients,
+    /// Files attached to the email, and inline parts referenced from the
+    /// HTML template as `cid:<content_id>`.
+    pub attachments: Vec<Attachment>,
+    /// The locale of the template files, e.g. `de-DE` for `html.de-DE.t`,
+    /// also passed to the templates as `lang`.
+    pub locale: Option<String>,
 }
 
 /// The structure representing an email details.
@@ -34,8 +52,8 @@ pub struct Args {
 pub struct Email {
     /// Mailbox to `From` header
     pub from: Option<String>,
-    /// Mailbox to `To` header
-    pub to: String,
+    /// Mailboxes to `To` header
+    pub to: Recipients,
     /// Mailbox to `ReplyTo` header
     pub reply_to: Option<String>,
     /// Subject header to message
@@ -44,10 +62,55 @@ pub struct Email {
     pub text: String,
     /// HTML template
     pub html: String,
-    /// BCC header to message
-    pub bcc: Option<String>,
-    /// CC header to message
-    pub cc: Option<String>,
+    /// Mailboxes to `Bcc` header
+    #[serde(default)]
+    pub bcc: Recipients,
+    /// Mailboxes to `Cc` header
+    #[serde(default)]
+    pub cc: Recipients,
+    /// Attachments and inline parts
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+    /// Additional headers, e.g. `List-Unsubscribe`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+}
+
+impl Email {
+    /// Checks that the email has recipients and that every mailbox parses.
+    ///
+    /// # Errors
+    ///
+    /// When there is no `To` mailbox or a mailbox is invalid, naming the
+    /// header and the mailbox.
+    pub fn validate(&self) -> Result<()> {
+        if self.to.is_empty() {
+            return Err(Error::Message("email has no `to` mailbox".to_string()));
+        }
+        self.to.mailboxes("to")?;
+        self.cc.mailboxes("cc")?;
+        self.bcc.mailboxes("bcc")?;
+        for (header, mailbox) in [("from", &self.from), ("reply_to", &self.reply_to)] {
+            if let Some(mailbox) = mailbox {
+                mailbox.parse::<lettre::message::Mailbox>().map_err(|err| {
+                    Error::Message(format!("invalid `{header}` mailbox `{mailbox}`: {err}"))
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Downloads the content of the attachments stored in the given storage.
+    ///
+    /// # Errors
+    ///
+    /// When an attachment cannot be downloaded.
+    pub async fn resolve_attachments(&mut self, storage: &crate::storage::Storage) -> Result<()> {
+        for attachment in &mut self.attachments {
+            attachment.resolve(storage).await?;
+        }
+        Ok(())
+    }
 }
 
 /// The options struct for configuring the email sender.
@@ -56,6 +119,9 @@ pub struct Email {
 pub struct MailerOpts {
     pub from: String,
     pub reply_to: Option<String>,
+    /// Inline the `<style>` rules of rendered HTML templates into `style`
+    /// attributes. Requires the `mailer_css_inline` feature.
+    pub inline_css: bool,
 }
 
 /// The `Mailer` trait defines methods for sending emails and processing email
@@ -78,6 +144,7 @@ pub trait Mailer {
 
         email.from = Some(email.from.unwrap_or_else(|| opts.from.clone()));
         email.reply_to = email.reply_to.or_else(|| opts.reply_to.clone());
+        email.validate()?;
 
         MailerWorker::perform_later(ctx, email.clone()).await?;
         Ok(())
@@ -86,18 +153,25 @@ pub trait Mailer {
     /// Renders and sends an email using the provided [`AppContext`], template
     /// directory, and arguments.
     async fn mail_template(ctx: &AppContext, dir: &Dir<'_>, args: Args) -> Result<()> {
-        let content = Template::new(dir).render(&args.locals)?;
+        let mut content = Template::new(dir)
+            .with_locale(args.locale.as_deref())
+            .render(&args.locals)?;
+        if Self::opts().inline_css {
+            content.html = template::inline_css(&content.html)?;
+        }
         Self::mail(
             ctx,
             &Email {
                 from: args.from.clone(),
-                to: args.to.clone(),
+                to: args.to,
                 reply_to: args.reply_to.clone(),
                 subject: content.subject,
                 text: content.text,
                 html: content.html,
-                bcc: args.bcc.clone(),
-                cc: args.cc.clone(),
+                bcc: args.bcc,
+                cc: args.cc,
+                attachments: args.attachments,
+                headers: BTreeMap::new(),
             },
         )
         .await
@@ -123,12 +197,21 @@ impl BackgroundWorker<Email> for MailerWorker {
     }
 
     /// Performs the email sending operation using the provided [`AppContext`]
-    /// and email details.
-    async fn perform(&self, email: Email) -> crate::Result<()> {
+    /// and email details: downloads storage attachments, runs the mailer
+    /// interceptors, sends the email and records the delivery.
+    async fn perform(&self, mut email: Email) -> crate::Result<()> {
         if let Some(mailer) = &self.ctx.mailer {
-            let res = mailer.mail(&email).await;
+            let res = async {
+                email.resolve_attachments(&self.ctx.storage).await?;
+                for interceptor in mailer.interceptors() {
+                    interceptor.intercept(&self.ctx, &mut email).await?;
+                }
+                mailer.deliver(&email).await
+            }
+            .await;
+            mailer.log_delivery(&self.ctx, &email, &res).await;
             match res {
-                Ok(res) => Ok(res),
+                Ok(_) => Ok(()),
                 Err(err) => {
                     error!(err = err.to_string(), "mailer error");
                     Err(err)
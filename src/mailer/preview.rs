@@ -0,0 +1,274 @@
+//! # Mail Previews
+//!
+//! Renders mailer templates with sample locals in the browser, to iterate on
+//! their design without triggering real flows. Mailers declare their
+//! previews by implementing [`Preview`]:
+//!
+//! ```rust,ignore
+//! impl Preview for AuthMailer {
+//!     fn previews() -> Vec<MailPreview> {
+//!         vec![MailPreview::new(
+//!             "welcome",
+//!             &welcome,
+//!             json!({"name": "Jane", "verifyToken": "1234", "domain": "http://localhost:5150"}),
+//!         )]
+//!     }
+//! }
+//! ```
+//!
+//! and are registered in `Hooks::routes`:
+//!
+//! ```rust,ignore
+//! AppRoutes::with_default_routes()
+//!     .add_route(Previews::new().register::<AuthMailer>().routes())
+//! ```
+//!
+//! The list is served at `/_mailers`, and a preview renders the files of a
+//! locale with `?locale=de-DE`. The routes answer `404` outside of the
+//! `development` and `test` environments.
+use std::{fmt::Write, sync::Arc};
+
+use axum::{
+    extract::{Path, Query, State},
+    routing::get,
+};
+use include_dir::Dir;
+use serde::Deserialize;
+
+use super::template::{Content, Template};
+use crate::{
+    app::AppContext,
+    controller::{format, Routes},
+    environment::Environment,
+    Error, Result,
+};
+
+/// The prefix of the preview routes.
+pub const ROUTE_PREFIX: &str = "_mailers";
+
+/// The query of a preview route, e.g. `?locale=de-DE`.
+#[derive(Debug, Deserialize)]
+struct PreviewParams {
+    locale: Option<String>,
+}
+
+/// A template directory rendered with sample locals.
+#[derive(Debug, Clone)]
+pub struct MailPreview {
+    /// The preview name, unique within its mailer.
+    pub name: String,
+    /// The template directory, as given to `Mailer::mail_template`.
+    pub dir: &'static Dir<'static>,
+    /// The sample locals.
+    pub locals: serde_json::Value,
+}
+
+impl MailPreview {
+    /// Creates a new preview.
+    #[must_use]
+    pub fn new(name: &str, dir: &'static Dir<'static>, locals: serde_json::Value) -> Self {
+        Self {
+            name: name.to_string(),
+            dir,
+            locals,
+        }
+    }
+
+    /// Renders the subject, text and HTML of the preview, with the files of
+    /// the given locale.
+    ///
+    /// # Errors
+    ///
+    /// When a template file is missing or cannot be rendered.
+    pub fn render(&self, locale: Option<&str>) -> Result<Content> {
+        Template::new(self.dir)
+            .with_locale(locale)
+            .render(&self.locals)
+    }
+}
+
+/// Declares the previews of a mailer.
+pub trait Preview {
+    /// The mailer name shown in the list, the type name by default.
+    #[must_use]
+    fn name() -> String {
+        let name = std::any::type_name::<Self>();
+        name.rsplit("::").next().unwrap_or(name).to_string()
+    }
+
+    /// The previews of the mailer.
+    fn previews() -> Vec<MailPreview>;
+}
+
+/// The registered mailer previews, served by [`Previews::routes`].
+#[derive(Debug, Clone, Default)]
+pub struct Previews {
+    mailers: Vec<(String, Vec<MailPreview>)>,
+}
+
+impl Previews {
+    /// Creates an empty registry.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the previews of a mailer.
+    #[must_use]
+    pub fn register<P: Preview>(mut self) -> Self {
+        self.mailers.push((P::name(), P::previews()));
+        self
+    }
+
+    /// Returns the preview of the given mailer.
+    #[must_use]
+    pub fn get(&self, mailer: &str, preview: &str) -> Option<&MailPreview> {
+        self.mailers
+            .iter()
+            .find(|(name, _)| name == mailer)
+            .and_then(|(_, previews)| previews.iter().find(|p| p.name == preview))
+    }
+
+    /// Defines and returns the preview routes, under [`ROUTE_PREFIX`].
+    #[must_use]
+    pub fn routes(self) -> Routes {
+        let previews = Arc::new(self);
+        let index_previews = previews.clone();
+        let show_previews = previews.clone();
+        let html_previews = previews;
+
+        Routes::at(ROUTE_PREFIX)
+            .add(
+                "/",
+                get(move |State(ctx): State<AppContext>| async move {
+                    ensure_enabled(&ctx)?;
+                    format::html(&index_previews.index())
+                }),
+            )
+            .add(
+                "/{mailer}/{preview}",
+                get(
+                    move |State(ctx): State<AppContext>,
+                          Path((mailer, preview)): Path<(String, String)>,
+                          Query(params): Query<PreviewParams>| async move {
+                        ensure_enabled(&ctx)?;
+                        let content = show_previews
+                            .find(&mailer, &preview)?
+                            .render(params.locale.as_deref())?;
+                        format::html(&page(&mailer, &preview, params.locale.as_deref(), &content))
+                    },
+                ),
+            )
+            .add(
+                "/{mailer}/{preview}/html",
+                get(
+                    move |State(ctx): State<AppContext>,
+                          Path((mailer, preview)): Path<(String, String)>,
+                          Query(params): Query<PreviewParams>| async move {
+                        ensure_enabled(&ctx)?;
+                        let content = html_previews
+                            .find(&mailer, &preview)?
+                            .render(params.locale.as_deref())?;
+                        format::html(&content.html)
+                    },
+                ),
+            )
+    }
+
+    fn find(&self, mailer: &str, preview: &str) -> Result<&MailPreview> {
+        self.get(mailer, preview).ok_or(Error::NotFound)
+    }
+
+    fn index(&self) -> String {
+        let mut html = String::from(
+            "<!DOCTYPE html><html><head><title>Mailers</title></head><body><h1>Mailers</h1>",
+        );
+        for (mailer, previews) in &self.mailers {
+            let mailer = escape(mailer);
+            let _ = write!(html, "<h2>{mailer}</h2><ul>");
+            for preview in previews {
+                let name = escape(&preview.name);
+                let _ = write!(
+                    html,
+                    r#"<li><a href="/{ROUTE_PREFIX}/{mailer}/{name}">{name}</a></li>"#
+                );
+            }
+            html.push_str("</ul>");
+        }
+        html.push_str("</body></html>");
+        html
+    }
+}
+
+/// Renders the page of a preview: the subject, the HTML part in a frame and
+/// the text part.
+fn page(mailer: &str, preview: &str, locale: Option<&str>, content: &Content) -> String {
+    let mailer = escape(mailer);
+    let preview = escape(preview);
+    let query = locale.map_or_else(String::new, |locale| format!("?locale={}", escape(locale)));
+    format!(
+        r#"<!DOCTYPE html><html><head><title>{mailer} / {preview}</title></head><body>
+<p><a href="/{ROUTE_PREFIX}">Mailers</a> / {mailer} / {preview}</p>
+<h1>{subject}</h1>
+<h2>HTML</h2>
+<iframe src="/{ROUTE_PREFIX}/{mailer}/{preview}/html{query}" style="width: 100%; height: 60vh; border: 1px solid #ccc"></iframe>
+<h2>Text</h2>
+<pre>{text}</pre>
+</body></html>"#,
+        subject = escape(&content.subject),
+        text = escape(&content.text),
+    )
+}
+
+fn ensure_enabled(ctx: &AppContext) -> Result<()> {
+    match ctx.environment {
+        Environment::Development | Environment::Test => Ok(()),
+        _ => Err(Error::NotFound),
+    }
+}
+
+fn escape(input: &str) -> String {
+    ::tera::escape_html(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use include_dir::include_dir;
+
+    use super::*;
+
+    static TEST: Dir<'_> = include_dir!("tests/fixtures/email_template/test");
+
+    struct TestMailer;
+
+    impl Preview for TestMailer {
+        fn previews() -> Vec<MailPreview> {
+            vec![MailPreview::new(
+                "welcome",
+                &TEST,
+                serde_json::json!({"name": "<Jane>", "verifyToken": "1111"}),
+            )]
+        }
+    }
+
+    #[test]
+    fn can_list_and_render_previews() {
+        let previews = Previews::new().register::<TestMailer>();
+        assert!(previews.get("TestMailer", "missing").is_none());
+
+        let index = previews.index();
+        assert!(index.contains("<h2>TestMailer</h2>"));
+        assert!(index.contains(r#"<a href="/_mailers/TestMailer/welcome">welcome</a>"#));
+
+        let content = previews
+            .get("TestMailer", "welcome")
+            .unwrap()
+            .render(None)
+            .unwrap();
+        assert!(content.html.contains("/verify/1111"));
+
+        let page = page("TestMailer", "welcome", Some("de-DE"), &content);
+        assert!(page.contains("<h1>Test &lt;Jane&gt;</h1>"));
+        assert!(page.contains(r#"src="/_mailers/TestMailer/welcome/html?locale=de-DE""#));
+    }
+}
@@ -0,0 +1,191 @@
+//! A list of mailboxes for the `To`, `Cc` and `Bcc` headers.
+//!
+//! Each entry is a mailbox such as `user@example.com` or
+//! `"Jane Doe" <jane@example.com>`. Entries are validated when the email is
+//! handed to [`super::Mailer::mail`].
+//!
+//! ```rust
+//! use loco_rs::mailer::Recipients;
+//!
+//! let to = Recipients::from("ops@example.com").add_named("Jane Doe", "jane@example.com");
+//! assert_eq!(to.len(), 2);
+//! ```
+
+use std::str::FromStr;
+
+use lettre::message::{Mailbox, Mailboxes};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::{Error, Result};
+
+/// A list of mailboxes.
+///
+/// Serialized as a list of strings. A single string or `null` deserialize
+/// too, as `Email` used to hold a single optional mailbox per header.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
+pub struct Recipients(Vec<String>);
+
+impl Recipients {
+    /// Creates an empty list.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    /// Adds a mailbox, e.g. `user@example.com` or `Jane <jane@example.com>`.
+    #[must_use]
+    pub fn add(mut self, mailbox: impl Into<String>) -> Self {
+        self.0.push(mailbox.into());
+        self
+    }
+
+    /// Adds an address with a display name, quoted as needed.
+    #[must_use]
+    pub fn add_named(mut self, name: &str, email: &str) -> Self {
+        let name = name.replace('\\', "\\\\").replace('"', "\\\"");
+        self.0.push(format!("\"{name}\" <{email}>"));
+        self
+    }
+
+    /// Returns `true` when the list is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns the number of entries.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Iterates over the entries.
+    pub fn iter(&self) -> impl Iterator<Item = &String> {
+        self.0.iter()
+    }
+
+    /// Parses the entries into mailboxes. An entry may hold a comma
+    /// separated list of mailboxes.
+    ///
+    /// # Errors
+    ///
+    /// When an entry is not a valid mailbox; the error names the header and
+    /// the entry.
+    pub fn mailboxes(&self, header: &str) -> Result<Vec<Mailbox>> {
+        let mut mailboxes = Vec::with_capacity(self.0.len());
+        for entry in &self.0 {
+            let parsed = Mailboxes::from_str(entry).map_err(|err| {
+                Error::Message(format!("invalid `{header}` mailbox `{entry}`: {err}"))
+            })?;
+            mailboxes.extend(parsed);
+        }
+        Ok(mailboxes)
+    }
+}
+
+impl From<String> for Recipients {
+    fn from(mailbox: String) -> Self {
+        Self(vec![mailbox])
+    }
+}
+
+impl From<&str> for Recipients {
+    fn from(mailbox: &str) -> Self {
+        Self(vec![mailbox.to_string()])
+    }
+}
+
+impl From<Vec<String>> for Recipients {
+    fn from(mailboxes: Vec<String>) -> Self {
+        Self(mailboxes)
+    }
+}
+
+impl From<Vec<&str>> for Recipients {
+    fn from(mailboxes: Vec<&str>) -> Self {
+        mailboxes.into_iter().map(ToString::to_string).collect()
+    }
+}
+
+impl From<Option<String>> for Recipients {
+    fn from(mailbox: Option<String>) -> Self {
+        Self(mailbox.into_iter().collect())
+    }
+}
+
+impl FromIterator<String> for Recipients {
+    fn from_iter<I: IntoIterator<Item = String>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl<'a> IntoIterator for &'a Recipients {
+    type Item = &'a String;
+    type IntoIter = std::slice::Iter<'a, String>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl<'de> Deserialize<'de> for Recipients {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum OneOrMany {
+            One(String),
+            Many(Vec<String>),
+        }
+
+        Ok(match Option::<OneOrMany>::deserialize(deserializer)? {
+            None => Self::new(),
+            Some(OneOrMany::One(mailbox)) if mailbox.is_empty() => Self::new(),
+            Some(OneOrMany::One(mailbox)) => Self::from(mailbox),
+            Some(OneOrMany::Many(mailboxes)) => Self(mailboxes),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_deserialize_legacy_and_lists() {
+        let recipients: Recipients = serde_json::from_str(r#""user@example.com""#).unwrap();
+        assert_eq!(recipients, Recipients::from("user@example.com"));
+
+        let recipients: Recipients = serde_json::from_str("null").unwrap();
+        assert!(recipients.is_empty());
+
+        let recipients: Recipients = serde_json::from_str(r#""""#).unwrap();
+        assert!(recipients.is_empty());
+
+        let recipients: Recipients =
+            serde_json::from_str(r#"["a@example.com", "B <b@example.com>"]"#).unwrap();
+        assert_eq!(recipients.len(), 2);
+        assert_eq!(
+            serde_json::to_string(&recipients).unwrap(),
+            r#"["a@example.com","B <b@example.com>"]"#
+        );
+    }
+
+    #[test]
+    fn can_parse_mailboxes() {
+        let recipients = Recipients::from("a@example.com, b@example.com")
+            .add_named("Doe, \"Jane\"", "jane@example.com");
+
+        let mailboxes = recipients.mailboxes("to").unwrap();
+        assert_eq!(mailboxes.len(), 3);
+        assert_eq!(mailboxes[2].name.as_deref(), Some("Doe, \"Jane\""));
+        assert_eq!(mailboxes[2].email.to_string(), "jane@example.com");
+
+        let err = Recipients::from("not an address")
+            .mailboxes("cc")
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("invalid `cc` mailbox `not an address`"));
+    }
+}
@@ -3,6 +3,16 @@
 //! template files, a `Content` struct to hold email content, and a `Template`
 //! struct to manage template rendering.
 //!
+//! Templates can be localized with locale-suffixed files: for the `de-DE`
+//! locale, `html.de-DE.t` is used when present, then `html.de.t`, then
+//! `html.t`. The locale is available to templates as `lang`, to be passed to
+//! functions registered with [`register_function`], such as the Fluent `t()`
+//! function used by the views.
+//!
+//! Layouts and partials shared by every mailer are registered once with
+//! [`register_templates`], and referenced by their path from any mailer
+//! template, e.g. `{% extends "layout.t" %}`.
+//!
 //! # Example
 //!
 //! ```rust, ignore
@@ -11,29 +21,171 @@
 //!
 //! static welcome: Dir<'_> = include_dir!("src/mailers/auth/welcome");
 //! let args = serde_json::json!({"name": "framework"});
-//! let content = Template::new("contnt").render(&args);
+//! let content = Template::new(&welcome).with_locale(Some("de-DE")).render(&args);
 //! ```
 
+use std::{
+    collections::HashMap,
+    sync::{Arc, OnceLock, RwLock},
+};
+
 use include_dir::Dir;
+use tera::{Context, Function, Tera};
+
+use crate::{errors::Error, Result};
+
+/// The name of the subject template file.
+const SUBJECT: &str = "subject";
+/// The name of the HTML template file.
+const HTML: &str = "html";
+/// The name of the plain text template file.
+const TEXT: &str = "text";
+/// The extension of the template files.
+const EXTENSION: &str = "t";
+
+type SharedFunction = Arc<dyn tera::Function>;
+
+static FUNCTIONS: OnceLock<RwLock<Vec<(String, SharedFunction)>>> = OnceLock::new();
+
+fn functions() -> &'static RwLock<Vec<(String, SharedFunction)>> {
+    FUNCTIONS.get_or_init(|| RwLock::new(Vec::new()))
+}
+
+/// Registers a Tera function available to every mailer template, such as
+/// the Fluent `t()` function registered on the view engine:
+///
+/// ```rust, ignore
+/// loco_rs::mailer::template::register_function("t", FluentLoader::new(arc));
+/// ```
+///
+/// A function registered again under the same name replaces the previous
+/// one.
+///
+/// # Panics
+///
+/// When the functions lock is poisoned.
+pub fn register_function<F: tera::Function + 'static>(name: &str, function: F) {
+    let mut functions = functions().write().unwrap();
+    functions.retain(|(registered, _)| registered != name);
+    functions.push((name.to_string(), Arc::new(function)));
+}
+
+static TEMPLATES: OnceLock<RwLock<Vec<(String, String)>>> = OnceLock::new();
+
+fn templates() -> &'static RwLock<Vec<(String, String)>> {
+    TEMPLATES.get_or_init(|| RwLock::new(Vec::new()))
+}
+
+/// Registers the files of a directory, recursively, as layouts and partials
+/// available to every mailer template under their path in the directory:
+///
+/// ```rust, ignore
+/// static shared: Dir<'_> = include_dir!("src/mailers/shared");
+/// loco_rs::mailer::template::register_templates(&shared);
+/// ```
+///
+/// ```text
+/// {% extends "layout.t" %}
+/// {% block content %}{% include "partials/footer.t" %}{% endblock content %}
+/// ```
+///
+/// A file registered again under the same path replaces the previous one.
+///
+/// # Panics
+///
+/// When the templates lock is poisoned.
+pub fn register_templates(dir: &Dir<'_>) {
+    let mut files = Vec::new();
+    collect_files(dir, &mut files);
+
+    let mut templates = templates().write().unwrap();
+    templates.retain(|(name, _)| !files.iter().any(|(file, _)| file == name));
+    templates.extend(files);
+}
+
+fn collect_files(dir: &Dir<'_>, files: &mut Vec<(String, String)>) {
+    for file in dir.files() {
+        files.push((
+            file.path().to_string_lossy().replace('\\', "/"),
+            String::from_utf8_lossy(file.contents()).to_string(),
+        ));
+    }
+    for dir in dir.dirs() {
+        collect_files(dir, files);
+    }
+}
+
+/// Inlines the `<style>` rules of the HTML into `style` attributes, for
+/// email clients ignoring style sheets.
+///
+/// # Errors
+///
+/// When the HTML or CSS cannot be processed.
+#[cfg(feature = "mailer_css_inline")]
+pub fn inline_css(html: &str) -> Result<String> {
+    css_inline::inline(html).map_err(|err| Error::Message(format!("cannot inline css: {err}")))
+}
+
+/// Inlines the `<style>` rules of the HTML into `style` attributes, for
+/// email clients ignoring style sheets.
+///
+/// # Errors
+///
+/// Always, as the `mailer_css_inline` feature is not enabled.
+#[cfg(not(feature = "mailer_css_inline"))]
+pub fn inline_css(_html: &str) -> Result<String> {
+    Err(Error::Message(
+        "css inlining requires the `mailer_css_inline` feature".to_string(),
+    ))
+}
+
+/// Returns the candidate file names of a template part for a locale, most
+/// specific first.
+fn candidates(name: &str, locale: Option<&str>) -> Vec<String> {
+    let mut candidates = Vec::with_capacity(3);
+    if let Some(locale) = locale {
+        candidates.push(format!("{name}.{locale}.{EXTENSION}"));
+        if let Some((language, _)) = locale.split_once('-') {
+            candidates.push(format!("{name}.{language}.{EXTENSION}"));
+        }
+    }
+    candidates.push(format!("{name}.{EXTENSION}"));
+    candidates
+}
+
+/// Reads the embedded file of a template part from the provided directory,
+/// the locale-suffixed one first, and returns its content as a string.
+fn embedded_file(dir: &Dir<'_>, name: &str, locale: Option<&str>) -> Result<String> {
+    let file = candidates(name, locale)
+        .iter()
+        .find_map(|candidate| dir.get_file(candidate))
+        .ok_or_else(|| {
+            Error::Message(format!("no mailer template file found {name}.{EXTENSION}"))
+        })?;
+    Ok(String::from_utf8_lossy(file.contents()).to_string())
+}
 
-use crate::{errors::Error, tera, Result};
-
-/// The filename for the subject template file.
-const SUBJECT: &str = "subject.t";
-/// The filename for the HTML template file.
-const HTML: &str = "html.t";
-/// The filename for the plain text template file.
-const TEXT: &str = "text.t";
-
-/// Reads an embedded file from the provided directory and returns its content
-/// as a string.
-fn embedded_file(dir: &Dir<'_>, name: &str) -> Result<String> {
-    Ok(String::from_utf8_lossy(
-        dir.get_file(name)
-            .ok_or_else(|| Error::Message(format!("no mailer template file found {name}")))?
-            .contents(),
-    )
-    .to_string())
+/// Renders a template with the registered functions.
+fn render_string(template: &str, context: &Context) -> Result<String> {
+    let mut tera = Tera::default();
+    tera.add_raw_templates(
+        templates()
+            .read()
+            .map_err(|err| Error::Message(err.to_string()))?
+            .iter()
+            .map(|(name, content)| (name.as_str(), content.as_str())),
+    )?;
+    for (name, function) in functions()
+        .read()
+        .map_err(|err| Error::Message(err.to_string()))?
+        .iter()
+    {
+        let function = function.clone();
+        tera.register_function(name, move |args: &HashMap<String, tera::Value>| {
+            function.call(args)
+        });
+    }
+    Ok(tera.render_str(template, context)?)
 }
 
 /// A structure representing the content of an email, including subject, text,
@@ -50,25 +202,42 @@ pub struct Content {
 pub struct Template<'a> {
     /// The directory containing the embedded template files.
     dir: &'a Dir<'a>,
+    /// The locale of the template files, the default files when `None`.
+    locale: Option<String>,
 }
 
 impl<'a> Template<'a> {
     /// Creates a new `Template` instance with the provided directory.
     pub const fn new(dir: &'a Dir<'_>) -> Self {
-        Self { dir }
+        Self { dir, locale: None }
+    }
+
+    /// Sets the locale used to pick the template files.
+    #[must_use]
+    pub fn with_locale(mut self, locale: Option<&str>) -> Self {
+        self.locale = locale.map(ToString::to_string);
+        self
     }
 
     /// Renders the email content based on the provided locals using the
     /// embedded templates.
     pub fn render(&self, locals: &serde_json::Value) -> Result<Content> {
-        let subject_t = embedded_file(self.dir, SUBJECT)?;
-        let text_t = embedded_file(self.dir, TEXT)?;
-        let html_t = embedded_file(self.dir, HTML)?;
+        let locale = self.locale.as_deref();
+        let subject_t = embedded_file(self.dir, SUBJECT, locale)?;
+        let text_t = embedded_file(self.dir, TEXT, locale)?;
+        let html_t = embedded_file(self.dir, HTML, locale)?;
+
+        let mut context = Context::from_serialize(locals)?;
+        if let Some(locale) = locale {
+            if !context.contains_key("lang") {
+                context.insert("lang", locale);
+            }
+        }
 
         // TODO(consider): check+consider offloading to tokio async this work
-        let text = tera::render_string(&text_t, locals)?;
-        let html = tera::render_string(&html_t, locals)?;
-        let subject = tera::render_string(&subject_t, locals)?;
+        let text = render_string(&text_t, &context)?;
+        let html = render_string(&html_t, &context)?;
+        let subject = render_string(&subject_t, &context)?;
         Ok(Content {
             subject,
             text,
@@ -95,4 +264,81 @@ mod tests {
             Template::new(&include_dir!("tests/fixtures/email_template/test")).render(&args)
         );
     }
+
+    #[test]
+    fn can_pick_localized_files() {
+        assert_eq!(
+            candidates("html", Some("de-DE")),
+            vec!["html.de-DE.t", "html.de.t", "html.t"]
+        );
+        assert_eq!(candidates("html", Some("fr")), vec!["html.fr.t", "html.t"]);
+        assert_eq!(candidates("html", None), vec!["html.t"]);
+
+        let dir = include_dir!("tests/fixtures/email_template/localized");
+        let args = serde_json::json!({"name": "Jane"});
+
+        let content = Template::new(&dir).render(&args).unwrap();
+        assert_eq!(content.subject, "Welcome Jane\n");
+        assert_eq!(content.text, "Hello Jane\n");
+
+        let content = Template::new(&dir)
+            .with_locale(Some("de-DE"))
+            .render(&args)
+            .unwrap();
+        assert_eq!(content.subject, "Willkommen Jane\n");
+        assert_eq!(content.text, "Hallo Jane (de-DE)\n");
+        assert!(content.html.contains("Jane"));
+
+        let content = Template::new(&dir)
+            .with_locale(Some("fr-FR"))
+            .render(&args)
+            .unwrap();
+        assert_eq!(content.subject, "Welcome Jane\n");
+    }
+
+    #[test]
+    fn can_use_registered_functions() {
+        register_function(
+            "shout",
+            |args: &HashMap<String, tera::Value>| -> tera::Result<tera::Value> {
+                let text = args
+                    .get("text")
+                    .and_then(tera::Value::as_str)
+                    .unwrap_or_default();
+                Ok(tera::Value::String(text.to_uppercase()))
+            },
+        );
+
+        let context = Context::from_serialize(serde_json::json!({"name": "jane"})).unwrap();
+        assert_eq!(
+            render_string("{{ shout(text=name) }}", &context).unwrap(),
+            "JANE"
+        );
+    }
+
+    #[test]
+    fn can_use_shared_templates() {
+        register_templates(&include_dir!("tests/fixtures/email_template/shared"));
+
+        let context = Context::from_serialize(serde_json::json!({"name": "Jane"})).unwrap();
+        let html = render_string(
+            r#"{% extends "layout.t" %}{% block content %}Hello {{ name }}{% endblock content %}"#,
+            &context,
+        )
+        .unwrap();
+        assert_eq!(
+            html,
+            "<html><body>Hello Jane<footer>Sent to Jane</footer></body></html>\n"
+        );
+    }
+
+    #[cfg(feature = "mailer_css_inline")]
+    #[test]
+    fn can_inline_css() {
+        let html = inline_css(
+            "<html><head><style>p { color: red; }</style></head><body><p>Hi</p></body></html>",
+        )
+        .unwrap();
+        assert!(html.contains(r#"<p style="color: red;">Hi</p>"#));
+    }
 }
@@ -0,0 +1,341 @@
+//! Mail transports used by the [`super::EmailSender`] to deliver messages.
+//!
+//! Loco ships SMTP, file (`.eml` files in a folder), sendmail and stub
+//! transports, selected from the `mailer` configuration. Other providers,
+//! such as HTTP APIs, implement [`MailTransport`] and are installed in
+//! `Hooks::after_context`:
+//!
+//! ```rust,ignore
+//! async fn after_context(mut ctx: AppContext) -> Result<AppContext> {
+//!     ctx.mailer = Some(EmailSender::new(MyApiTransport::new(&ctx.config)?));
+//!     Ok(ctx)
+//! }
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use lettre::{
+    address::Envelope, AsyncFileTransport, AsyncSendmailTransport, AsyncSmtpTransport,
+    AsyncTransport, Message, Tokio1Executor,
+};
+
+use super::{AttachmentContent, Email, Recipients};
+use crate::{Error, Result};
+
+/// Delivers built messages.
+#[async_trait]
+pub trait MailTransport: Send + Sync + std::fmt::Debug {
+    /// Sends the message. `email` holds the structured content the message
+    /// was built from, for transports posting fields rather than MIME.
+    async fn send(&self, email: &Email, message: &Message) -> Result<()>;
+
+    /// Returns the stub transport, which keeps the sent messages for tests.
+    fn as_stub(&self) -> Option<&StubTransport> {
+        None
+    }
+}
+
+/// Sends messages to an SMTP server.
+#[derive(Debug, Clone)]
+pub struct SmtpTransport(pub AsyncSmtpTransport<Tokio1Executor>);
+
+#[async_trait]
+impl MailTransport for SmtpTransport {
+    async fn send(&self, _email: &Email, message: &Message) -> Result<()> {
+        self.0.send(message.clone()).await?;
+        Ok(())
+    }
+}
+
+/// Writes each message to an `<id>.eml` file in a folder, to inspect mails
+/// in development without a mail server.
+#[derive(Debug, Clone)]
+pub struct FileTransport(AsyncFileTransport<Tokio1Executor>);
+
+impl FileTransport {
+    /// Creates a transport writing to the given folder, created if missing.
+    ///
+    /// # Errors
+    ///
+    /// When the folder cannot be created.
+    pub fn new(path: &Path) -> Result<Self> {
+        std::fs::create_dir_all(path)?;
+        Ok(Self(AsyncFileTransport::new(path)))
+    }
+}
+
+#[async_trait]
+impl MailTransport for FileTransport {
+    async fn send(&self, _email: &Email, message: &Message) -> Result<()> {
+        let id = self
+            .0
+            .send(message.clone())
+            .await
+            .map_err(|e| Error::Message(format!("sending email error: {e}")))?;
+        tracing::debug!(id, "email written to file");
+        Ok(())
+    }
+}
+
+/// Pipes messages to the local `sendmail` command.
+#[derive(Debug, Clone)]
+pub struct SendmailTransport(AsyncSendmailTransport<Tokio1Executor>);
+
+impl SendmailTransport {
+    /// Creates a transport using `sendmail` from the `PATH`, or the given
+    /// command.
+    #[must_use]
+    pub fn new(command: Option<&str>) -> Self {
+        Self(command.map_or_else(
+            AsyncSendmailTransport::new,
+            AsyncSendmailTransport::new_with_command,
+        ))
+    }
+}
+
+#[async_trait]
+impl MailTransport for SendmailTransport {
+    async fn send(&self, _email: &Email, message: &Message) -> Result<()> {
+        self.0
+            .send(message.clone())
+            .await
+            .map_err(|e| Error::Message(format!("sending email error: {e}")))?;
+        Ok(())
+    }
+}
+
+/// An attachment of a [`SentMail`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SentAttachment {
+    pub filename: String,
+    /// The MIME type given when attaching, `None` when it was inferred.
+    pub content_type: Option<String>,
+    /// The content id of an inline part.
+    pub content_id: Option<String>,
+    /// The size of the content, in bytes.
+    pub size: usize,
+}
+
+/// A message kept by the [`StubTransport`], with the fields tests assert on.
+#[derive(Debug, Clone)]
+pub struct SentMail {
+    pub message_id: Option<String>,
+    pub from: Option<String>,
+    /// The addresses of the `To` header, without display names.
+    pub to: Vec<String>,
+    pub cc: Vec<String>,
+    pub bcc: Vec<String>,
+    pub subject: String,
+    pub text: String,
+    pub html: String,
+    pub attachments: Vec<SentAttachment>,
+    pub headers: BTreeMap<String, String>,
+    pub envelope: Envelope,
+    /// The formatted message.
+    pub raw: String,
+}
+
+impl SentMail {
+    /// Builds the sent mail from the email and the message built from it.
+    ///
+    /// # Errors
+    ///
+    /// When a recipient is not a valid mailbox.
+    pub fn new(email: &Email, message: &Message) -> Result<Self> {
+        let addresses = |recipients: &Recipients, header: &str| -> Result<Vec<String>> {
+            Ok(recipients
+                .mailboxes(header)?
+                .into_iter()
+                .map(|mailbox| mailbox.email.to_string())
+                .collect())
+        };
+
+        Ok(Self {
+            message_id: message
+                .headers()
+                .get_raw("Message-ID")
+                .map(ToString::to_string),
+            from: email.from.clone(),
+            to: addresses(&email.to, "to")?,
+            cc: addresses(&email.cc, "cc")?,
+            bcc: addresses(&email.bcc, "bcc")?,
+            subject: email.subject.clone(),
+            text: email.text.clone(),
+            html: email.html.clone(),
+            attachments: email
+                .attachments
+                .iter()
+                .map(|attachment| SentAttachment {
+                    filename: attachment.filename.clone(),
+                    content_type: attachment.content_type.clone(),
+                    content_id: attachment.content_id.clone(),
+                    size: match &attachment.content {
+                        AttachmentContent::Bytes { data } => data.len(),
+                        AttachmentContent::Storage { .. } => 0,
+                    },
+                })
+                .collect(),
+            headers: email.headers.clone(),
+            envelope: message.envelope().clone(),
+            raw: String::from_utf8_lossy(&message.formatted()).to_string(),
+        })
+    }
+
+    /// Returns `true` when the address is in the `To`, `Cc` or `Bcc`
+    /// header. Addresses compare case-insensitively.
+    #[must_use]
+    pub fn is_sent_to(&self, address: &str) -> bool {
+        self.to
+            .iter()
+            .chain(&self.cc)
+            .chain(&self.bcc)
+            .any(|to| to.eq_ignore_ascii_case(address))
+    }
+
+    /// Returns the attachment with the given file name.
+    #[must_use]
+    pub fn attachment(&self, filename: &str) -> Option<&SentAttachment> {
+        self.attachments.iter().find(|a| a.filename == filename)
+    }
+}
+
+/// Keeps the sent messages in memory instead of delivering them.
+#[derive(Debug, Clone, Default)]
+pub struct StubTransport {
+    mails: Arc<Mutex<Vec<SentMail>>>,
+}
+
+impl StubTransport {
+    /// Returns the envelope and formatted content of the sent messages.
+    ///
+    /// # Panics
+    ///
+    /// When the messages lock is poisoned.
+    #[must_use]
+    pub fn messages(&self) -> Vec<(Envelope, String)> {
+        self.mails
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|mail| (mail.envelope.clone(), mail.raw.clone()))
+            .collect()
+    }
+
+    /// Returns the sent messages, oldest first.
+    ///
+    /// # Panics
+    ///
+    /// When the messages lock is poisoned.
+    #[must_use]
+    pub fn mails(&self) -> Vec<SentMail> {
+        self.mails.lock().unwrap().clone()
+    }
+
+    /// Forgets the sent messages.
+    ///
+    /// # Panics
+    ///
+    /// When the messages lock is poisoned.
+    pub fn clear(&self) {
+        self.mails.lock().unwrap().clear();
+    }
+}
+
+#[async_trait]
+impl MailTransport for StubTransport {
+    async fn send(&self, email: &Email, message: &Message) -> Result<()> {
+        let mail = SentMail::new(email, message)?;
+        self.mails
+            .lock()
+            .map_err(|e| Error::Message(format!("sending email error: {e}")))?
+            .push(mail);
+        Ok(())
+    }
+
+    fn as_stub(&self) -> Option<&StubTransport> {
+        Some(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tree_fs::TreeBuilder;
+
+    use super::*;
+
+    fn message() -> Message {
+        Message::builder()
+            .from("test@framework.com".parse().unwrap())
+            .to("user1@framework.com".parse().unwrap())
+            .subject("Email Subject")
+            .body("Welcome".to_string())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn can_write_eml_files() {
+        let tree_fs = TreeBuilder::default().drop(true).create().unwrap();
+        let folder = tree_fs.root.join("mails");
+        let transport = FileTransport::new(&folder).unwrap();
+
+        transport.send(&Email::default(), &message()).await.unwrap();
+
+        let files: Vec<_> = std::fs::read_dir(&folder)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "eml"))
+            .collect();
+        assert_eq!(files.len(), 1);
+        let content = std::fs::read_to_string(&files[0]).unwrap();
+        assert!(content.contains("Subject: Email Subject"));
+    }
+
+    #[tokio::test]
+    async fn can_keep_stub_messages() {
+        let transport = StubTransport::default();
+        let sender: Arc<dyn MailTransport> = Arc::new(transport.clone());
+
+        sender.send(&Email::default(), &message()).await.unwrap();
+
+        assert!(sender.as_stub().is_some());
+        assert_eq!(transport.messages().len(), 1);
+        assert!(transport.messages()[0].1.contains("Welcome"));
+
+        transport.clear();
+        assert!(transport.messages().is_empty());
+    }
+
+    #[tokio::test]
+    async fn can_keep_structured_mails() {
+        let transport = StubTransport::default();
+        let email = Email {
+            from: Some("test@framework.com".to_string()),
+            to: Recipients::from("Jane <User1@framework.com>"),
+            bcc: "user2@framework.com".into(),
+            subject: "Email Subject".to_string(),
+            text: "Welcome".to_string(),
+            attachments: vec![crate::mailer::Attachment::from_bytes(
+                "a.txt",
+                b"abc".to_vec(),
+            )],
+            ..Default::default()
+        };
+
+        transport.send(&email, &message()).await.unwrap();
+
+        let mails = transport.mails();
+        assert_eq!(mails.len(), 1);
+        assert_eq!(mails[0].to, vec!["User1@framework.com".to_string()]);
+        assert!(mails[0].is_sent_to("user1@framework.com"));
+        assert!(mails[0].is_sent_to("user2@framework.com"));
+        assert!(!mails[0].is_sent_to("user3@framework.com"));
+        assert_eq!(mails[0].text, "Welcome");
+        assert_eq!(mails[0].attachment("a.txt").map(|a| a.size), Some(3));
+        assert!(mails[0].raw.contains("Subject: Email Subject"));
+    }
+}
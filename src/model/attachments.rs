@@ -0,0 +1,548 @@
+//! # Attachments
+//!
+//! Links files kept in [`Storage`] to records of any entity. Each attachment
+//! is a row in the `attachments` table (see
+//! [`crate::schema::create_attachments_table`]) pointing at the owning
+//! record by its table name and primary key, under an attachment name such
+//! as `avatar` or `documents`.
+//!
+//! ```rust,ignore
+//! use loco_rs::model::attachments;
+//!
+//! let avatar = attachments::attach(&ctx.db, &ctx.storage, &user, "avatar", "me.png", &content).await?;
+//! let all = attachments::list_named(&ctx.db, &user, "avatar").await?;
+//! attachments::detach(&ctx.db, &ctx.storage, &avatar).await?;
+//! ```
+//!
+//! ## Deleting records
+//!
+//! Model hooks only have access to the database, so deleting a record marks
+//! its attachments for purging; the files are removed from storage by
+//! [`purge_detached`], which the [`PurgeAttachments`] task runs:
+//!
+//! ```rust,ignore
+//! #[async_trait::async_trait]
+//! impl ActiveModelBehavior for ActiveModel {
+//!     async fn after_delete<C>(self, db: &C) -> Result<Self, DbErr>
+//!     where
+//!         C: ConnectionTrait,
+//!     {
+//!         loco_rs::model::attachments::mark_for_purge(db, &self).await?;
+//!         Ok(self)
+//!     }
+//! }
+//! ```
+//!
+//! When storage is at hand, [`purge`] removes the attachments of a record
+//! right away.
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use sea_orm::{
+    entity::prelude::*, sea_query::Expr, ActiveValue, Iterable, PrimaryKeyToColumn, QueryOrder,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{ModelError, ModelResult};
+use crate::{
+    app::AppContext,
+    controller::extractor::upload::UploadedFile,
+    storage::Storage,
+    task::{Task, TaskInfo, Vars},
+};
+
+/// The name of the attachments table.
+pub const TABLE: &str = "attachments";
+
+/// The storage prefix attachments are uploaded under.
+pub const PREFIX: &str = "attachments";
+
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "attachments")]
+pub struct Model {
+    pub created_at: DateTimeWithTimeZone,
+    pub updated_at: DateTimeWithTimeZone,
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub record_type: String,
+    pub record_id: String,
+    pub name: String,
+    #[sea_orm(unique)]
+    pub key: String,
+    pub file_name: String,
+    pub content_type: Option<String>,
+    pub size: i64,
+    pub detached_at: Option<DateTimeWithTimeZone>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl Model {
+    /// Returns the storage path of the attached file.
+    #[must_use]
+    pub fn path(&self) -> PathBuf {
+        PathBuf::from(&self.key)
+    }
+
+    /// Downloads the attached file.
+    ///
+    /// # Errors
+    ///
+    /// When the file could not be downloaded.
+    pub async fn download(&self, storage: &Storage) -> ModelResult<Vec<u8>> {
+        storage
+            .download(&self.path())
+            .await
+            .map_err(ModelError::wrap)
+    }
+}
+
+/// Identifies the record owning attachments: its table name and primary key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub record_type: String,
+    pub record_id: String,
+}
+
+impl Record {
+    #[must_use]
+    pub fn new(record_type: &str, record_id: &str) -> Self {
+        Self {
+            record_type: record_type.to_string(),
+            record_id: record_id.to_string(),
+        }
+    }
+
+    /// Builds the record of an active model, or `None` when its primary key
+    /// is not set.
+    #[must_use]
+    pub fn of_active<A: ActiveModelTrait>(model: &A) -> Option<Self> {
+        let ids = <<A::Entity as EntityTrait>::PrimaryKey as Iterable>::iter()
+            .map(|pk| {
+                model
+                    .get(pk.into_column())
+                    .into_value()
+                    .map(value_to_string)
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(Self {
+            record_type: A::Entity::default().table_name().to_string(),
+            record_id: ids.join(","),
+        })
+    }
+}
+
+impl<M: ModelTrait> From<&M> for Record {
+    fn from(model: &M) -> Self {
+        let ids = <<M::Entity as EntityTrait>::PrimaryKey as Iterable>::iter()
+            .map(|pk| value_to_string(model.get(pk.into_column())))
+            .collect::<Vec<_>>();
+
+        Self {
+            record_type: M::Entity::default().table_name().to_string(),
+            record_id: ids.join(","),
+        }
+    }
+}
+
+fn value_to_string(value: Value) -> String {
+    match value {
+        Value::TinyInt(Some(v)) => v.to_string(),
+        Value::SmallInt(Some(v)) => v.to_string(),
+        Value::Int(Some(v)) => v.to_string(),
+        Value::BigInt(Some(v)) => v.to_string(),
+        Value::TinyUnsigned(Some(v)) => v.to_string(),
+        Value::SmallUnsigned(Some(v)) => v.to_string(),
+        Value::Unsigned(Some(v)) => v.to_string(),
+        Value::BigUnsigned(Some(v)) => v.to_string(),
+        Value::String(Some(v)) => *v,
+        Value::Uuid(Some(v)) => v.to_string(),
+        value => format!("{value:?}"),
+    }
+}
+
+fn find_attached(record: &Record) -> Select<Entity> {
+    Entity::find()
+        .filter(Column::RecordType.eq(record.record_type.as_str()))
+        .filter(Column::RecordId.eq(record.record_id.as_str()))
+        .filter(Column::DetachedAt.is_null())
+        .order_by_asc(Column::Id)
+}
+
+/// Uploads the given content to storage and attaches it to the record under
+/// `name`. The content type is sniffed from the content.
+///
+/// # Errors
+///
+/// When the upload or the insert fails. The uploaded file is removed when
+/// the insert fails.
+pub async fn attach<C, R>(
+    db: &C,
+    storage: &Storage,
+    record: R,
+    name: &str,
+    file_name: &str,
+    content: &Bytes,
+) -> ModelResult<Model>
+where
+    C: ConnectionTrait,
+    R: Into<Record>,
+{
+    let record = record.into();
+    let mut key = PathBuf::from(PREFIX).join(uuid::Uuid::new_v4().to_string());
+    if let Some(extension) = std::path::Path::new(file_name).extension() {
+        key.set_extension(extension);
+    }
+
+    storage
+        .upload(&key, content)
+        .await
+        .map_err(ModelError::wrap)?;
+
+    let res = insert(
+        db,
+        &record,
+        name,
+        &key,
+        file_name,
+        infer::get(content).map(|kind| kind.mime_type().to_string()),
+        content.len() as u64,
+    )
+    .await;
+    if res.is_err() {
+        if let Err(err) = storage.delete(&key).await {
+            tracing::warn!(key = %key.display(), err = %err, "could not remove uploaded attachment");
+        }
+    }
+    res
+}
+
+/// Attaches a file already stored by the
+/// [`Upload`](crate::controller::extractor::upload::Upload) extractor.
+///
+/// # Errors
+///
+/// When the insert fails.
+pub async fn attach_uploaded<C, R>(
+    db: &C,
+    record: R,
+    name: &str,
+    file: &UploadedFile,
+) -> ModelResult<Model>
+where
+    C: ConnectionTrait,
+    R: Into<Record>,
+{
+    insert(
+        db,
+        &record.into(),
+        name,
+        &file.key,
+        &file.file_name,
+        Some(file.content_type.clone()),
+        file.size,
+    )
+    .await
+}
+
+async fn insert<C: ConnectionTrait>(
+    db: &C,
+    record: &Record,
+    name: &str,
+    key: &std::path::Path,
+    file_name: &str,
+    content_type: Option<String>,
+    size: u64,
+) -> ModelResult<Model> {
+    let item = ActiveModel {
+        record_type: ActiveValue::set(record.record_type.clone()),
+        record_id: ActiveValue::set(record.record_id.clone()),
+        name: ActiveValue::set(name.to_string()),
+        key: ActiveValue::set(key.display().to_string()),
+        file_name: ActiveValue::set(file_name.to_string()),
+        content_type: ActiveValue::set(content_type),
+        size: ActiveValue::set(i64::try_from(size).map_err(ModelError::wrap)?),
+        ..Default::default()
+    };
+    Ok(item.insert(db).await?)
+}
+
+/// Lists the attachments of the record.
+///
+/// # Errors
+///
+/// When the query fails.
+pub async fn list<C, R>(db: &C, record: R) -> ModelResult<Vec<Model>>
+where
+    C: ConnectionTrait,
+    R: Into<Record>,
+{
+    Ok(find_attached(&record.into()).all(db).await?)
+}
+
+/// Lists the attachments of the record under `name`.
+///
+/// # Errors
+///
+/// When the query fails.
+pub async fn list_named<C, R>(db: &C, record: R, name: &str) -> ModelResult<Vec<Model>>
+where
+    C: ConnectionTrait,
+    R: Into<Record>,
+{
+    Ok(find_attached(&record.into())
+        .filter(Column::Name.eq(name))
+        .all(db)
+        .await?)
+}
+
+/// Removes the attachment and its file.
+///
+/// # Errors
+///
+/// When the file or the row could not be deleted.
+pub async fn detach<C: ConnectionTrait>(
+    db: &C,
+    storage: &Storage,
+    attachment: &Model,
+) -> ModelResult<()> {
+    storage
+        .delete(&attachment.path())
+        .await
+        .map_err(ModelError::wrap)?;
+    Entity::delete_by_id(attachment.id).exec(db).await?;
+    Ok(())
+}
+
+/// Removes the attachments of the record under `name`, returning how many
+/// were removed.
+///
+/// # Errors
+///
+/// When a file or a row could not be deleted.
+pub async fn detach_named<C, R>(
+    db: &C,
+    storage: &Storage,
+    record: R,
+    name: &str,
+) -> ModelResult<u64>
+where
+    C: ConnectionTrait,
+    R: Into<Record>,
+{
+    let attachments = list_named(db, record, name).await?;
+    for attachment in &attachments {
+        detach(db, storage, attachment).await?;
+    }
+    Ok(attachments.len() as u64)
+}
+
+/// Removes all the attachments of the record, returning how many were
+/// removed.
+///
+/// # Errors
+///
+/// When a file or a row could not be deleted.
+pub async fn purge<C, R>(db: &C, storage: &Storage, record: R) -> ModelResult<u64>
+where
+    C: ConnectionTrait,
+    R: Into<Record>,
+{
+    let attachments = list(db, record).await?;
+    for attachment in &attachments {
+        detach(db, storage, attachment).await?;
+    }
+    Ok(attachments.len() as u64)
+}
+
+/// Marks the attachments of a deleted record for purging, returning how many
+/// were marked. Meant to be called from `ActiveModelBehavior::after_delete`.
+///
+/// # Errors
+///
+/// When the update fails.
+pub async fn mark_for_purge<C, A>(db: &C, model: &A) -> Result<u64, DbErr>
+where
+    C: ConnectionTrait,
+    A: ActiveModelTrait,
+{
+    let Some(record) = Record::of_active(model) else {
+        return Ok(0);
+    };
+
+    let res = Entity::update_many()
+        .col_expr(Column::DetachedAt, Expr::current_timestamp().into())
+        .filter(Column::RecordType.eq(record.record_type.as_str()))
+        .filter(Column::RecordId.eq(record.record_id.as_str()))
+        .filter(Column::DetachedAt.is_null())
+        .exec(db)
+        .await?;
+    Ok(res.rows_affected)
+}
+
+/// Deletes the files and rows of the attachments marked for purging,
+/// returning how many were purged.
+///
+/// # Errors
+///
+/// When the query fails. Files that could not be deleted are kept for the
+/// next run.
+pub async fn purge_detached<C: ConnectionTrait>(db: &C, storage: &Storage) -> ModelResult<u64> {
+    let attachments = Entity::find()
+        .filter(Column::DetachedAt.is_not_null())
+        .all(db)
+        .await?;
+
+    let mut purged = 0;
+    for attachment in &attachments {
+        match detach(db, storage, attachment).await {
+            Ok(()) => purged += 1,
+            Err(err) => {
+                tracing::warn!(key = %attachment.key, err = %err, "could not purge attachment");
+            }
+        }
+    }
+    Ok(purged)
+}
+
+/// A task purging the attachments of deleted records. Register it in
+/// `Hooks::register_tasks` and run it with
+/// `cargo loco task purge_attachments`, or from the scheduler.
+pub struct PurgeAttachments;
+
+#[async_trait]
+impl Task for PurgeAttachments {
+    fn task(&self) -> TaskInfo {
+        TaskInfo {
+            name: "purge_attachments".to_string(),
+            detail: "Deletes the stored files of attachments whose record was deleted".to_string(),
+        }
+    }
+
+    async fn run(&self, app_context: &AppContext, _vars: &Vars) -> crate::Result<()> {
+        let purged = purge_detached(&app_context.db, &app_context.storage).await?;
+        tracing::info!(purged, "purged attachments");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm_migration::SchemaManager;
+
+    use super::*;
+    use crate::{
+        schema::create_attachments_table,
+        storage::drivers,
+        tests_cfg::db::{dummy_connection, test_db},
+    };
+
+    async fn setup() -> (DatabaseConnection, Storage) {
+        let db = dummy_connection().await;
+        create_attachments_table(&SchemaManager::new(&db))
+            .await
+            .unwrap();
+        let storage = Storage::single(drivers::mem::new());
+        (db, storage)
+    }
+
+    async fn exists(storage: &Storage, attachment: &Model) -> bool {
+        let store = storage.as_store("store").unwrap();
+        store.exists(&attachment.path()).await.unwrap()
+    }
+
+    fn record() -> test_db::Model {
+        test_db::Model {
+            id: 7,
+            name: "loco".to_string(),
+            created_at: chrono::Utc::now().naive_utc(),
+            updated_at: chrono::Utc::now().naive_utc(),
+        }
+    }
+
+    #[test]
+    fn can_build_record() {
+        let record = Record::from(&record());
+        assert_eq!(record, Record::new("loco", "7"));
+
+        let active = test_db::ActiveModel {
+            id: ActiveValue::set(7),
+            ..Default::default()
+        };
+        assert_eq!(Record::of_active(&active), Some(Record::new("loco", "7")));
+        assert_eq!(Record::of_active(&test_db::ActiveModel::default()), None);
+    }
+
+    #[tokio::test]
+    async fn can_attach_list_and_detach() {
+        let (db, storage) = setup().await;
+        let record = record();
+
+        let attachment = attach(
+            &db,
+            &storage,
+            &record,
+            "avatar",
+            "me.txt",
+            &Bytes::from("content"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(attachment.record_type, "loco");
+        assert_eq!(attachment.record_id, "7");
+        assert_eq!(attachment.size, 7);
+        assert!(attachment.key.starts_with("attachments/"));
+        assert!(attachment.key.ends_with(".txt"));
+        assert_eq!(attachment.download(&storage).await.unwrap(), b"content");
+
+        attach(&db, &storage, &record, "docs", "1.txt", &Bytes::from("1"))
+            .await
+            .unwrap();
+
+        assert_eq!(list(&db, &record).await.unwrap().len(), 2);
+        assert_eq!(
+            list_named(&db, &record, "avatar").await.unwrap(),
+            vec![attachment.clone()]
+        );
+
+        detach(&db, &storage, &attachment).await.unwrap();
+        assert!(!exists(&storage, &attachment).await);
+        assert_eq!(list(&db, &record).await.unwrap().len(), 1);
+
+        assert_eq!(purge(&db, &storage, &record).await.unwrap(), 1);
+        assert!(list(&db, &record).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn can_purge_deleted_records() {
+        let (db, storage) = setup().await;
+        let record = record();
+
+        let attachment = attach(
+            &db,
+            &storage,
+            &record,
+            "avatar",
+            "me.txt",
+            &Bytes::from("1"),
+        )
+        .await
+        .unwrap();
+
+        let active = test_db::ActiveModel {
+            id: ActiveValue::set(7),
+            ..Default::default()
+        };
+        assert_eq!(mark_for_purge(&db, &active).await.unwrap(), 1);
+        assert!(list(&db, &record).await.unwrap().is_empty());
+        assert!(exists(&storage, &attachment).await);
+
+        assert_eq!(purge_detached(&db, &storage).await.unwrap(), 1);
+        assert!(!exists(&storage, &attachment).await);
+    }
+}
@@ -0,0 +1,128 @@
+//! # Mail Deliveries
+//!
+//! The `mail_deliveries` table (see
+//! [`crate::schema::create_mail_deliveries_table`]) written by
+//! [`crate::mailer::delivery::DatabaseDeliveryLog`], one row per sent or
+//! failed email.
+//!
+//! ```rust,ignore
+//! use loco_rs::model::mail_deliveries;
+//!
+//! let failed = mail_deliveries::list_failed(&ctx.db).await?;
+//! ```
+use sea_orm::{entity::prelude::*, ActiveValue, QueryOrder};
+use serde::{Deserialize, Serialize};
+
+use super::ModelResult;
+use crate::mailer::{
+    delivery::{DeliveryOutcome, DeliveryRecord},
+    Recipients,
+};
+
+/// The name of the mail deliveries table.
+pub const TABLE: &str = "mail_deliveries";
+
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "mail_deliveries")]
+pub struct Model {
+    pub created_at: DateTimeWithTimeZone,
+    pub updated_at: DateTimeWithTimeZone,
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub message_id: Option<String>,
+    pub sender: Option<String>,
+    pub to: String,
+    pub cc: String,
+    pub bcc: String,
+    pub subject: String,
+    pub outcome: String,
+    pub error: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+fn join(recipients: &Recipients) -> String {
+    recipients.iter().cloned().collect::<Vec<_>>().join(", ")
+}
+
+/// Inserts a delivery record.
+///
+/// # Errors
+///
+/// When the insert fails.
+pub async fn create<C: ConnectionTrait>(db: &C, record: &DeliveryRecord) -> ModelResult<Model> {
+    let item = ActiveModel {
+        message_id: ActiveValue::set(record.message_id.clone()),
+        sender: ActiveValue::set(record.from.clone()),
+        to: ActiveValue::set(join(&record.to)),
+        cc: ActiveValue::set(join(&record.cc)),
+        bcc: ActiveValue::set(join(&record.bcc)),
+        subject: ActiveValue::set(record.subject.clone()),
+        outcome: ActiveValue::set(record.outcome.as_str().to_string()),
+        error: ActiveValue::set(record.error.clone()),
+        ..Default::default()
+    };
+    Ok(item.insert(db).await?)
+}
+
+/// Lists the failed deliveries, most recent first.
+///
+/// # Errors
+///
+/// When the query fails.
+pub async fn list_failed<C: ConnectionTrait>(db: &C) -> ModelResult<Vec<Model>> {
+    Ok(Entity::find()
+        .filter(Column::Outcome.eq(DeliveryOutcome::Failed.as_str()))
+        .order_by_desc(Column::Id)
+        .all(db)
+        .await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm_migration::SchemaManager;
+
+    use super::*;
+    use crate::{
+        mailer::Email, schema::create_mail_deliveries_table, tests_cfg::db::dummy_connection,
+    };
+
+    #[tokio::test]
+    async fn can_record_deliveries() {
+        let db = dummy_connection().await;
+        create_mail_deliveries_table(&SchemaManager::new(&db))
+            .await
+            .unwrap();
+
+        let email = Email {
+            from: Some("test@framework.com".to_string()),
+            to: Recipients::from(vec!["user1@framework.com", "user2@framework.com"]),
+            subject: "Email Subject".to_string(),
+            ..Default::default()
+        };
+
+        let sent = create(
+            &db,
+            &DeliveryRecord::new(&email, &Ok(Some("<id@framework.com>".to_string()))),
+        )
+        .await
+        .unwrap();
+        assert_eq!(sent.to, "user1@framework.com, user2@framework.com");
+        assert_eq!(sent.outcome, "sent");
+        assert_eq!(sent.message_id.as_deref(), Some("<id@framework.com>"));
+
+        create(
+            &db,
+            &DeliveryRecord::new(&email, &Err(crate::Error::Message("refused".to_string()))),
+        )
+        .await
+        .unwrap();
+
+        let failed = list_failed(&db).await.unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].error.as_deref(), Some("refused"));
+    }
+}
@@ -628,3 +628,101 @@ pub async fn drop_table(m: &SchemaManager<'_>, table: &str) -> Result<(), DbErr>
     m.drop_table(Table::drop().table(Alias::new(nz_table)).to_owned())
         .await
 }
+
+///
+/// Create the `attachments` table used by [`crate::model::attachments`], with
+/// an index on the owning record.
+///
+/// ```ignore
+/// create_attachments_table(m).await;
+/// ```
+/// # Errors
+/// fails when it fails
+pub async fn create_attachments_table(m: &SchemaManager<'_>) -> Result<(), DbErr> {
+    create_table(
+        m,
+        crate::model::attachments::TABLE,
+        &[
+            ("record_type", ColType::String),
+            ("record_id", ColType::String),
+            ("name", ColType::String),
+            ("key", ColType::StringUniq),
+            ("file_name", ColType::String),
+            ("content_type", ColType::StringNull),
+            ("size", ColType::BigInteger),
+            ("detached_at", ColType::TimestampWithTimeZoneNull),
+        ],
+        &[],
+    )
+    .await?;
+
+    m.create_index(
+        Index::create()
+            .name(format!("idx-{}-record", crate::model::attachments::TABLE))
+            .table(Alias::new(crate::model::attachments::TABLE))
+            .col(Alias::new("record_type"))
+            .col(Alias::new("record_id"))
+            .if_not_exists()
+            .to_owned(),
+    )
+    .await
+}
+
+///
+/// Drop the `attachments` table, if it exists. The table is shared by all the
+/// models, so only drop it from a migration dedicated to it.
+///
+/// ```ignore
+/// drop_attachments_table(m).await;
+/// ```
+/// # Errors
+/// fails when it fails
+pub async fn drop_attachments_table(m: &SchemaManager<'_>) -> Result<(), DbErr> {
+    m.drop_table(
+        Table::drop()
+            .table(Alias::new(crate::model::attachments::TABLE))
+            .if_exists()
+            .to_owned(),
+    )
+    .await
+}
+
+///
+/// Create the `mail_deliveries` table written by
+/// [`crate::mailer::delivery::DatabaseDeliveryLog`].
+///
+/// ```ignore
+/// create_mail_deliveries_table(m).await;
+/// ```
+/// # Errors
+/// fails when it fails
+pub async fn create_mail_deliveries_table(m: &SchemaManager<'_>) -> Result<(), DbErr> {
+    create_table(
+        m,
+        crate::model::mail_deliveries::TABLE,
+        &[
+            ("message_id", ColType::StringNull),
+            ("sender", ColType::StringNull),
+            ("to", ColType::Text),
+            ("cc", ColType::Text),
+            ("bcc", ColType::Text),
+            ("subject", ColType::String),
+            ("outcome", ColType::String),
+            ("error", ColType::TextNull),
+        ],
+        &[],
+    )
+    .await
+}
+
+///
+/// Drop the `mail_deliveries` table.
+///
+/// ```ignore
+/// drop_mail_deliveries_table(m).await;
+/// ```
+/// # Errors
+/// fails when it fails
+pub async fn drop_mail_deliveries_table(m: &SchemaManager<'_>) -> Result<(), DbErr> {
+    drop_table(m, crate::model::mail_deliveries::TABLE).await
+}
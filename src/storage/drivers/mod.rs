@@ -1,7 +1,14 @@
-use std::path::Path;
+use std::{
+    ops::Range,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use async_trait::async_trait;
-use bytes::Bytes;
+use axum::http::Method;
+use bytes::{Bytes, BytesMut};
+use chrono::{DateTime, Utc};
+use futures_util::{stream::BoxStream, StreamExt, TryStreamExt};
 use opendal::Reader;
 
 #[cfg(feature = "storage_aws_s3")]
@@ -15,7 +22,14 @@ pub mod mem;
 pub mod null;
 pub mod opendal_adapter;
 
-use super::StorageResult;
+use super::{StorageError, StorageResult};
+
+/// A stream of content chunks, used to upload and download content without
+/// buffering it in memory.
+pub type BytesStream = BoxStream<'static, StorageResult<Bytes>>;
+
+/// A stream of the objects found under a prefix, see [`StoreDriver::list`].
+pub type ListStream = BoxStream<'static, StorageResult<ObjectMetadata>>;
 
 #[derive(Debug)]
 pub struct UploadResponse {
@@ -23,10 +37,18 @@ pub struct UploadResponse {
     pub version: Option<String>,
 }
 
-/// TODO: Add more methods to `GetResponse` to read the content in different
-/// ways
-///
-/// For example, we can read a specific range of bytes from the stream.
+/// The metadata of a stored object.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ObjectMetadata {
+    pub path: PathBuf,
+    pub size: u64,
+    pub content_type: Option<String>,
+    pub e_tag: Option<String>,
+    pub last_modified: Option<DateTime<Utc>>,
+}
+
+/// The content returned from a store, which can be read whole, by range or
+/// as a stream.
 pub struct GetResponse {
     stream: Reader,
 }
@@ -44,6 +66,31 @@ impl GetResponse {
     pub async fn bytes(&self) -> StorageResult<Bytes> {
         Ok(self.stream.read(..).await?.to_bytes())
     }
+
+    /// Read the given range of bytes from the stream and return as `Bytes`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `StorageError` with the reason for the failure.
+    pub async fn range(&self, range: Range<u64>) -> StorageResult<Bytes> {
+        Ok(self.stream.read(range).await?.to_bytes())
+    }
+
+    /// Converts the response into a [`BytesStream`], optionally limited to
+    /// the given range of bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `StorageError` with the reason for the failure.
+    pub async fn into_stream(self, range: Option<Range<u64>>) -> StorageResult<BytesStream> {
+        let stream = match range {
+            Some(range) => self.stream.into_bytes_stream(range).await?,
+            None => self.stream.into_bytes_stream(..).await?,
+        };
+        Ok(stream
+            .map_err(|err| StorageError::Any(Box::new(err)))
+            .boxed())
+    }
 }
 
 #[async_trait]
@@ -56,6 +103,28 @@ pub trait StoreDriver: Sync + Send {
     /// Returns a `StorageResult` with the result of the upload operation.
     async fn upload(&self, path: &Path, content: &Bytes) -> StorageResult<UploadResponse>;
 
+    /// Uploads the content of the given stream to the specified path in the
+    /// object store.
+    ///
+    /// The default implementation collects the stream in memory and calls
+    /// [`StoreDriver::upload`]; drivers that can write chunks should
+    /// override it.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `StorageResult` with the result of the upload operation.
+    async fn upload_stream(
+        &self,
+        path: &Path,
+        mut stream: BytesStream,
+    ) -> StorageResult<UploadResponse> {
+        let mut content = BytesMut::new();
+        while let Some(chunk) = stream.next().await {
+            content.extend_from_slice(&chunk?);
+        }
+        self.upload(path, &content.freeze()).await
+    }
+
     /// Retrieves the content from the specified path in the object store.
     ///
     /// # Errors
@@ -63,6 +132,20 @@ pub trait StoreDriver: Sync + Send {
     /// Returns a `StorageResult` with the result of the retrieval operation.
     async fn get(&self, path: &Path) -> StorageResult<GetResponse>;
 
+    /// Retrieves the content from the specified path in the object store as a
+    /// stream, optionally limited to the given range of bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `StorageResult` with the result of the retrieval operation.
+    async fn download_stream(
+        &self,
+        path: &Path,
+        range: Option<Range<u64>>,
+    ) -> StorageResult<BytesStream> {
+        self.get(path).await?.into_stream(range).await
+    }
+
     /// Deletes the content at the specified path in the object store.
     ///
     /// # Errors
@@ -94,4 +177,44 @@ pub trait StoreDriver: Sync + Send {
     /// Returns a `StorageResult` with a boolean indicating the existence of the
     /// content.
     async fn exists(&self, path: &Path) -> StorageResult<bool>;
+
+    /// Lists the objects stored under the specified prefix, recursively.
+    ///
+    /// Objects are fetched page by page from the object store while the
+    /// stream is consumed. Not supported by default.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `StorageResult` with the stream of the objects metadata.
+    async fn list(&self, _prefix: &Path) -> StorageResult<ListStream> {
+        Err(StorageError::Any("Operation not supported".into()))
+    }
+
+    /// Returns the metadata of the content at the specified path in the
+    /// object store. Not supported by default.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `StorageResult` with the metadata of the content.
+    async fn stat(&self, _path: &Path) -> StorageResult<ObjectMetadata> {
+        Err(StorageError::Any("Operation not supported".into()))
+    }
+
+    /// Returns a presigned URL to access the content at the specified path
+    /// with the given HTTP method, valid until `expires_in` elapses.
+    ///
+    /// Returns `None` when the object store has no native presigning, the
+    /// default.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `StorageResult` with the presigned URL, if any.
+    async fn presign(
+        &self,
+        _path: &Path,
+        _expires_in: Duration,
+        _method: &Method,
+    ) -> StorageResult<Option<String>> {
+        Ok(None)
+    }
 }
@@ -4,12 +4,16 @@
 //! Loco framework is initialized. The primary purpose of this driver is to
 //! simplify the user workflow by avoiding the need for feature flags or
 //! optional storage driver configurations.
-use std::path::Path;
+use std::{path::Path, time::Duration};
 
 use async_trait::async_trait;
+use axum::http::Method;
 use bytes::Bytes;
 
-use super::{GetResponse, StorageResult, StoreDriver, UploadResponse};
+use super::{
+    BytesStream, GetResponse, ListStream, ObjectMetadata, StorageResult, StoreDriver,
+    UploadResponse,
+};
 use crate::storage::StorageError;
 
 pub struct NullStorage {}
@@ -34,6 +38,22 @@ impl StoreDriver for NullStorage {
         ))
     }
 
+    /// Uploads the content of the given stream to the specified path in the
+    /// object store.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `StorageResult` with the result of the upload operation.
+    async fn upload_stream(
+        &self,
+        _path: &Path,
+        _stream: BytesStream,
+    ) -> StorageResult<UploadResponse> {
+        Err(StorageError::Any(
+            "Operation not supported by null storage".into(),
+        ))
+    }
+
     /// Retrieves the content from the specified path in the object store.
     ///
     /// # Errors
@@ -92,4 +112,42 @@ impl StoreDriver for NullStorage {
             "Operation not supported by null storage".into(),
         ))
     }
+
+    /// Lists the objects stored under the specified prefix, recursively.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `StorageResult` with the stream of the objects metadata.
+    async fn list(&self, _prefix: &Path) -> StorageResult<ListStream> {
+        Err(StorageError::Any(
+            "Operation not supported by null storage".into(),
+        ))
+    }
+
+    /// Returns the metadata of the content at the specified path in the
+    /// object store.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `StorageResult` with the metadata of the content.
+    async fn stat(&self, _path: &Path) -> StorageResult<ObjectMetadata> {
+        Err(StorageError::Any(
+            "Operation not supported by null storage".into(),
+        ))
+    }
+
+    /// The null storage has no native presigning, always returns `None` so
+    /// the storage falls back to its URL signer.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `StorageResult` with the presigned URL, if any.
+    async fn presign(
+        &self,
+        _path: &Path,
+        _expires_in: Duration,
+        _method: &Method,
+    ) -> StorageResult<Option<String>> {
+        Ok(None)
+    }
 }
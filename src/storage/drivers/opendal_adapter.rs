@@ -1,11 +1,15 @@
-use std::path::Path;
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use async_trait::async_trait;
+use axum::http::Method;
 use bytes::Bytes;
-use futures_util::SinkExt;
-use opendal::{layers::RetryLayer, Operator};
+use futures_util::{SinkExt, StreamExt, TryStreamExt};
+use opendal::{layers::RetryLayer, Metadata, Metakey, Operator};
 
-use super::{GetResponse, StoreDriver, UploadResponse};
+use super::{BytesStream, GetResponse, ListStream, ObjectMetadata, StoreDriver, UploadResponse};
 use crate::storage::{StorageError, StorageResult};
 
 pub struct OpendalAdapter {
@@ -23,6 +27,16 @@ impl OpendalAdapter {
     }
 }
 
+fn to_object_metadata(path: &str, meta: &Metadata) -> ObjectMetadata {
+    ObjectMetadata {
+        path: PathBuf::from(path),
+        size: meta.content_length(),
+        content_type: meta.content_type().map(ToString::to_string),
+        e_tag: meta.etag().map(ToString::to_string),
+        last_modified: meta.last_modified(),
+    }
+}
+
 #[async_trait]
 impl StoreDriver for OpendalAdapter {
     /// Uploads the content represented by `Bytes` to the specified path in the
@@ -42,6 +56,45 @@ impl StoreDriver for OpendalAdapter {
         })
     }
 
+    /// Uploads the content of the given stream to the specified path in the
+    /// object store, writing chunk by chunk.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `StorageResult` with the result of the upload operation.
+    async fn upload_stream(
+        &self,
+        path: &Path,
+        mut stream: BytesStream,
+    ) -> StorageResult<UploadResponse> {
+        let mut writer = self
+            .opendal_impl
+            .writer(&path.display().to_string())
+            .await?;
+        while let Some(chunk) = stream.next().await {
+            match chunk {
+                Ok(chunk) => writer.write(chunk).await?,
+                Err(err) => {
+                    // don't leave a partial object behind, but report the
+                    // stream error rather than a failure to clean up
+                    if let Err(abort_err) = writer.abort().await {
+                        tracing::warn!(
+                            err = abort_err.to_string(),
+                            path = %path.display(),
+                            "could not abort partial upload"
+                        );
+                    }
+                    return Err(err);
+                }
+            }
+        }
+        writer.close().await?;
+        Ok(UploadResponse {
+            e_tag: None,
+            version: None,
+        })
+    }
+
     /// Retrieves the content from the specified path in the object store.
     ///
     /// # Errors
@@ -141,4 +194,137 @@ impl StoreDriver for OpendalAdapter {
         let path = path.display().to_string();
         Ok(self.opendal_impl.exists(&path).await.unwrap_or(false))
     }
+
+    /// Lists the objects stored under the specified prefix, recursively.
+    ///
+    /// Directories are not returned, only the objects they contain.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `StorageResult` with the stream of the objects metadata.
+    async fn list(&self, prefix: &Path) -> StorageResult<ListStream> {
+        let prefix = prefix.display().to_string();
+        let prefix = if prefix.is_empty() {
+            "/".to_string()
+        } else {
+            prefix
+        };
+        let lister = self
+            .opendal_impl
+            .lister_with(&prefix)
+            .recursive(true)
+            .metakey(
+                Metakey::Mode
+                    | Metakey::ContentLength
+                    | Metakey::ContentType
+                    | Metakey::Etag
+                    | Metakey::LastModified,
+            )
+            .await?;
+
+        Ok(lister
+            .try_filter(|entry| futures_util::future::ready(!entry.metadata().is_dir()))
+            .map_ok(|entry| to_object_metadata(entry.path(), entry.metadata()))
+            .map_err(StorageError::from)
+            .boxed())
+    }
+
+    /// Returns the metadata of the content at the specified path in the
+    /// object store.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `StorageResult` with the metadata of the content.
+    async fn stat(&self, path: &Path) -> StorageResult<ObjectMetadata> {
+        let path = path.display().to_string();
+        let meta = self.opendal_impl.stat(&path).await?;
+        Ok(to_object_metadata(&path, &meta))
+    }
+
+    /// Returns a presigned URL when the underlying service supports
+    /// presigning (S3, Azure blob and GCS), `None` otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `StorageResult` with the presigned URL, if any.
+    async fn presign(
+        &self,
+        path: &Path,
+        expires_in: Duration,
+        method: &Method,
+    ) -> StorageResult<Option<String>> {
+        let capability = self.opendal_impl.info().full_capability();
+        let path = path.display().to_string();
+        let req = match *method {
+            Method::GET if capability.presign_read => {
+                self.opendal_impl.presign_read(&path, expires_in).await?
+            }
+            Method::PUT if capability.presign_write => {
+                self.opendal_impl.presign_write(&path, expires_in).await?
+            }
+            Method::HEAD if capability.presign_stat => {
+                self.opendal_impl.presign_stat(&path, expires_in).await?
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(req.uri().to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use bytes::Bytes;
+    use futures_util::TryStreamExt;
+
+    use crate::storage::drivers::mem;
+
+    #[tokio::test]
+    async fn can_list_prefix() {
+        let store = mem::new();
+        for path in ["users/1.txt", "users/data/2.txt", "posts/3.txt"] {
+            assert!(store
+                .upload(PathBuf::from(path).as_path(), &Bytes::from("content"))
+                .await
+                .is_ok());
+        }
+
+        let mut paths: Vec<PathBuf> = store
+            .list(PathBuf::from("users/").as_path())
+            .await
+            .unwrap()
+            .map_ok(|meta| meta.path)
+            .try_collect()
+            .await
+            .unwrap();
+        paths.sort();
+
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("users/1.txt"),
+                PathBuf::from("users/data/2.txt")
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn can_stat() {
+        let store = mem::new();
+        let path = PathBuf::from("users").join("1.txt");
+        assert!(store
+            .upload(path.as_path(), &Bytes::from("content"))
+            .await
+            .is_ok());
+
+        let meta = store.stat(path.as_path()).await.unwrap();
+        assert_eq!(meta.path, path);
+        assert_eq!(meta.size, 7);
+
+        assert!(store
+            .stat(PathBuf::from("not-found").as_path())
+            .await
+            .is_err());
+    }
 }
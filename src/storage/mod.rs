@@ -12,15 +12,27 @@
 //! The selected strategy can be dynamically changed at runtime.
 mod contents;
 pub mod drivers;
+pub mod signed_url;
 pub mod strategies;
+pub mod sync;
+#[cfg(feature = "storage_image")]
+pub mod variants;
 use std::{
     collections::BTreeMap,
+    ops::Range,
     path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
 };
 
+use axum::http::Method;
 use bytes::Bytes;
+use futures_util::TryStreamExt;
+use tokio::io::AsyncRead;
+use tokio_util::io::ReaderStream;
 
-use self::drivers::StoreDriver;
+use self::drivers::{BytesStream, ListStream, ObjectMetadata, StoreDriver};
+use crate::config::{ImageVariantConfig, StorageConfig, StorageStrategyConfig, StoreConfig};
 
 #[derive(thiserror::Error, Debug)]
 #[allow(clippy::module_name_repetitions)]
@@ -44,8 +56,10 @@ pub enum StorageError {
 pub type StorageResult<T> = std::result::Result<T, StorageError>;
 
 pub struct Storage {
-    pub stores: BTreeMap<String, Box<dyn StoreDriver>>,
+    pub stores: BTreeMap<String, Arc<dyn StoreDriver>>,
     pub strategy: Box<dyn strategies::StorageStrategy>,
+    url_signer: Option<signed_url::UrlSigner>,
+    variants: BTreeMap<String, ImageVariantConfig>,
 }
 
 impl Storage {
@@ -63,7 +77,9 @@ impl Storage {
         let default_key = "store";
         Self {
             strategy: Box::new(strategies::single::SingleStrategy::new(default_key)),
-            stores: BTreeMap::from([(default_key.to_string(), store)]),
+            stores: BTreeMap::from([(default_key.to_string(), Arc::from(store))]),
+            url_signer: None,
+            variants: BTreeMap::new(),
         }
     }
 
@@ -73,7 +89,102 @@ impl Storage {
         stores: BTreeMap<String, Box<dyn StoreDriver>>,
         strategy: Box<dyn strategies::StorageStrategy>,
     ) -> Self {
-        Self { stores, strategy }
+        Self {
+            stores: stores
+                .into_iter()
+                .map(|(name, store)| (name, Arc::from(store)))
+                .collect(),
+            strategy,
+            url_signer: None,
+            variants: BTreeMap::new(),
+        }
+    }
+
+    /// Sets the signer used by [`Storage::presigned_url`] for stores without
+    /// native presigning, such as `local` and `mem`.
+    ///
+    /// # Examples
+    ///```
+    /// use loco_rs::storage::{self, signed_url::UrlSigner};
+    ///
+    /// let storage = storage::Storage::single(storage::drivers::mem::new())
+    ///     .with_url_signer(UrlSigner::new("secret", "http://localhost:5150/_storage"));
+    /// ```
+    #[must_use]
+    pub fn with_url_signer(mut self, signer: signed_url::UrlSigner) -> Self {
+        self.url_signer = Some(signer);
+        self
+    }
+
+    /// Returns the signer used for stores without native presigning, if
+    /// configured.
+    #[must_use]
+    pub fn url_signer(&self) -> Option<&signed_url::UrlSigner> {
+        self.url_signer.as_ref()
+    }
+
+    /// Sets the image variant definitions, see [`ImageVariantConfig`].
+    #[must_use]
+    pub fn with_variants(mut self, variants: BTreeMap<String, ImageVariantConfig>) -> Self {
+        self.variants = variants;
+        self
+    }
+
+    /// Returns the image variant definitions, by name.
+    #[must_use]
+    pub fn variants(&self) -> &BTreeMap<String, ImageVariantConfig> {
+        &self.variants
+    }
+
+    /// Creates a new storage instance from the `storage:` configuration
+    /// section.
+    ///
+    /// When no strategy is configured, a single store is required and used
+    /// with the single strategy.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a store cannot be built (for example when the
+    /// feature of its driver is not enabled), or if the strategy refers to a
+    /// store that is not configured.
+    pub fn from_config(config: &StorageConfig) -> StorageResult<Self> {
+        let mut stores = BTreeMap::new();
+        for (name, store) in &config.stores {
+            stores.insert(name.clone(), create_store(store)?);
+        }
+
+        let strategy: Box<dyn strategies::StorageStrategy> = match &config.strategy {
+            None => {
+                let mut names = config.stores.keys();
+                match (names.next(), names.next()) {
+                    (Some(name), None) => Box::new(strategies::single::SingleStrategy::new(name)),
+                    _ => {
+                        return Err(StorageError::Any(
+                            "a storage strategy is required when configuring zero or more than \
+                             one store"
+                                .into(),
+                        ))
+                    }
+                }
+            }
+            Some(strategy) => {
+                if let Some(name) = strategy_stores(strategy)
+                    .into_iter()
+                    .find(|name| !stores.contains_key(*name))
+                {
+                    return Err(StorageError::StoreNotFound(name.clone()));
+                }
+                create_strategy(strategy)?
+            }
+        };
+
+        let storage = Self::new(stores, strategy).with_variants(config.variants.clone());
+        Ok(match &config.signed_urls {
+            Some(cfg) => {
+                storage.with_url_signer(signed_url::UrlSigner::new(&cfg.secret, &cfg.base_url))
+            }
+            None => storage,
+        })
     }
 
     /// Uploads content to the storage at the specified path.
@@ -122,6 +233,132 @@ impl Storage {
         strategy.upload(self, path, content).await
     }
 
+    /// Uploads the content of the given stream to the storage at the
+    /// specified path, without buffering it in memory when the strategy and
+    /// the stores support it.
+    ///
+    /// This method uses the selected strategy for the upload operation.
+    ///
+    /// # Examples
+    ///```
+    /// use loco_rs::storage;
+    /// use std::path::Path;
+    /// use bytes::Bytes;
+    /// use futures_util::{stream, StreamExt};
+    /// pub async fn upload() {
+    ///     let storage = storage::Storage::single(storage::drivers::mem::new());
+    ///     let path = Path::new("example.txt");
+    ///     let chunks = vec![Ok(Bytes::from("Lo")), Ok(Bytes::from("co!"))];
+    ///     let result = storage.upload_stream(path, stream::iter(chunks).boxed()).await;
+    ///     assert!(result.is_ok());
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the upload operation fails, if the
+    /// stream yields an error or if there is an issue with the strategy
+    /// configuration.
+    pub async fn upload_stream(&self, path: &Path, stream: BytesStream) -> StorageResult<()> {
+        self.upload_stream_with_strategy(path, stream, &*self.strategy)
+            .await
+    }
+
+    /// Uploads the content of the given stream to the storage at the
+    /// specified path using a specific strategy.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the upload operation fails, if the
+    /// stream yields an error or if there is an issue with the strategy
+    /// configuration.
+    pub async fn upload_stream_with_strategy(
+        &self,
+        path: &Path,
+        stream: BytesStream,
+        strategy: &dyn strategies::StorageStrategy,
+    ) -> StorageResult<()> {
+        strategy.upload_stream(self, path, stream).await
+    }
+
+    /// Uploads the content of the given reader (for example a
+    /// [`tokio::fs::File`]) to the storage at the specified path.
+    ///
+    /// # Examples
+    ///```
+    /// use loco_rs::storage;
+    /// use std::path::Path;
+    /// pub async fn upload() {
+    ///     let storage = storage::Storage::single(storage::drivers::mem::new());
+    ///     let file = tokio::fs::File::open("Cargo.toml").await.unwrap();
+    ///     let result = storage.upload_reader(Path::new("Cargo.toml"), file).await;
+    ///     assert!(result.is_ok());
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the upload operation fails, if reading
+    /// fails or if there is an issue with the strategy configuration.
+    pub async fn upload_reader<R>(&self, path: &Path, reader: R) -> StorageResult<()>
+    where
+        R: AsyncRead + Send + 'static,
+    {
+        let stream = ReaderStream::new(reader).map_err(|err| StorageError::Any(Box::new(err)));
+        self.upload_stream(path, Box::pin(stream)).await
+    }
+
+    /// Downloads content from the storage at the specified path as a stream,
+    /// optionally limited to the given range of bytes.
+    ///
+    /// This method uses the selected strategy for the download operation.
+    ///
+    /// # Examples
+    ///```
+    /// use loco_rs::storage;
+    /// use std::path::Path;
+    /// use bytes::Bytes;
+    /// use futures_util::TryStreamExt;
+    /// pub async fn download() {
+    ///     let storage = storage::Storage::single(storage::drivers::mem::new());
+    ///     let path = Path::new("example.txt");
+    ///     storage.upload(path, &Bytes::from("Loco!")).await;
+    ///
+    ///     let stream = storage.download_stream(path, Some(0..4)).await.unwrap();
+    ///     let chunks: Vec<Bytes> = stream.try_collect().await.unwrap();
+    ///     assert_eq!(chunks.concat(), b"Loco");
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the download operation fails or if there
+    /// is an issue with the strategy configuration.
+    pub async fn download_stream(
+        &self,
+        path: &Path,
+        range: Option<Range<u64>>,
+    ) -> StorageResult<BytesStream> {
+        self.download_stream_with_policy(path, range, &*self.strategy)
+            .await
+    }
+
+    /// Downloads content from the storage at the specified path as a stream
+    /// using a specific strategy.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the download operation fails or if there
+    /// is an issue with the strategy configuration.
+    pub async fn download_stream_with_policy(
+        &self,
+        path: &Path,
+        range: Option<Range<u64>>,
+        strategy: &dyn strategies::StorageStrategy,
+    ) -> StorageResult<BytesStream> {
+        strategy.download_stream(self, path, range).await
+    }
+
     /// Downloads content from the storage at the specified path.
     ///
     /// This method uses the selected strategy for the download operation.
@@ -321,6 +558,151 @@ impl Storage {
         strategy.copy(self, from, to).await
     }
 
+    /// Lists the objects stored under the specified prefix, recursively.
+    ///
+    /// This method uses the selected strategy for the list operation.
+    ///
+    /// # Examples
+    ///```
+    /// use loco_rs::storage;
+    /// use std::path::Path;
+    /// use bytes::Bytes;
+    /// use futures_util::TryStreamExt;
+    /// pub async fn list() {
+    ///     let storage = storage::Storage::single(storage::drivers::mem::new());
+    ///     storage.upload(Path::new("users/1.txt"), &Bytes::from("Loco!")).await;
+    ///
+    ///     let objects: Vec<_> = storage
+    ///         .list(Path::new("users/"))
+    ///         .await
+    ///         .unwrap()
+    ///         .try_collect()
+    ///         .await
+    ///         .unwrap();
+    ///     assert_eq!(objects.len(), 1);
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the list operation fails or if there
+    /// is an issue with the strategy configuration.
+    pub async fn list(&self, prefix: &Path) -> StorageResult<ListStream> {
+        self.list_with_policy(prefix, &*self.strategy).await
+    }
+
+    /// Lists the objects stored under the specified prefix using a specific
+    /// strategy.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the list operation fails or if there
+    /// is an issue with the strategy configuration.
+    pub async fn list_with_policy(
+        &self,
+        prefix: &Path,
+        strategy: &dyn strategies::StorageStrategy,
+    ) -> StorageResult<ListStream> {
+        strategy.list(self, prefix).await
+    }
+
+    /// Returns the metadata (size, content type, etag and last modified
+    /// date) of the content at the specified path.
+    ///
+    /// This method uses the selected strategy for the stat operation.
+    ///
+    /// # Examples
+    ///```
+    /// use loco_rs::storage;
+    /// use std::path::Path;
+    /// use bytes::Bytes;
+    /// pub async fn stat() {
+    ///     let storage = storage::Storage::single(storage::drivers::mem::new());
+    ///     let path = Path::new("example.txt");
+    ///     storage.upload(path, &Bytes::from("Loco!")).await;
+    ///
+    ///     let meta = storage.stat(path).await.unwrap();
+    ///     assert_eq!(meta.size, 5);
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the stat operation fails or if there
+    /// is an issue with the strategy configuration.
+    pub async fn stat(&self, path: &Path) -> StorageResult<ObjectMetadata> {
+        self.stat_with_policy(path, &*self.strategy).await
+    }
+
+    /// Returns the metadata of the content at the specified path using a
+    /// specific strategy.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the stat operation fails or if there
+    /// is an issue with the strategy configuration.
+    pub async fn stat_with_policy(
+        &self,
+        path: &Path,
+        strategy: &dyn strategies::StorageStrategy,
+    ) -> StorageResult<ObjectMetadata> {
+        strategy.stat(self, path).await
+    }
+
+    /// Returns a temporary URL to access the content at the specified path
+    /// with the given HTTP method, valid until `expires_in` elapses.
+    ///
+    /// S3, Azure blob and GCS stores use their native presigning. Other
+    /// stores fall back to the configured [`signed_url::UrlSigner`], which
+    /// only supports `GET` and requires mounting [`signed_url::routes`].
+    ///
+    /// # Examples
+    ///```
+    /// use loco_rs::storage::{self, signed_url::UrlSigner};
+    /// use std::{path::Path, time::Duration};
+    /// use axum::http::Method;
+    /// pub async fn presigned_url() {
+    ///     let storage = storage::Storage::single(storage::drivers::mem::new())
+    ///         .with_url_signer(UrlSigner::new("secret", "http://localhost:5150/_storage"));
+    ///
+    ///     let url = storage
+    ///         .presigned_url(Path::new("example.txt"), Duration::from_secs(300), &Method::GET)
+    ///         .await
+    ///         .unwrap();
+    ///     assert!(url.starts_with("http://localhost:5150/_storage/example.txt?"));
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if presigning fails, or if the store has
+    /// no native presigning and no signer is configured or the method is not
+    /// `GET`.
+    pub async fn presigned_url(
+        &self,
+        path: &Path,
+        expires_in: Duration,
+        method: &Method,
+    ) -> StorageResult<String> {
+        if let Some(url) = self
+            .strategy
+            .presign(self, path, expires_in, method)
+            .await?
+        {
+            return Ok(url);
+        }
+
+        match &self.url_signer {
+            Some(signer) if method == Method::GET => signer.sign(path, expires_in),
+            Some(_) => Err(StorageError::Any(
+                format!("signed URLs only support GET, got {method}").into(),
+            )),
+            None => Err(StorageError::Any(
+                "the store has no native presigning and no URL signer is configured".into(),
+            )),
+        }
+    }
+
     /// Returns a reference to the store with the specified name if exists.
     ///
     /// # Examples
@@ -364,4 +746,260 @@ impl Storage {
         self.as_store(name)
             .ok_or(StorageError::StoreNotFound(name.to_string()))
     }
+
+    /// Returns a shared handle to the store with the specified name, for work
+    /// that outlives the borrow of the storage such as background tasks.
+    pub(crate) fn as_shared_store(&self, name: &str) -> StorageResult<Arc<dyn StoreDriver>> {
+        self.stores
+            .get(name)
+            .cloned()
+            .ok_or(StorageError::StoreNotFound(name.to_string()))
+    }
+}
+
+fn create_strategy(
+    config: &StorageStrategyConfig,
+) -> StorageResult<Box<dyn strategies::StorageStrategy>> {
+    Ok(match config {
+        StorageStrategyConfig::Single { store } => {
+            Box::new(strategies::single::SingleStrategy::new(store))
+        }
+        StorageStrategyConfig::Mirror {
+            primary,
+            secondaries,
+            failure_mode,
+            read_repair,
+        } => Box::new(
+            strategies::mirror::MirrorStrategy::new(
+                primary,
+                Some(secondaries.clone()),
+                failure_mode.clone(),
+            )
+            .with_read_repair(*read_repair),
+        ),
+        StorageStrategyConfig::Backup {
+            primary,
+            secondaries,
+            failure_mode,
+        } => Box::new(strategies::backup::BackupStrategy::new(
+            primary,
+            Some(secondaries.clone()),
+            failure_mode.clone(),
+        )),
+        #[cfg(feature = "storage_encryption")]
+        StorageStrategyConfig::Encrypted {
+            key_id,
+            keys,
+            strategy,
+        } => Box::new(strategies::encrypted::EncryptedStrategy::from_hex_keys(
+            create_strategy(strategy)?,
+            key_id,
+            keys,
+        )?),
+        #[allow(unreachable_patterns)]
+        _ => {
+            return Err(StorageError::Any(
+                "the feature of the configured storage strategy was not selected and compiled"
+                    .into(),
+            ))
+        }
+    })
+}
+
+/// Returns the names of the stores the strategy refers to.
+fn strategy_stores(config: &StorageStrategyConfig) -> Vec<&String> {
+    match config {
+        StorageStrategyConfig::Single { store } => vec![store],
+        StorageStrategyConfig::Mirror {
+            primary,
+            secondaries,
+            ..
+        }
+        | StorageStrategyConfig::Backup {
+            primary,
+            secondaries,
+            ..
+        } => std::iter::once(primary).chain(secondaries).collect(),
+        StorageStrategyConfig::Encrypted { strategy, .. } => strategy_stores(strategy),
+    }
+}
+
+fn create_store(config: &StoreConfig) -> StorageResult<Box<dyn StoreDriver>> {
+    match config {
+        StoreConfig::Local(cfg) => drivers::local::new_with_prefix(&cfg.root),
+        StoreConfig::Mem => Ok(drivers::mem::new()),
+        #[cfg(feature = "storage_aws_s3")]
+        StoreConfig::S3(cfg) => match (&cfg.key_id, &cfg.secret_key) {
+            (Some(key_id), Some(secret_key)) => drivers::aws::with_credentials(
+                &cfg.bucket,
+                &cfg.region,
+                drivers::aws::Credential {
+                    key_id: key_id.clone(),
+                    secret_key: secret_key.clone(),
+                    token: cfg.token.clone(),
+                },
+            ),
+            _ => drivers::aws::new(&cfg.bucket, &cfg.region),
+        },
+        #[cfg(feature = "storage_azure")]
+        StoreConfig::Azure(cfg) => {
+            drivers::azure::new(&cfg.container, &cfg.account_name, &cfg.access_key)
+        }
+        #[cfg(feature = "storage_gcp")]
+        StoreConfig::Gcp(cfg) => drivers::gcp::new(&cfg.bucket, &cfg.credential_path),
+        #[allow(unreachable_patterns)]
+        _ => Err(StorageError::Any(
+            "the storage driver feature of a configured store was not selected and compiled".into(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(yaml: &str) -> StorageConfig {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[tokio::test]
+    async fn can_create_from_config() {
+        let storage = Storage::from_config(&config(
+            r"
+stores:
+  primary:
+    kind: Mem
+  secondary:
+    kind: Mem
+strategy:
+  kind: Mirror
+  primary: primary
+  secondaries:
+    - secondary
+  failure_mode: MirrorAll
+",
+        ))
+        .unwrap();
+
+        let path = Path::new("users/1.txt");
+        assert!(storage.upload(path, &Bytes::from("content")).await.is_ok());
+        assert!(storage
+            .as_store("secondary")
+            .unwrap()
+            .exists(path)
+            .await
+            .unwrap());
+    }
+
+    #[test]
+    fn can_create_single_store_without_strategy() {
+        let storage = Storage::from_config(&config(
+            r"
+stores:
+  files:
+    kind: Mem
+",
+        ))
+        .unwrap();
+        assert!(storage.as_store("files").is_some());
+    }
+
+    #[test]
+    fn cannot_create_with_unknown_store() {
+        let res = Storage::from_config(&config(
+            r"
+stores:
+  files:
+    kind: Mem
+strategy:
+  kind: Backup
+  primary: files
+  secondaries:
+    - missing
+  failure_mode:
+    CountFailure: 1
+",
+        ));
+        assert!(matches!(res, Err(StorageError::StoreNotFound(name)) if name == "missing"));
+    }
+
+    #[test]
+    fn cannot_create_many_stores_without_strategy() {
+        let res = Storage::from_config(&config(
+            r"
+stores:
+  one:
+    kind: Mem
+  two:
+    kind: Mem
+",
+        ));
+        assert!(res.is_err());
+    }
+
+    #[cfg(feature = "storage_encryption")]
+    #[tokio::test]
+    async fn can_create_encrypted_from_config() {
+        let key = "ab".repeat(32);
+        let storage = Storage::from_config(&config(&format!(
+            r"
+stores:
+  local:
+    kind: Mem
+strategy:
+  kind: Encrypted
+  key_id: current
+  keys:
+    current: {key}
+  strategy:
+    kind: Single
+    store: local
+"
+        )))
+        .unwrap();
+
+        let path = Path::new("users/1.txt");
+        assert!(storage.upload(path, &Bytes::from("content")).await.is_ok());
+
+        let stored = storage
+            .as_store("local")
+            .unwrap()
+            .get(path)
+            .await
+            .unwrap()
+            .bytes()
+            .await
+            .unwrap();
+        assert_eq!(strategies::encrypted::key_id(&stored), Some("current"));
+
+        let content: String = storage.download(path).await.unwrap();
+        assert_eq!(content, "content");
+    }
+
+    #[tokio::test]
+    async fn can_presign_with_url_signer() {
+        let storage = Storage::single(drivers::mem::new());
+        let path = Path::new("users/1.txt");
+        let expires_in = Duration::from_secs(60);
+
+        assert!(storage
+            .presigned_url(path, expires_in, &Method::GET)
+            .await
+            .is_err());
+
+        let storage = storage.with_url_signer(signed_url::UrlSigner::new(
+            "secret",
+            "http://localhost:5150/_storage",
+        ));
+        let url = storage
+            .presigned_url(path, expires_in, &Method::GET)
+            .await
+            .unwrap();
+        assert!(url.starts_with("http://localhost:5150/_storage/users/1.txt?expires="));
+
+        assert!(storage
+            .presigned_url(path, expires_in, &Method::PUT)
+            .await
+            .is_err());
+    }
 }
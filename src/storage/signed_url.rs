@@ -0,0 +1,229 @@
+//! # Signed URLs
+//!
+//! Stores without native presigning (such as `local` and `mem`) can still
+//! hand out temporary links with [`UrlSigner`]: the URL carries an expiry
+//! timestamp and an HMAC-SHA256 signature of the path and expiry, which
+//! the route returned by [`routes`] verifies before streaming the file.
+//!
+//! ```rust,ignore
+//! AppRoutes::with_default_routes()
+//!     .add_route(loco_rs::storage::signed_url::routes())
+//! ```
+use std::{fmt::Write, path::Path, time::Duration};
+
+use axum::{
+    extract::{Path as PathParam, Query, State},
+    response::Response,
+    routing::get,
+};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+
+use super::{StorageError, StorageResult};
+use crate::{
+    app::AppContext,
+    controller::{format, Routes},
+    Error, Result,
+};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// The prefix of the route serving signed URLs.
+pub const ROUTE_PREFIX: &str = "_storage";
+
+/// Signs and verifies storage URLs.
+#[derive(Clone)]
+pub struct UrlSigner {
+    secret: Vec<u8>,
+    base_url: String,
+}
+
+impl UrlSigner {
+    /// Creates a new signer.
+    ///
+    /// `base_url` is the public URL of the route returned by [`routes`], for
+    /// example `http://localhost:5150/_storage`.
+    #[must_use]
+    pub fn new(secret: &str, base_url: &str) -> Self {
+        Self {
+            secret: secret.as_bytes().to_vec(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Returns a URL to download the given path, valid until `expires_in`
+    /// elapses.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the signing key is invalid.
+    pub fn sign(&self, path: &Path, expires_in: Duration) -> StorageResult<String> {
+        let path = normalize(path);
+        let expires = chrono::Utc::now().timestamp()
+            + i64::try_from(expires_in.as_secs()).unwrap_or(i64::MAX / 2);
+        let signature = to_hex(&self.mac(&path, expires)?.finalize().into_bytes());
+
+        Ok(format!(
+            "{}/{}?expires={expires}&signature={signature}",
+            self.base_url,
+            encode_path(&path)
+        ))
+    }
+
+    /// Verifies the signature and expiry of a signed URL.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the signing key is invalid.
+    pub fn verify(&self, path: &Path, expires: i64, signature: &str) -> StorageResult<bool> {
+        if expires < chrono::Utc::now().timestamp() {
+            return Ok(false);
+        }
+        let Some(signature) = from_hex(signature) else {
+            return Ok(false);
+        };
+        Ok(self
+            .mac(&normalize(path), expires)?
+            .verify_slice(&signature)
+            .is_ok())
+    }
+
+    fn mac(&self, path: &str, expires: i64) -> StorageResult<HmacSha256> {
+        let mut mac = HmacSha256::new_from_slice(&self.secret)
+            .map_err(|err| StorageError::Any(Box::new(err)))?;
+        mac.update(format!("GET\n{path}\n{expires}").as_bytes());
+        Ok(mac)
+    }
+}
+
+impl std::fmt::Debug for UrlSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UrlSigner")
+            .field("base_url", &self.base_url)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SignedParams {
+    expires: i64,
+    signature: String,
+}
+
+/// Verifies the signature and expiry, then streams the file.
+async fn serve(
+    State(ctx): State<AppContext>,
+    PathParam(path): PathParam<String>,
+    Query(params): Query<SignedParams>,
+) -> Result<Response> {
+    let signer = ctx
+        .storage
+        .url_signer()
+        .ok_or_else(|| Error::Message("storage URL signing is not configured".to_string()))?;
+
+    let path = Path::new(&path);
+    if !signer.verify(path, params.expires, &params.signature)? {
+        return Err(Error::Unauthorized(
+            "invalid or expired signature".to_string(),
+        ));
+    }
+
+    let meta = ctx.storage.stat(path).await.map_err(|_| Error::NotFound)?;
+    let stream = ctx.storage.download_stream(path, None).await?;
+
+    format::stream(
+        stream,
+        meta.content_type
+            .as_deref()
+            .unwrap_or("application/octet-stream"),
+        Some(meta.size),
+    )
+}
+
+/// Defines and returns the route serving signed URLs, under
+/// [`ROUTE_PREFIX`].
+#[must_use]
+pub fn routes() -> Routes {
+    Routes::at(ROUTE_PREFIX).add("/{*path}", get(serve))
+}
+
+fn normalize(path: &Path) -> String {
+    path.display()
+        .to_string()
+        .trim_start_matches('/')
+        .to_string()
+}
+
+fn encode_path(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~/".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            let _ = write!(encoded, "%{byte:02X}");
+        }
+    }
+    encoded
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+pub(crate) fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_sign_and_verify() {
+        let signer = UrlSigner::new("secret", "http://localhost:5150/_storage/");
+        let path = Path::new("users/my file.txt");
+
+        let url = signer.sign(path, Duration::from_secs(60)).unwrap();
+        assert!(url.starts_with("http://localhost:5150/_storage/users/my%20file.txt?expires="));
+
+        let query = url.split_once('?').unwrap().1;
+        let (expires, signature) = query.split_once('&').unwrap();
+        let expires: i64 = expires.trim_start_matches("expires=").parse().unwrap();
+        let signature = signature.trim_start_matches("signature=");
+
+        assert!(signer.verify(path, expires, signature).unwrap());
+        assert!(!signer
+            .verify(Path::new("users/other.txt"), expires, signature)
+            .unwrap());
+        assert!(!signer.verify(path, expires + 1, signature).unwrap());
+        assert!(!UrlSigner::new("other", "http://localhost")
+            .verify(path, expires, signature)
+            .unwrap());
+    }
+
+    #[test]
+    fn cannot_verify_expired() {
+        let signer = UrlSigner::new("secret", "http://localhost:5150/_storage");
+        let path = Path::new("1.txt");
+        let expires = chrono::Utc::now().timestamp() - 1;
+        let signature = to_hex(
+            &signer
+                .mac("1.txt", expires)
+                .unwrap()
+                .finalize()
+                .into_bytes(),
+        );
+
+        assert!(!signer.verify(path, expires, &signature).unwrap());
+    }
+}
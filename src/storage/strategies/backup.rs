@@ -21,14 +21,26 @@
 //!
 //! * `download`: Initiates the download of the given path only from primary
 //!   storage.
-use std::{collections::BTreeMap, path::Path};
+//!
+//! * `upload_stream`: Streams the content to the primary storage, then streams
+//!   it from the primary to each secondary storage, following the same
+//!   [`FailureMode`] as `upload`.
+//!
+//! * `download_stream`/`list`/`stat`: Same as `download`, only from primary
+//!   storage.
+use std::{collections::BTreeMap, ops::Range, path::Path, time::Duration};
 
+use axum::http::Method;
 use bytes::Bytes;
 
-use crate::storage::{strategies::StorageStrategy, Storage, StorageError, StorageResult};
+use crate::storage::{
+    drivers::{BytesStream, ListStream, ObjectMetadata},
+    strategies::{replicate, StorageStrategy},
+    Storage, StorageError, StorageResult,
+};
 
 /// Enum representing the failure mode for the [`BackupStrategy`].
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
 pub enum FailureMode {
     /// Fail if any secondary storage backend encounters an error.
     BackupAll,
@@ -93,6 +105,54 @@ impl StorageStrategy for BackupStrategy {
         Ok(store.get(path).await?.bytes().await?)
     }
 
+    /// Uploads the content of the given stream to the primary and, if
+    /// configured, copies it from the primary to the secondary storage
+    /// backends.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageResult`] indicating success or an error depend of the
+    /// [`FailureMode`].
+    async fn upload_stream(
+        &self,
+        storage: &Storage,
+        path: &Path,
+        stream: BytesStream,
+    ) -> StorageResult<()> {
+        storage
+            .as_store_err(&self.primary)?
+            .upload_stream(path, stream)
+            .await?;
+
+        let mut collect_errors: BTreeMap<String, String> = BTreeMap::new();
+        if let Some(secondaries) = self.secondaries.as_ref() {
+            for secondary_store in secondaries {
+                if let Err(err) = replicate(storage, &self.primary, secondary_store, path).await {
+                    collect_errors.insert(secondary_store.to_string(), err.to_string());
+                }
+            }
+        }
+
+        if self.failure_mode.should_fail(&collect_errors) {
+            return Err(StorageError::Multi(collect_errors));
+        }
+
+        Ok(())
+    }
+
+    /// Downloads content as a stream only from primary storage backend.
+    async fn download_stream(
+        &self,
+        storage: &Storage,
+        path: &Path,
+        range: Option<Range<u64>>,
+    ) -> StorageResult<BytesStream> {
+        storage
+            .as_store_err(&self.primary)?
+            .download_stream(path, range)
+            .await
+    }
+
     /// Deletes content from the primary and, if configured, secondary storage
     /// backends.
     ///
@@ -194,6 +254,32 @@ impl StorageStrategy for BackupStrategy {
 
         Ok(())
     }
+
+    /// Lists the objects under the given prefix only from primary storage
+    /// backend.
+    async fn list(&self, storage: &Storage, prefix: &Path) -> StorageResult<ListStream> {
+        storage.as_store_err(&self.primary)?.list(prefix).await
+    }
+
+    /// Returns the metadata of the given path only from primary storage
+    /// backend.
+    async fn stat(&self, storage: &Storage, path: &Path) -> StorageResult<ObjectMetadata> {
+        storage.as_store_err(&self.primary)?.stat(path).await
+    }
+
+    /// Returns a presigned URL from the primary storage backend.
+    async fn presign(
+        &self,
+        storage: &Storage,
+        path: &Path,
+        expires_in: Duration,
+        method: &Method,
+    ) -> StorageResult<Option<String>> {
+        storage
+            .as_store_err(&self.primary)?
+            .presign(path, expires_in, method)
+            .await
+    }
 }
 
 impl BackupStrategy {
@@ -0,0 +1,531 @@
+//! # `EncryptedStrategy` Implementation for Storage Strategies
+//!
+//! This module provides an implementation of the [`StorageStrategy`] that
+//! encrypts the content with AES-256-GCM before handing it to an inner
+//! strategy, and decrypts it on download. Any strategy can be wrapped, so
+//! the content of a [`MirrorStrategy`](super::mirror::MirrorStrategy) or a
+//! [`BackupStrategy`](super::backup::BackupStrategy) is encrypted once and
+//! replicated as is.
+//!
+//! ## Key rotation
+//!
+//! Every object starts with a header naming the key it was encrypted with.
+//! New content is encrypted with the current key, and content encrypted with
+//! any other known key is still decrypted, so a key can be rotated by adding
+//! a new key, making it the current one and keeping the old one until all
+//! objects are re-uploaded.
+//!
+//! ## Strategy Description per operation
+//!
+//! * `upload`/`upload_stream`: Encrypts the content in memory, then uploads it
+//!   with the inner strategy.
+//! * `download`/`download_stream`: Downloads the content with the inner
+//!   strategy and decrypts it in memory.
+//! * `delete`/`rename`/`copy`: Handled by the inner strategy.
+//! * `list`/`stat`: Handled by the inner strategy, reporting the size of the
+//!   decrypted content. When the known keys have ids of different lengths,
+//!   the header of each object is read to tell its size.
+//! * `presign`: Never presigns, since the store would serve encrypted
+//!   content. [`Storage::presigned_url`] falls back to signed URLs, which are
+//!   served decrypted.
+//!
+//! Requires the `storage_encryption` feature.
+use std::{collections::BTreeMap, ops::Range, path::Path, time::Duration};
+
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    Aes256Gcm, Nonce,
+};
+use axum::http::Method;
+use bytes::Bytes;
+use futures_util::{stream, StreamExt, TryStreamExt};
+
+use crate::storage::{
+    drivers::{ListStream, ObjectMetadata},
+    signed_url::from_hex,
+    strategies::StorageStrategy,
+    Storage, StorageError, StorageResult,
+};
+
+/// Identifies encrypted objects.
+const MAGIC: &[u8] = b"LOCOENC";
+const VERSION: u8 = 1;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+/// Encrypts content before delegating to an inner strategy.
+pub struct EncryptedStrategy {
+    /// The strategy storing the encrypted content.
+    pub inner: Box<dyn StorageStrategy>,
+    key_id: String,
+    keys: BTreeMap<String, Aes256Gcm>,
+}
+
+impl EncryptedStrategy {
+    /// Creates a new instance of `EncryptedStrategy`, encrypting with the
+    /// 32 bytes key named `key_id` and decrypting with any of `keys`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `key_id` is not one of `keys`, or if a key is not
+    /// 32 bytes long.
+    pub fn new(
+        inner: Box<dyn StorageStrategy>,
+        key_id: &str,
+        keys: &BTreeMap<String, Vec<u8>>,
+    ) -> StorageResult<Self> {
+        if !keys.contains_key(key_id) {
+            return Err(StorageError::Any(
+                format!("encryption key `{key_id}` is not configured").into(),
+            ));
+        }
+        if key_id.len() > usize::from(u8::MAX) {
+            return Err(StorageError::Any(
+                "encryption key ids must be at most 255 bytes long".into(),
+            ));
+        }
+
+        let keys = keys
+            .iter()
+            .map(|(id, key)| {
+                Aes256Gcm::new_from_slice(key)
+                    .map(|cipher| (id.clone(), cipher))
+                    .map_err(|_| {
+                        StorageError::Any(
+                            format!("encryption key `{id}` must be 32 bytes long").into(),
+                        )
+                    })
+            })
+            .collect::<StorageResult<_>>()?;
+
+        Ok(Self {
+            inner,
+            key_id: key_id.to_string(),
+            keys,
+        })
+    }
+
+    /// Same as [`EncryptedStrategy::new`], with hex encoded keys as found in
+    /// the configuration.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a key is not valid hex, or see
+    /// [`EncryptedStrategy::new`].
+    pub fn from_hex_keys(
+        inner: Box<dyn StorageStrategy>,
+        key_id: &str,
+        keys: &BTreeMap<String, String>,
+    ) -> StorageResult<Self> {
+        let keys = keys
+            .iter()
+            .map(|(id, key)| {
+                from_hex(key).map(|key| (id.clone(), key)).ok_or_else(|| {
+                    StorageError::Any(format!("encryption key `{id}` is not valid hex").into())
+                })
+            })
+            .collect::<StorageResult<_>>()?;
+        Self::new(inner, key_id, &keys)
+    }
+
+    /// Encrypts the content with the current key.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the encryption fails.
+    pub fn encrypt(&self, content: &[u8]) -> StorageResult<Bytes> {
+        let cipher = &self.keys[&self.key_id];
+        let header = header(&self.key_id);
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+
+        let encrypted = cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: content,
+                    aad: &header,
+                },
+            )
+            .map_err(|_| StorageError::Any("could not encrypt content".into()))?;
+
+        let mut output = header;
+        output.extend_from_slice(&nonce);
+        output.extend_from_slice(&encrypted);
+        Ok(output.into())
+    }
+
+    /// Decrypts content encrypted with any of the known keys.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the content is not encrypted, was encrypted with
+    /// an unknown key, or was tampered with.
+    pub fn decrypt(&self, content: &[u8]) -> StorageResult<Bytes> {
+        let key_id =
+            key_id(content).ok_or_else(|| StorageError::Any("content is not encrypted".into()))?;
+        let cipher = self.keys.get(key_id).ok_or_else(|| {
+            StorageError::Any(format!("unknown encryption key `{key_id}`").into())
+        })?;
+
+        let header_len = MAGIC.len() + 2 + key_id.len();
+        let (header, rest) = content.split_at(header_len);
+        if rest.len() < NONCE_LEN {
+            return Err(StorageError::Any("encrypted content is truncated".into()));
+        }
+        let (nonce, encrypted) = rest.split_at(NONCE_LEN);
+
+        let decrypted = cipher
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: encrypted,
+                    aad: header,
+                },
+            )
+            .map_err(|_| StorageError::Any("could not decrypt content".into()))?;
+        Ok(decrypted.into())
+    }
+}
+
+impl EncryptedStrategy {
+    /// The length of the key ids when all the known keys share it.
+    fn uniform_key_id_len(&self) -> Option<usize> {
+        let mut lens = self.keys.keys().map(String::len);
+        let first = lens.next()?;
+        lens.all(|len| len == first).then_some(first)
+    }
+
+    /// Replaces the size of an encrypted object with the size of its content.
+    async fn decrypted_meta(
+        &self,
+        storage: &Storage,
+        mut meta: ObjectMetadata,
+    ) -> StorageResult<ObjectMetadata> {
+        let key_id_len = match self.uniform_key_id_len() {
+            Some(len) => len,
+            None => {
+                let max_header_len = (MAGIC.len() + 2 + usize::from(u8::MAX)) as u64;
+                let header_range: Range<u64> = 0..meta.size.min(max_header_len);
+                let head: Vec<Bytes> = self
+                    .inner
+                    .download_stream(storage, &meta.path, Some(header_range))
+                    .await?
+                    .try_collect()
+                    .await?;
+                match key_id(&head.concat()) {
+                    Some(key_id) => key_id.len(),
+                    // not encrypted, served as is
+                    None => return Ok(meta),
+                }
+            }
+        };
+        let overhead = MAGIC.len() + 2 + key_id_len + NONCE_LEN + TAG_LEN;
+        meta.size = meta.size.saturating_sub(overhead as u64);
+        Ok(meta)
+    }
+}
+
+fn header(key_id: &str) -> Vec<u8> {
+    let mut header = Vec::with_capacity(MAGIC.len() + 2 + key_id.len());
+    header.extend_from_slice(MAGIC);
+    header.push(VERSION);
+    // the length is checked when creating the strategy
+    header.push(u8::try_from(key_id.len()).unwrap_or(u8::MAX));
+    header.extend_from_slice(key_id.as_bytes());
+    header
+}
+
+/// Returns the id of the key the content was encrypted with, or `None` when
+/// the content is not encrypted. Useful to find the objects to re-upload when
+/// rotating keys.
+#[must_use]
+pub fn key_id(content: &[u8]) -> Option<&str> {
+    let rest = content.strip_prefix(MAGIC)?;
+    let (&version, rest) = rest.split_first()?;
+    if version != VERSION {
+        return None;
+    }
+    let (&len, rest) = rest.split_first()?;
+    std::str::from_utf8(rest.get(..usize::from(len))?).ok()
+}
+
+/// Implementation of `StorageStrategy` encrypting the content of an inner
+/// strategy.
+#[async_trait::async_trait]
+impl StorageStrategy for EncryptedStrategy {
+    /// Encrypts and uploads content with the inner strategy.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageResult`] indicating of the operation status.
+    async fn upload(&self, storage: &Storage, path: &Path, content: &Bytes) -> StorageResult<()> {
+        let content = self.encrypt(content)?;
+        self.inner.upload(storage, path, &content).await
+    }
+
+    /// Downloads content with the inner strategy and decrypts it.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageResult`] indicating of the operation status.
+    async fn download(&self, storage: &Storage, path: &Path) -> StorageResult<Bytes> {
+        let content = self.inner.download(storage, path).await?;
+        self.decrypt(&content)
+    }
+
+    /// Deletes the given path with the inner strategy
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageResult`] indicating of the operation status.
+    async fn delete(&self, storage: &Storage, path: &Path) -> StorageResult<()> {
+        self.inner.delete(storage, path).await
+    }
+
+    /// Renames the file name with the inner strategy
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageResult`] indicating of the operation status.
+    async fn rename(&self, storage: &Storage, from: &Path, to: &Path) -> StorageResult<()> {
+        self.inner.rename(storage, from, to).await
+    }
+
+    /// Copy file from the given path to the new path with the inner strategy
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageResult`] indicating of the operation status.
+    async fn copy(&self, storage: &Storage, from: &Path, to: &Path) -> StorageResult<()> {
+        self.inner.copy(storage, from, to).await
+    }
+
+    /// Lists the objects under the given prefix with the inner strategy,
+    /// with the size of their decrypted content
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageResult`] indicating of the operation status.
+    async fn list(&self, storage: &Storage, prefix: &Path) -> StorageResult<ListStream> {
+        let list = self.inner.list(storage, prefix).await?;
+        if let Some(key_id_len) = self.uniform_key_id_len() {
+            let overhead = (MAGIC.len() + 2 + key_id_len + NONCE_LEN + TAG_LEN) as u64;
+            return Ok(list
+                .map_ok(move |mut meta| {
+                    meta.size = meta.size.saturating_sub(overhead);
+                    meta
+                })
+                .boxed());
+        }
+
+        // the header of each object tells the length of its key id
+        let objects: Vec<ObjectMetadata> = list.try_collect().await?;
+        let mut decrypted = Vec::with_capacity(objects.len());
+        for meta in objects {
+            decrypted.push(self.decrypted_meta(storage, meta).await?);
+        }
+        Ok(stream::iter(decrypted.into_iter().map(Ok)).boxed())
+    }
+
+    /// Returns the metadata of the given path with the inner strategy, with
+    /// the size of the decrypted content
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageResult`] indicating of the operation status.
+    async fn stat(&self, storage: &Storage, path: &Path) -> StorageResult<ObjectMetadata> {
+        let meta = self.inner.stat(storage, path).await?;
+        self.decrypted_meta(storage, meta).await
+    }
+
+    /// Never presigns, since stores would serve the encrypted content
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageResult`] indicating of the operation status.
+    async fn presign(
+        &self,
+        _storage: &Storage,
+        _path: &Path,
+        _expires_in: Duration,
+        _method: &Method,
+    ) -> StorageResult<Option<String>> {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::storage::{
+        drivers,
+        strategies::{mirror, mirror::MirrorStrategy, single::SingleStrategy},
+    };
+
+    fn keys() -> BTreeMap<String, Vec<u8>> {
+        BTreeMap::from([
+            ("old".to_string(), vec![1; 32]),
+            ("new".to_string(), vec![2; 32]),
+        ])
+    }
+
+    fn strategy(key_id: &str) -> EncryptedStrategy {
+        EncryptedStrategy::new(Box::new(SingleStrategy::new("default")), key_id, &keys()).unwrap()
+    }
+
+    #[test]
+    fn can_encrypt_and_decrypt() {
+        let strategy = strategy("new");
+
+        let encrypted = strategy.encrypt(b"content").unwrap();
+        assert_eq!(key_id(&encrypted), Some("new"));
+        assert!(!encrypted.windows(7).any(|w| w == b"content"));
+        assert_eq!(strategy.decrypt(&encrypted).unwrap(), "content");
+
+        // the nonce is random
+        assert_ne!(strategy.encrypt(b"content").unwrap(), encrypted);
+    }
+
+    #[test]
+    fn can_decrypt_with_rotated_key() {
+        let encrypted = strategy("old").encrypt(b"content").unwrap();
+        assert_eq!(strategy("new").decrypt(&encrypted).unwrap(), "content");
+
+        let only_new = EncryptedStrategy::new(
+            Box::new(SingleStrategy::new("default")),
+            "new",
+            &BTreeMap::from([("new".to_string(), vec![2; 32])]),
+        )
+        .unwrap();
+        assert!(only_new.decrypt(&encrypted).is_err());
+    }
+
+    #[test]
+    fn cannot_decrypt_tampered_content() {
+        let strategy = strategy("new");
+        let mut encrypted = strategy.encrypt(b"content").unwrap().to_vec();
+
+        let last = encrypted.len() - 1;
+        encrypted[last] ^= 1;
+        assert!(strategy.decrypt(&encrypted).is_err());
+        assert!(strategy.decrypt(b"content").is_err());
+    }
+
+    #[test]
+    fn cannot_create_with_invalid_keys() {
+        let inner = || Box::new(SingleStrategy::new("default")) as Box<dyn StorageStrategy>;
+        assert!(EncryptedStrategy::new(inner(), "missing", &keys()).is_err());
+        assert!(EncryptedStrategy::new(
+            inner(),
+            "short",
+            &BTreeMap::from([("short".to_string(), vec![1; 16])])
+        )
+        .is_err());
+        assert!(EncryptedStrategy::from_hex_keys(
+            inner(),
+            "key",
+            &BTreeMap::from([("key".to_string(), "zz".to_string())])
+        )
+        .is_err());
+        assert!(EncryptedStrategy::from_hex_keys(
+            inner(),
+            "key",
+            &BTreeMap::from([("key".to_string(), "ab".repeat(32))])
+        )
+        .is_ok());
+    }
+
+    #[tokio::test]
+    async fn reports_decrypted_sizes() {
+        for key_ids in [["old", "new"], ["k", "longer"]] {
+            let keys = BTreeMap::from([
+                (key_ids[0].to_string(), vec![1; 32]),
+                (key_ids[1].to_string(), vec![2; 32]),
+            ]);
+            let storage = Storage::single(drivers::mem::new());
+            for (key_id, path) in [(key_ids[0], "users/1.txt"), (key_ids[1], "users/2.txt")] {
+                let strategy =
+                    EncryptedStrategy::new(Box::new(SingleStrategy::new("store")), key_id, &keys)
+                        .unwrap();
+                assert!(storage
+                    .upload_with_strategy(Path::new(path), &Bytes::from("file content"), &strategy)
+                    .await
+                    .is_ok());
+            }
+
+            let strategy =
+                EncryptedStrategy::new(Box::new(SingleStrategy::new("store")), key_ids[1], &keys)
+                    .unwrap();
+            for path in ["users/1.txt", "users/2.txt"] {
+                let meta = storage
+                    .stat_with_policy(Path::new(path), &strategy)
+                    .await
+                    .unwrap();
+                assert_eq!(meta.size, 12);
+            }
+            let objects: Vec<ObjectMetadata> = storage
+                .list_with_policy(Path::new("users/"), &strategy)
+                .await
+                .unwrap()
+                .try_collect()
+                .await
+                .unwrap();
+            assert_eq!(objects.len(), 2);
+            assert!(objects.iter().all(|meta| meta.size == 12));
+        }
+    }
+
+    #[tokio::test]
+    async fn can_encrypt_mirrored_content() {
+        let strategy = EncryptedStrategy::new(
+            Box::new(MirrorStrategy::new(
+                "primary",
+                Some(vec!["secondary".to_string()]),
+                mirror::FailureMode::MirrorAll,
+            )),
+            "new",
+            &keys(),
+        )
+        .unwrap();
+        let storage = Storage::new(
+            BTreeMap::from([
+                ("primary".to_string(), drivers::mem::new()),
+                ("secondary".to_string(), drivers::mem::new()),
+            ]),
+            Box::new(strategy),
+        );
+
+        let path = PathBuf::from("users").join("1.txt");
+        assert!(storage
+            .upload(path.as_path(), &Bytes::from("file content"))
+            .await
+            .is_ok());
+
+        for store in ["primary", "secondary"] {
+            let stored = storage
+                .as_store(store)
+                .unwrap()
+                .get(path.as_path())
+                .await
+                .unwrap()
+                .bytes()
+                .await
+                .unwrap();
+            assert_eq!(key_id(&stored), Some("new"));
+        }
+
+        let content: String = storage.download(path.as_path()).await.unwrap();
+        assert_eq!(content, "file content");
+
+        let content: Vec<Bytes> = storage
+            .download_stream(path.as_path(), Some(5..12))
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(content.concat(), b"content");
+    }
+}
@@ -17,17 +17,37 @@
 //!
 //! * `download`: Initiates the download of the given path from the primary
 //!   storage. If successful, it returns the content. If not found in the
-//!   primary, it looks for the content in the secondary storages. If the
-//!   content is not found in any storage backend (both primary and secondary),
-//!   it returns an error.
-use std::{collections::BTreeMap, path::Path};
+//!   primary, it looks for the content in the secondary storages, in order. If
+//!   the content is not found in any storage backend (both primary and
+//!   secondary), it returns an error. With read repair enabled (see
+//!   [`MirrorStrategy::with_read_repair`]), the content found in a secondary is
+//!   uploaded back to the storages that failed to return it.
+//!
+//! * `upload_stream`: Streams the content to the primary storage, then streams
+//!   it from the primary to each secondary storage, following the same
+//!   [`FailureMode`] as `upload`.
+//!
+//! * `download_stream`/`stat`: Same as `download`, returning a stream or the
+//!   metadata. The read repair of `download_stream` runs in a background task,
+//!   so the stream is returned without waiting for it.
+//!
+//! * `list`: Lists the objects of the primary storage.
+//!
+//! To find and fix objects that diverged between the storages, see
+//! [`Storage::resync`].
+use std::{collections::BTreeMap, ops::Range, path::Path, time::Duration};
 
+use axum::http::Method;
 use bytes::Bytes;
 
-use crate::storage::{strategies::StorageStrategy, Storage, StorageError, StorageResult};
+use crate::storage::{
+    drivers::{BytesStream, ListStream, ObjectMetadata},
+    strategies::{replicate, StorageStrategy},
+    Storage, StorageError, StorageResult,
+};
 
 /// Enum representing the failure mode for the [`MirrorStrategy`].
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
 pub enum FailureMode {
     /// Fail if any secondary storage mirror encounters an error.
     MirrorAll,
@@ -44,6 +64,9 @@ pub struct MirrorStrategy {
     pub secondaries: Option<Vec<String>>,
     /// The failure mode for handling errors from secondary storage backends.
     pub failure_mode: FailureMode,
+    /// Whether content found in a secondary is uploaded back to the
+    /// storages that failed to return it.
+    pub read_repair: bool,
 }
 
 /// Implementation of the [`StorageStrategy`] for the [`MirrorStrategy`].
@@ -91,26 +114,93 @@ impl StorageStrategy for MirrorStrategy {
     }
 
     /// Downloads content from the primary storage backend. If the primary
-    /// fails, attempts to download from secondary backends.
+    /// fails, attempts to download from secondary backends, repairing the
+    /// failed ones when read repair is enabled.
     async fn download(&self, storage: &Storage, path: &Path) -> StorageResult<Bytes> {
-        let res = Self::try_download(storage, &self.primary, path).await;
-
-        match res {
-            Ok(content) => Ok(content),
-            Err(error) => {
-                if let Some(secondaries) = self.secondaries.as_ref() {
-                    for secondary_store in secondaries {
-                        if let Ok(content) =
-                            Self::try_download(storage, secondary_store, path).await
-                        {
-                            return Ok(content);
+        let mut failed = Vec::new();
+        let mut first_error = None;
+        for store_name in self.stores() {
+            match Self::try_download(storage, store_name, path).await {
+                Ok(content) => {
+                    if self.read_repair {
+                        for failed_store in failed {
+                            Self::repair(storage, failed_store, path, &content).await;
                         }
                     }
+                    return Ok(content);
+                }
+                Err(err) => {
+                    failed.push(store_name);
+                    first_error.get_or_insert(err);
+                }
+            }
+        }
+
+        Err(first_error.unwrap_or_else(|| StorageError::StoreNotFound(self.primary.clone())))
+    }
+
+    /// Uploads the content of the given stream to the primary and, if
+    /// configured, copies it from the primary to the secondary storage
+    /// mirrors.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageResult`] indicating success or an error depend of the
+    /// [`FailureMode`].
+    async fn upload_stream(
+        &self,
+        storage: &Storage,
+        path: &Path,
+        stream: BytesStream,
+    ) -> StorageResult<()> {
+        storage
+            .as_store_err(&self.primary)?
+            .upload_stream(path, stream)
+            .await?;
+
+        let mut collect_errors: BTreeMap<String, String> = BTreeMap::new();
+        if let Some(secondaries) = self.secondaries.as_ref() {
+            for secondary_store in secondaries {
+                if let Err(err) = replicate(storage, &self.primary, secondary_store, path).await {
+                    collect_errors.insert(secondary_store.to_string(), err.to_string());
                 }
+            }
+        }
+
+        if self.failure_mode.should_fail(&collect_errors) {
+            return Err(StorageError::Multi(collect_errors));
+        }
 
-                return Err(error);
+        Ok(())
+    }
+
+    /// Downloads content as a stream from the primary storage backend. If the
+    /// primary fails, attempts to download from secondary backends, repairing
+    /// the failed ones when read repair is enabled.
+    async fn download_stream(
+        &self,
+        storage: &Storage,
+        path: &Path,
+        range: Option<Range<u64>>,
+    ) -> StorageResult<BytesStream> {
+        let mut failed = Vec::new();
+        let mut first_error = None;
+        for store_name in self.stores() {
+            match Self::try_download_stream(storage, store_name, path, range.clone()).await {
+                Ok(stream) => {
+                    if self.read_repair && !failed.is_empty() {
+                        Self::spawn_repair(storage, store_name, &failed, path);
+                    }
+                    return Ok(stream);
+                }
+                Err(err) => {
+                    failed.push(store_name);
+                    first_error.get_or_insert(err);
+                }
             }
         }
+
+        Err(first_error.unwrap_or_else(|| StorageError::StoreNotFound(self.primary.clone())))
     }
 
     /// Deletes content from the primary and, if configured, secondary storage
@@ -211,6 +301,50 @@ impl StorageStrategy for MirrorStrategy {
 
         Ok(())
     }
+
+    /// Lists the objects under the given prefix from the primary storage
+    /// backend.
+    async fn list(&self, storage: &Storage, prefix: &Path) -> StorageResult<ListStream> {
+        storage.as_store_err(&self.primary)?.list(prefix).await
+    }
+
+    /// Returns the metadata of the given path from the primary storage
+    /// backend. If the primary fails, attempts to read it from secondary
+    /// backends.
+    async fn stat(&self, storage: &Storage, path: &Path) -> StorageResult<ObjectMetadata> {
+        let res = storage.as_store_err(&self.primary)?.stat(path).await;
+
+        match res {
+            Ok(meta) => Ok(meta),
+            Err(error) => {
+                if let Some(secondaries) = self.secondaries.as_ref() {
+                    for secondary_store in secondaries {
+                        if let Ok(store) = storage.as_store_err(secondary_store) {
+                            if let Ok(meta) = store.stat(path).await {
+                                return Ok(meta);
+                            }
+                        }
+                    }
+                }
+
+                Err(error)
+            }
+        }
+    }
+
+    /// Returns a presigned URL from the primary storage backend.
+    async fn presign(
+        &self,
+        storage: &Storage,
+        path: &Path,
+        expires_in: Duration,
+        method: &Method,
+    ) -> StorageResult<Option<String>> {
+        storage
+            .as_store_err(&self.primary)?
+            .presign(path, expires_in, method)
+            .await
+    }
 }
 
 impl MirrorStrategy {
@@ -221,9 +355,64 @@ impl MirrorStrategy {
             primary: primary.to_string(),
             secondaries,
             failure_mode,
+            read_repair: false,
+        }
+    }
+
+    /// Enables read repair: when a download fails on the primary or a
+    /// secondary and succeeds on a later secondary, the content is uploaded
+    /// back to the storages that failed.
+    #[must_use]
+    pub fn with_read_repair(mut self, read_repair: bool) -> Self {
+        self.read_repair = read_repair;
+        self
+    }
+
+    /// The primary followed by the secondaries, in order.
+    fn stores(&self) -> impl Iterator<Item = &String> {
+        std::iter::once(&self.primary).chain(self.secondaries.iter().flatten())
+    }
+
+    // Private helper function for uploading content back to a store that
+    // failed to return it.
+    async fn repair(storage: &Storage, store_name: &str, path: &Path, content: &Bytes) {
+        let res = match storage.as_store_err(store_name) {
+            Ok(store) => store.upload(path, content).await.map(|_| ()),
+            Err(err) => Err(err),
+        };
+        if let Err(err) = res {
+            tracing::warn!(store = %store_name, path = %path.display(), err = %err, "read repair failed");
         }
     }
 
+    // Private helper function for streaming the content of a store back to
+    // the stores that failed to return it, in the background.
+    fn spawn_repair(storage: &Storage, from_store: &str, failed: &[&String], path: &Path) {
+        let Ok(from) = storage.as_shared_store(from_store) else {
+            return;
+        };
+        let failed = failed
+            .iter()
+            .map(|name| ((*name).clone(), storage.as_shared_store(name)))
+            .collect::<Vec<_>>();
+        let path = path.to_path_buf();
+
+        tokio::spawn(async move {
+            for (name, store) in failed {
+                let res = match store {
+                    Ok(store) => match from.download_stream(&path, None).await {
+                        Ok(stream) => store.upload_stream(&path, stream).await,
+                        Err(err) => Err(err),
+                    },
+                    Err(err) => Err(err),
+                };
+                if let Err(err) = res {
+                    tracing::warn!(store = %name, path = %path.display(), err = %err, "read repair failed");
+                }
+            }
+        });
+    }
+
     // Private helper function for downloading from a specific store.
     async fn try_download(
         storage: &Storage,
@@ -233,6 +422,17 @@ impl MirrorStrategy {
         let store = storage.as_store_err(store_name)?;
         store.get(path).await?.bytes().await
     }
+
+    // Private helper function for streaming from a specific store.
+    async fn try_download_stream(
+        storage: &Storage,
+        store_name: &str,
+        path: &Path,
+        range: Option<Range<u64>>,
+    ) -> StorageResult<BytesStream> {
+        let store = storage.as_store_err(store_name)?;
+        store.download_stream(path, range).await
+    }
 }
 
 impl FailureMode {
@@ -250,6 +450,8 @@ mod tests {
 
     use std::{collections::BTreeMap, path::PathBuf};
 
+    use futures_util::{stream, StreamExt, TryStreamExt};
+
     use super::*;
     use crate::storage::{drivers, Storage};
 
@@ -731,4 +933,120 @@ mod tests {
         assert!(store_1.exists(new_path.as_path()).await.unwrap());
         assert!(store_3.exists(new_path.as_path()).await.unwrap());
     }
+
+    #[tokio::test]
+    async fn upload_stream_should_mirror_to_secondaries() {
+        let store_1 = drivers::mem::new();
+        let store_2 = drivers::mem::new();
+
+        let strategy = Box::new(MirrorStrategy::new(
+            "store_1",
+            Some(vec!["store_2".to_string()]),
+            FailureMode::MirrorAll,
+        )) as Box<dyn StorageStrategy>;
+
+        let storage = Storage::new(
+            BTreeMap::from([
+                ("store_1".to_string(), store_1),
+                ("store_2".to_string(), store_2),
+            ]),
+            strategy,
+        );
+
+        let path = PathBuf::from("users").join("data").join("1.txt");
+        let chunks = vec![Ok(Bytes::from("file ")), Ok(Bytes::from("content"))];
+
+        assert!(storage
+            .upload_stream(path.as_path(), stream::iter(chunks).boxed())
+            .await
+            .is_ok());
+
+        let store_2 = storage.as_store("store_2").unwrap();
+        assert_eq!(
+            store_2
+                .get(path.as_path())
+                .await
+                .unwrap()
+                .bytes()
+                .await
+                .unwrap(),
+            Bytes::from("file content")
+        );
+
+        assert!(storage
+            .as_store("store_1")
+            .unwrap()
+            .delete(path.as_path())
+            .await
+            .is_ok());
+
+        let content: Vec<Bytes> = storage
+            .download_stream(path.as_path(), None)
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(content.concat(), b"file content");
+    }
+
+    #[tokio::test]
+    async fn can_repair_on_read() {
+        let strategy = Box::new(
+            MirrorStrategy::new(
+                "store_1",
+                Some(vec!["store_2".to_string(), "store_3".to_string()]),
+                FailureMode::MirrorAll,
+            )
+            .with_read_repair(true),
+        ) as Box<dyn StorageStrategy>;
+
+        let storage = Storage::new(
+            BTreeMap::from([
+                ("store_1".to_string(), drivers::mem::new()),
+                ("store_2".to_string(), drivers::mem::new()),
+                ("store_3".to_string(), drivers::mem::new()),
+            ]),
+            strategy,
+        );
+        let store_1 = storage.as_store("store_1").unwrap();
+        let store_2 = storage.as_store("store_2").unwrap();
+        let store_3 = storage.as_store("store_3").unwrap();
+
+        let path = PathBuf::from("users").join("data").join("1.txt");
+        assert!(store_3
+            .upload(path.as_path(), &Bytes::from("file content"))
+            .await
+            .is_ok());
+
+        let content: String = storage.download(path.as_path()).await.unwrap();
+        assert_eq!(content, "file content");
+
+        assert!(store_1.exists(path.as_path()).await.unwrap());
+        assert!(store_2.exists(path.as_path()).await.unwrap());
+
+        let other = PathBuf::from("users").join("data").join("2.txt");
+        assert!(store_2
+            .upload(other.as_path(), &Bytes::from("other"))
+            .await
+            .is_ok());
+        let content: Vec<Bytes> = storage
+            .download_stream(other.as_path(), None)
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(content.concat(), b"other");
+
+        // the repair of a stream runs in the background
+        for _ in 0..100 {
+            if store_1.exists(other.as_path()).await.unwrap() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(store_1.exists(other.as_path()).await.unwrap());
+        assert!(!store_3.exists(other.as_path()).await.unwrap());
+    }
 }
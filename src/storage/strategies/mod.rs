@@ -1,12 +1,17 @@
 pub mod backup;
+#[cfg(feature = "storage_encryption")]
+pub mod encrypted;
 pub mod mirror;
 pub mod single;
 
-use std::path::Path;
+use std::{ops::Range, path::Path, time::Duration};
 
-use bytes::Bytes;
+use axum::http::Method;
+use bytes::{Bytes, BytesMut};
+use futures_util::{stream, StreamExt};
 
-use crate::storage::{Storage, StorageResult};
+use super::drivers::{BytesStream, ListStream, ObjectMetadata};
+use crate::storage::{Storage, StorageError, StorageResult};
 
 #[async_trait::async_trait]
 pub trait StorageStrategy: Sync + Send {
@@ -15,4 +20,114 @@ pub trait StorageStrategy: Sync + Send {
     async fn delete(&self, storage: &Storage, path: &Path) -> StorageResult<()>;
     async fn rename(&self, storage: &Storage, from: &Path, to: &Path) -> StorageResult<()>;
     async fn copy(&self, storage: &Storage, from: &Path, to: &Path) -> StorageResult<()>;
+
+    /// Returns a presigned URL to access the content at the specified path.
+    ///
+    /// Returns `None` by default, so [`Storage::presigned_url`] falls back to
+    /// its URL signer.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageResult`] with the presigned URL, if any.
+    async fn presign(
+        &self,
+        _storage: &Storage,
+        _path: &Path,
+        _expires_in: Duration,
+        _method: &Method,
+    ) -> StorageResult<Option<String>> {
+        Ok(None)
+    }
+
+    /// Lists the objects stored under the specified prefix, recursively.
+    ///
+    /// Not supported by default.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageResult`] with the stream of the objects metadata.
+    async fn list(&self, _storage: &Storage, _prefix: &Path) -> StorageResult<ListStream> {
+        Err(StorageError::Any("Operation not supported".into()))
+    }
+
+    /// Returns the metadata of the content at the specified path.
+    ///
+    /// Not supported by default.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageResult`] with the metadata of the content.
+    async fn stat(&self, _storage: &Storage, _path: &Path) -> StorageResult<ObjectMetadata> {
+        Err(StorageError::Any("Operation not supported".into()))
+    }
+
+    /// Uploads the content of the given stream.
+    ///
+    /// The default implementation collects the stream in memory and calls
+    /// [`StorageStrategy::upload`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageResult`] indicating of the operation status.
+    async fn upload_stream(
+        &self,
+        storage: &Storage,
+        path: &Path,
+        mut stream: BytesStream,
+    ) -> StorageResult<()> {
+        let mut content = BytesMut::new();
+        while let Some(chunk) = stream.next().await {
+            content.extend_from_slice(&chunk?);
+        }
+        self.upload(storage, path, &content.freeze()).await
+    }
+
+    /// Downloads content as a stream, optionally limited to the given range
+    /// of bytes.
+    ///
+    /// The default implementation calls [`StorageStrategy::download`] and
+    /// slices the content in memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageResult`] indicating of the operation status.
+    async fn download_stream(
+        &self,
+        storage: &Storage,
+        path: &Path,
+        range: Option<Range<u64>>,
+    ) -> StorageResult<BytesStream> {
+        let content = self.download(storage, path).await?;
+        let content = match range {
+            Some(range) => {
+                let len = content.len();
+                let start = usize::try_from(range.start).unwrap_or(len).min(len);
+                let end = usize::try_from(range.end).unwrap_or(len).clamp(start, len);
+                content.slice(start..end)
+            }
+            None => content,
+        };
+        Ok(stream::once(async move { Ok(content) }).boxed())
+    }
+}
+
+/// Streams the content of the given path from one store to another.
+///
+/// Used by the strategies to copy streamed uploads to their secondaries
+/// without buffering the content in memory.
+pub(crate) async fn replicate(
+    storage: &Storage,
+    from_store: &str,
+    to_store: &str,
+    path: &Path,
+) -> StorageResult<()> {
+    let stream = storage
+        .as_store_err(from_store)?
+        .download_stream(path, None)
+        .await?;
+    storage
+        .as_store_err(to_store)?
+        .upload_stream(path, stream)
+        .await?;
+    Ok(())
 }
@@ -2,11 +2,16 @@
 //!
 //! This module provides an implementation of the [`StorageStrategy`] for a
 //! single storage strategy.
-use std::path::Path;
+use std::{ops::Range, path::Path, time::Duration};
 
+use axum::http::Method;
 use bytes::Bytes;
 
-use crate::storage::{strategies::StorageStrategy, Storage, StorageResult};
+use crate::storage::{
+    drivers::{BytesStream, ListStream, ObjectMetadata},
+    strategies::StorageStrategy,
+    Storage, StorageResult,
+};
 
 /// Represents a single storage strategy.
 #[derive(Clone)]
@@ -41,6 +46,41 @@ impl StorageStrategy for SingleStrategy {
         Ok(())
     }
 
+    /// Uploads the content of the given stream to the primary storage.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageResult`] indicating of the operation status.
+    async fn upload_stream(
+        &self,
+        storage: &Storage,
+        path: &Path,
+        stream: BytesStream,
+    ) -> StorageResult<()> {
+        storage
+            .as_store_err(&self.primary)?
+            .upload_stream(path, stream)
+            .await?;
+        Ok(())
+    }
+
+    /// Downloads content as a stream
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageResult`] indicating of the operation status.
+    async fn download_stream(
+        &self,
+        storage: &Storage,
+        path: &Path,
+        range: Option<Range<u64>>,
+    ) -> StorageResult<BytesStream> {
+        storage
+            .as_store_err(&self.primary)?
+            .download_stream(path, range)
+            .await
+    }
+
     /// Downloads content
     ///
     /// # Errors
@@ -80,6 +120,42 @@ impl StorageStrategy for SingleStrategy {
     async fn copy(&self, storage: &Storage, from: &Path, to: &Path) -> StorageResult<()> {
         Ok(storage.as_store_err(&self.primary)?.copy(from, to).await?)
     }
+
+    /// Lists the objects under the given prefix
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageResult`] indicating of the operation status.
+    async fn list(&self, storage: &Storage, prefix: &Path) -> StorageResult<ListStream> {
+        storage.as_store_err(&self.primary)?.list(prefix).await
+    }
+
+    /// Returns the metadata of the given path
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageResult`] indicating of the operation status.
+    async fn stat(&self, storage: &Storage, path: &Path) -> StorageResult<ObjectMetadata> {
+        storage.as_store_err(&self.primary)?.stat(path).await
+    }
+
+    /// Returns a presigned URL from the primary storage
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageResult`] indicating of the operation status.
+    async fn presign(
+        &self,
+        storage: &Storage,
+        path: &Path,
+        expires_in: Duration,
+        method: &Method,
+    ) -> StorageResult<Option<String>> {
+        storage
+            .as_store_err(&self.primary)?
+            .presign(path, expires_in, method)
+            .await
+    }
 }
 
 #[cfg(test)]
@@ -87,6 +163,8 @@ mod tests {
 
     use std::{collections::BTreeMap, path::PathBuf};
 
+    use futures_util::{stream, StreamExt, TryStreamExt};
+
     use super::*;
     use crate::storage::{drivers, Storage};
 
@@ -203,4 +281,59 @@ mod tests {
         assert!(store.exists(orig_path.as_path()).await.unwrap());
         assert!(store.exists(new_path.as_path()).await.unwrap());
     }
+
+    #[tokio::test]
+    async fn can_upload_and_download_stream() {
+        let store = drivers::mem::new();
+
+        let strategy = Box::new(SingleStrategy::new("default")) as Box<dyn StorageStrategy>;
+
+        let storage = Storage::new(BTreeMap::from([("default".to_string(), store)]), strategy);
+
+        let path = PathBuf::from("users").join("data").join("1.txt");
+        let chunks = vec![Ok(Bytes::from("file ")), Ok(Bytes::from("content"))];
+
+        assert!(storage
+            .upload_stream(path.as_path(), stream::iter(chunks).boxed())
+            .await
+            .is_ok());
+
+        let download_file: String = storage.download(path.as_path()).await.unwrap();
+        assert_eq!(download_file, "file content");
+
+        let content: Vec<Bytes> = storage
+            .download_stream(path.as_path(), Some(5..12))
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(content.concat(), b"content");
+    }
+
+    #[tokio::test]
+    async fn can_list_and_stat() {
+        let store = drivers::mem::new();
+
+        let strategy = Box::new(SingleStrategy::new("default")) as Box<dyn StorageStrategy>;
+
+        let storage = Storage::new(BTreeMap::from([("default".to_string(), store)]), strategy);
+
+        let path = PathBuf::from("users").join("data").join("1.txt");
+        let file_content = Bytes::from("file content");
+        assert!(storage.upload(path.as_path(), &file_content).await.is_ok());
+
+        let list: Vec<_> = storage
+            .list(PathBuf::from("users/").as_path())
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].path, path);
+
+        let meta = storage.stat(path.as_path()).await.unwrap();
+        assert_eq!(meta.size, 12);
+    }
 }
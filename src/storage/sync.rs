@@ -0,0 +1,338 @@
+//! # Storage Sync
+//!
+//! Compares the objects under a prefix across stores, using their listings,
+//! and copies the missing or diverging objects. An object diverges from the
+//! first store having it when their sizes differ, or when it was last
+//! modified before the first store's. `ETag`s are not compared, since each
+//! provider computes them differently.
+//! The first store is the source of truth, which makes the primary of a
+//! mirror a natural first store.
+//!
+//! ```rust,ignore
+//! let stores = vec!["primary".to_string(), "secondary".to_string()];
+//! let report = ctx.storage.verify(Path::new("uploads/"), &stores).await?;
+//! if !report.is_synced() {
+//!     ctx.storage.resync(Path::new("uploads/"), &stores).await?;
+//! }
+//! ```
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use futures_util::TryStreamExt;
+use serde::Serialize;
+
+use super::{drivers::ObjectMetadata, strategies::replicate, Storage, StorageResult};
+use crate::{
+    app::AppContext,
+    task::{Task, TaskInfo, Vars},
+};
+
+/// The result of comparing stores, see [`Storage::verify`].
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct SyncReport {
+    /// The number of distinct paths found across the stores.
+    pub checked: usize,
+    /// The paths missing from each store.
+    pub missing: BTreeMap<String, Vec<PathBuf>>,
+    /// The paths of each store whose object diverges from the source store,
+    /// by size or modification time.
+    pub mismatched: BTreeMap<String, Vec<PathBuf>>,
+    /// The number of objects copied by [`Storage::resync`].
+    pub repaired: usize,
+    /// The copies that failed, by `<store>:<path>`.
+    pub errors: BTreeMap<String, String>,
+}
+
+impl SyncReport {
+    /// Returns `true` when no path is missing or diverging.
+    #[must_use]
+    pub fn is_synced(&self) -> bool {
+        self.missing.values().all(Vec::is_empty) && self.mismatched.values().all(Vec::is_empty)
+    }
+}
+
+impl Storage {
+    /// Lists the objects under the given prefix in each of the given stores,
+    /// and reports the paths missing from a store or whose object diverges
+    /// from the first store having them.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a store is not found or cannot be listed.
+    pub async fn verify(&self, prefix: &Path, stores: &[String]) -> StorageResult<SyncReport> {
+        self.sync_stores(prefix, stores, false).await
+    }
+
+    /// Same as [`Storage::verify`], then copies the missing and diverging
+    /// objects from the first store having them. Objects are never deleted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a store is not found or cannot be listed. Failed
+    /// copies are reported in [`SyncReport::errors`].
+    pub async fn resync(&self, prefix: &Path, stores: &[String]) -> StorageResult<SyncReport> {
+        self.sync_stores(prefix, stores, true).await
+    }
+
+    async fn sync_stores(
+        &self,
+        prefix: &Path,
+        stores: &[String],
+        repair: bool,
+    ) -> StorageResult<SyncReport> {
+        // path -> store -> metadata
+        let mut objects: BTreeMap<PathBuf, BTreeMap<&str, ObjectMetadata>> = BTreeMap::new();
+        for store_name in stores {
+            let list = self.as_store_err(store_name)?.list(prefix).await?;
+            let list: Vec<_> = list.try_collect().await?;
+            for meta in list {
+                objects
+                    .entry(meta.path.clone())
+                    .or_default()
+                    .insert(store_name.as_str(), meta);
+            }
+        }
+
+        let mut report = SyncReport {
+            checked: objects.len(),
+            ..Default::default()
+        };
+        for (path, metas) in &objects {
+            let Some((source, source_meta)) = stores
+                .iter()
+                .find_map(|name| metas.get(name.as_str()).map(|meta| (name, meta)))
+            else {
+                continue;
+            };
+
+            for store_name in stores {
+                let diverged = match metas.get(store_name.as_str()) {
+                    None => &mut report.missing,
+                    Some(meta) if diverges(source_meta, meta) => &mut report.mismatched,
+                    Some(_) => continue,
+                };
+                diverged
+                    .entry(store_name.clone())
+                    .or_default()
+                    .push(path.clone());
+
+                if repair {
+                    match replicate(self, source, store_name, path).await {
+                        Ok(()) => report.repaired += 1,
+                        Err(err) => {
+                            report.errors.insert(
+                                format!("{store_name}:{}", path.display()),
+                                err.to_string(),
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+/// Returns `true` when the object of a store diverges from the object of the
+/// source store. Modification times are only compared when both stores
+/// report them.
+///
+/// `ETag`s are specific to each provider (an MD5 or a multipart `ETag` on S3,
+/// opaque values on Azure or GCS), so they would report every object of a
+/// mirror across providers as diverging.
+fn diverges(source: &ObjectMetadata, other: &ObjectMetadata) -> bool {
+    if source.size != other.size {
+        return true;
+    }
+    matches!(
+        (source.last_modified, other.last_modified),
+        (Some(source), Some(other)) if other < source
+    )
+}
+
+/// A task verifying, and optionally resyncing, a prefix across stores.
+///
+/// ```sh
+/// cargo loco task storage_sync prefix:uploads/ stores:primary,secondary
+/// cargo loco task storage_sync prefix:uploads/ stores:primary,secondary repair:true
+/// ```
+///
+/// `stores` defaults to all the stores.
+pub struct SyncStores;
+
+#[async_trait]
+impl Task for SyncStores {
+    fn task(&self) -> TaskInfo {
+        TaskInfo {
+            name: "storage_sync".to_string(),
+            detail: "Verifies and resyncs the objects under a prefix across storage stores"
+                .to_string(),
+        }
+    }
+
+    async fn run(&self, app_context: &AppContext, vars: &Vars) -> crate::Result<()> {
+        let storage = &app_context.storage;
+        let prefix = vars.cli_arg("prefix").map_or("", String::as_str);
+        let stores = vars.cli_arg("stores").map_or_else(
+            |_| storage.stores.keys().cloned().collect(),
+            |stores| {
+                stores
+                    .split(',')
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+            },
+        );
+        let repair = vars.cli_arg("repair").is_ok_and(|repair| repair == "true");
+
+        let report = if repair {
+            storage.resync(Path::new(prefix), &stores).await?
+        } else {
+            storage.verify(Path::new(prefix), &stores).await?
+        };
+        if report.is_synced() && report.errors.is_empty() {
+            tracing::info!(
+                prefix,
+                checked = report.checked,
+                repaired = report.repaired,
+                "stores are in sync"
+            );
+        } else {
+            tracing::warn!(
+                prefix,
+                checked = report.checked,
+                repaired = report.repaired,
+                report = %serde_json::to_string(&report)?,
+                "stores diverge"
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::*;
+    use crate::storage::{
+        drivers,
+        strategies::{mirror, mirror::MirrorStrategy, StorageStrategy},
+    };
+
+    fn storage() -> Storage {
+        let strategy = Box::new(MirrorStrategy::new(
+            "primary",
+            Some(vec!["secondary".to_string()]),
+            mirror::FailureMode::MirrorAll,
+        )) as Box<dyn StorageStrategy>;
+
+        Storage::new(
+            BTreeMap::from([
+                ("primary".to_string(), drivers::mem::new()),
+                ("secondary".to_string(), drivers::mem::new()),
+            ]),
+            strategy,
+        )
+    }
+
+    #[tokio::test]
+    async fn can_verify_and_resync() {
+        let storage = storage();
+        let stores = vec!["primary".to_string(), "secondary".to_string()];
+        let primary = storage.as_store("primary").unwrap();
+        let secondary = storage.as_store("secondary").unwrap();
+
+        assert!(storage
+            .upload(Path::new("users/1.txt"), &Bytes::from("synced"))
+            .await
+            .is_ok());
+        assert!(primary
+            .upload(Path::new("users/2.txt"), &Bytes::from("primary only"))
+            .await
+            .is_ok());
+        assert!(secondary
+            .upload(Path::new("users/3.txt"), &Bytes::from("secondary only"))
+            .await
+            .is_ok());
+        assert!(secondary
+            .upload(Path::new("users/1.txt"), &Bytes::from("diverged"))
+            .await
+            .is_ok());
+        assert!(primary
+            .upload(Path::new("other/4.txt"), &Bytes::from("other prefix"))
+            .await
+            .is_ok());
+
+        let report = storage.verify(Path::new("users/"), &stores).await.unwrap();
+        assert!(!report.is_synced());
+        assert_eq!(report.checked, 3);
+        assert_eq!(
+            report.missing,
+            BTreeMap::from([
+                ("primary".to_string(), vec![PathBuf::from("users/3.txt")]),
+                ("secondary".to_string(), vec![PathBuf::from("users/2.txt")]),
+            ])
+        );
+        assert_eq!(
+            report.mismatched,
+            BTreeMap::from([("secondary".to_string(), vec![PathBuf::from("users/1.txt")])])
+        );
+        assert_eq!(report.repaired, 0);
+
+        let report = storage.resync(Path::new("users/"), &stores).await.unwrap();
+        assert_eq!(report.repaired, 3);
+        assert!(report.errors.is_empty());
+
+        let content: String = secondary
+            .get(Path::new("users/1.txt"))
+            .await
+            .unwrap()
+            .bytes()
+            .await
+            .map(|bytes| String::from_utf8(bytes.to_vec()).unwrap())
+            .unwrap();
+        assert_eq!(content, "synced");
+        assert!(primary.exists(Path::new("users/3.txt")).await.unwrap());
+        assert!(!secondary.exists(Path::new("other/4.txt")).await.unwrap());
+
+        assert!(storage
+            .verify(Path::new("users/"), &stores)
+            .await
+            .unwrap()
+            .is_synced());
+    }
+
+    #[test]
+    fn can_detect_diverging_objects() {
+        let meta = |size, e_tag: Option<&str>, secs| ObjectMetadata {
+            path: PathBuf::from("users/1.txt"),
+            size,
+            content_type: None,
+            e_tag: e_tag.map(ToString::to_string),
+            last_modified: chrono::DateTime::from_timestamp(secs, 0),
+        };
+
+        let source = meta(6, Some("a"), 100);
+        assert!(!diverges(&source, &meta(6, Some("a"), 100)));
+        assert!(!diverges(&source, &meta(6, None, 200)));
+        assert!(diverges(&source, &meta(8, Some("a"), 100)));
+        // an S3 multipart ETag next to an opaque Azure one
+        let s3 = meta(6, Some("\"9b2cf535f27731c974343645a3985328-2\""), 100);
+        assert!(!diverges(&s3, &meta(6, Some("0x8DC2D5A4B3F1E21"), 100)));
+        // modified before the source, an outdated copy
+        assert!(diverges(&source, &meta(6, None, 50)));
+    }
+
+    #[tokio::test]
+    async fn cannot_verify_unknown_store() {
+        let storage = storage();
+        assert!(storage
+            .verify(Path::new(""), &["missing".to_string()])
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,340 @@
+//! # Image Variants
+//!
+//! Produces resized, cropped or converted versions of stored images, as
+//! defined in the `storage.variants` configuration (see
+//! [`ImageVariantConfig`]). Variants are stored next to the original:
+//! `products/1.jpg` gets a `thumb` variant at `products/1.thumb.webp`.
+//!
+//! Variants are generated on demand with [`Storage::variant`], or ahead of
+//! time by the [`ImageVariantsWorker`]:
+//!
+//! ```rust,ignore
+//! // returns the stored thumbnail, generating it on first use
+//! let thumb = ctx.storage.variant(Path::new("products/1.jpg"), "thumb").await?;
+//!
+//! // or, right after the upload
+//! ImageVariantsWorker::perform_later(&ctx, ImageVariantsWorkerArgs {
+//!     path: PathBuf::from("products/1.jpg"),
+//!     variants: None,
+//! })
+//! .await?;
+//! ```
+//!
+//! Requires the `storage_image` feature.
+use std::{
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use image::{codecs::jpeg::JpegEncoder, imageops::FilterType, DynamicImage};
+use serde::{Deserialize, Serialize};
+
+use super::{Storage, StorageError, StorageResult};
+use crate::{
+    app::AppContext,
+    bgworker::BackgroundWorker,
+    config::{ImageFit, ImageFormat, ImageVariantConfig},
+};
+
+impl Storage {
+    /// Returns the path of the named variant of the image, generating and
+    /// storing it when it does not exist yet.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the variant is not configured, or if the original
+    /// cannot be downloaded, processed or the variant uploaded.
+    pub async fn variant(&self, path: &Path, name: &str) -> StorageResult<PathBuf> {
+        let variant_path = variant_path(path, name, self.variant_config(name)?);
+        if self.stat(&variant_path).await.is_ok() {
+            return Ok(variant_path);
+        }
+        self.generate_variant(path, name).await
+    }
+
+    /// Generates and stores the named variant of the image, replacing an
+    /// existing one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the variant is not configured, or if the original
+    /// cannot be downloaded, processed or the variant uploaded.
+    pub async fn generate_variant(&self, path: &Path, name: &str) -> StorageResult<PathBuf> {
+        let config = self.variant_config(name)?.clone();
+        let original: Vec<u8> = self.download(path).await?;
+        let variant_path = variant_path(path, name, &config);
+
+        let content = process_blocking(original.into(), config).await?;
+        self.upload(&variant_path, &content).await?;
+        Ok(variant_path)
+    }
+
+    /// Generates and stores the given variants of the image, or all the
+    /// configured variants when `names` is `None`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a variant is not configured, or if the original
+    /// cannot be downloaded, processed or a variant uploaded.
+    pub async fn generate_variants(
+        &self,
+        path: &Path,
+        names: Option<&[String]>,
+    ) -> StorageResult<Vec<PathBuf>> {
+        let names = names.map_or_else(|| self.variants.keys().cloned().collect(), <[_]>::to_vec);
+        let original: Bytes = self.download::<Vec<u8>>(path).await?.into();
+
+        let mut paths = Vec::with_capacity(names.len());
+        for name in &names {
+            let config = self.variant_config(name)?.clone();
+            let variant_path = variant_path(path, name, &config);
+
+            let content = process_blocking(original.clone(), config).await?;
+            self.upload(&variant_path, &content).await?;
+            paths.push(variant_path);
+        }
+        Ok(paths)
+    }
+
+    fn variant_config(&self, name: &str) -> StorageResult<&ImageVariantConfig> {
+        self.variants.get(name).ok_or_else(|| {
+            StorageError::Any(format!("image variant `{name}` is not configured").into())
+        })
+    }
+}
+
+/// Returns the path a variant of the image is stored at.
+#[must_use]
+pub fn variant_path(path: &Path, name: &str, config: &ImageVariantConfig) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = config.format.map_or_else(
+        || {
+            path.extension()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string()
+        },
+        |format| extension(format).to_string(),
+    );
+    path.with_file_name(format!("{stem}.{name}.{extension}"))
+}
+
+const fn extension(format: ImageFormat) -> &'static str {
+    match format {
+        ImageFormat::Png => "png",
+        ImageFormat::Jpeg => "jpg",
+        ImageFormat::WebP => "webp",
+    }
+}
+
+async fn process_blocking(content: Bytes, config: ImageVariantConfig) -> StorageResult<Bytes> {
+    tokio::task::spawn_blocking(move || process(&content, &config))
+        .await
+        .map_err(|err| StorageError::Any(Box::new(err)))?
+}
+
+/// Crops, resizes and encodes an image according to the variant
+/// configuration.
+///
+/// # Errors
+///
+/// Returns an error if the image cannot be decoded or encoded.
+pub fn process(content: &[u8], config: &ImageVariantConfig) -> StorageResult<Bytes> {
+    let original_format = image::guess_format(content).map_err(to_storage_error)?;
+    let mut img =
+        image::load_from_memory_with_format(content, original_format).map_err(to_storage_error)?;
+
+    if let Some(crop) = &config.crop {
+        img = img.crop_imm(crop.x, crop.y, crop.width, crop.height);
+    }
+
+    img = match (config.width, config.height, &config.fit) {
+        (None, None, _) => img,
+        (Some(width), Some(height), ImageFit::Cover) => {
+            img.resize_to_fill(width, height, FilterType::Lanczos3)
+        }
+        (Some(width), Some(height), ImageFit::Fill) => {
+            img.resize_exact(width, height, FilterType::Lanczos3)
+        }
+        (width, height, _) => img.resize(
+            width.unwrap_or(u32::MAX),
+            height.unwrap_or(u32::MAX),
+            FilterType::Lanczos3,
+        ),
+    };
+
+    let mut buf = Cursor::new(Vec::new());
+    match config.format {
+        Some(ImageFormat::Jpeg) => DynamicImage::ImageRgb8(img.to_rgb8()).write_with_encoder(
+            JpegEncoder::new_with_quality(&mut buf, config.quality.unwrap_or(80)),
+        ),
+        Some(ImageFormat::WebP) => {
+            DynamicImage::ImageRgba8(img.to_rgba8()).write_to(&mut buf, image::ImageFormat::WebP)
+        }
+        Some(ImageFormat::Png) => img.write_to(&mut buf, image::ImageFormat::Png),
+        None => img.write_to(&mut buf, original_format),
+    }
+    .map_err(to_storage_error)?;
+
+    Ok(buf.into_inner().into())
+}
+
+fn to_storage_error(err: image::ImageError) -> StorageError {
+    StorageError::Any(Box::new(err))
+}
+
+/// Arguments of the [`ImageVariantsWorker`].
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ImageVariantsWorkerArgs {
+    /// The path of the original image.
+    pub path: PathBuf,
+    /// The variants to generate, all the configured variants when `None`.
+    pub variants: Option<Vec<String>>,
+}
+
+/// A worker generating the variants of a stored image. Register it in
+/// `Hooks::connect_workers`:
+///
+/// ```rust,ignore
+/// queue.register(ImageVariantsWorker::build(ctx)).await?;
+/// ```
+pub struct ImageVariantsWorker {
+    pub ctx: AppContext,
+}
+
+#[async_trait]
+impl BackgroundWorker<ImageVariantsWorkerArgs> for ImageVariantsWorker {
+    fn build(ctx: &AppContext) -> Self {
+        Self { ctx: ctx.clone() }
+    }
+
+    async fn perform(&self, args: ImageVariantsWorkerArgs) -> crate::Result<()> {
+        let paths = self
+            .ctx
+            .storage
+            .generate_variants(&args.path, args.variants.as_deref())
+            .await?;
+        tracing::info!(path = %args.path.display(), variants = paths.len(), "generated image variants");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use image::{GenericImageView, ImageBuffer, Rgb};
+
+    use super::*;
+    use crate::{config::ImageCrop, storage::drivers};
+
+    fn png(width: u32, height: u32) -> Bytes {
+        let img = ImageBuffer::from_pixel(width, height, Rgb([255u8, 0, 0]));
+        let mut buf = Cursor::new(Vec::new());
+        img.write_to(&mut buf, image::ImageFormat::Png).unwrap();
+        buf.into_inner().into()
+    }
+
+    fn variant(
+        width: Option<u32>,
+        height: Option<u32>,
+        fit: ImageFit,
+        format: Option<ImageFormat>,
+    ) -> ImageVariantConfig {
+        ImageVariantConfig {
+            width,
+            height,
+            fit,
+            crop: None,
+            format,
+            quality: None,
+        }
+    }
+
+    #[test]
+    fn can_build_variant_path() {
+        let path = Path::new("products/1.png");
+        assert_eq!(
+            variant_path(path, "thumb", &variant(None, None, ImageFit::Contain, None)),
+            PathBuf::from("products/1.thumb.png")
+        );
+        assert_eq!(
+            variant_path(
+                path,
+                "thumb",
+                &variant(None, None, ImageFit::Contain, Some(ImageFormat::WebP))
+            ),
+            PathBuf::from("products/1.thumb.webp")
+        );
+    }
+
+    #[test]
+    fn can_process() {
+        let content = png(40, 20);
+
+        let img = image::load_from_memory(
+            &process(&content, &variant(Some(10), None, ImageFit::Contain, None)).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(img.dimensions(), (10, 5));
+
+        let out = process(
+            &content,
+            &variant(Some(10), Some(10), ImageFit::Cover, Some(ImageFormat::Jpeg)),
+        )
+        .unwrap();
+        assert_eq!(image::guess_format(&out).unwrap(), image::ImageFormat::Jpeg);
+        assert_eq!(
+            image::load_from_memory(&out).unwrap().dimensions(),
+            (10, 10)
+        );
+
+        let mut cropped = variant(None, None, ImageFit::Contain, Some(ImageFormat::WebP));
+        cropped.crop = Some(ImageCrop {
+            x: 0,
+            y: 0,
+            width: 8,
+            height: 4,
+        });
+        let out = process(&content, &cropped).unwrap();
+        assert_eq!(image::guess_format(&out).unwrap(), image::ImageFormat::WebP);
+        assert_eq!(image::load_from_memory(&out).unwrap().dimensions(), (8, 4));
+    }
+
+    #[tokio::test]
+    async fn can_generate_variants_on_demand() {
+        let storage = Storage::single(drivers::mem::new()).with_variants(BTreeMap::from([
+            (
+                "thumb".to_string(),
+                variant(Some(4), Some(4), ImageFit::Fill, Some(ImageFormat::Png)),
+            ),
+            (
+                "small".to_string(),
+                variant(Some(20), None, ImageFit::Contain, None),
+            ),
+        ]));
+        let path = Path::new("products/1.png");
+        storage.upload(path, &png(40, 20)).await.unwrap();
+
+        let thumb = storage.variant(path, "thumb").await.unwrap();
+        assert_eq!(thumb, PathBuf::from("products/1.thumb.png"));
+        let content: Vec<u8> = storage.download(&thumb).await.unwrap();
+        assert_eq!(
+            image::load_from_memory(&content).unwrap().dimensions(),
+            (4, 4)
+        );
+
+        let paths = storage.generate_variants(path, None).await.unwrap();
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("products/1.small.png"),
+                PathBuf::from("products/1.thumb.png")
+            ]
+        );
+
+        assert!(storage.variant(path, "unknown").await.is_err());
+    }
+}
@@ -0,0 +1,145 @@
+//! Assertions on the emails sent through the stub mailer.
+//!
+//! The helpers read the messages kept by the stub transport, enabled with
+//! `mailer.stub: true` in the test configuration.
+//!
+//! ```rust,ignore
+//! use loco_rs::testing::prelude::*;
+//!
+//! request::<App, _, _>(|request, ctx| async move {
+//!     clear_mails(&ctx);
+//!     request.post("/api/auth/register").json(&payload).await;
+//!
+//!     assert_mail_sent_to(&ctx, "user@example.com");
+//!     let mail = last_mail(&ctx);
+//!     assert_eq!(mail.subject, "Welcome");
+//!     assert!(mail.html.contains("/verify/"));
+//! })
+//! .await;
+//! ```
+
+use crate::{
+    app::AppContext,
+    mailer::transport::{SentMail, StubTransport},
+};
+
+fn stub(ctx: &AppContext) -> &StubTransport {
+    ctx.mailer
+        .as_ref()
+        .and_then(|mailer| mailer.transport.as_stub())
+        .expect("the mailer is not a stub, set `mailer.stub: true` in the test configuration")
+}
+
+/// Returns the sent emails, oldest first.
+///
+/// # Panics
+///
+/// When the context has no stub mailer.
+#[must_use]
+pub fn sent_mails(ctx: &AppContext) -> Vec<SentMail> {
+    stub(ctx).mails()
+}
+
+/// Returns the last sent email.
+///
+/// # Panics
+///
+/// When the context has no stub mailer, or no email was sent.
+#[must_use]
+pub fn last_mail(ctx: &AppContext) -> SentMail {
+    sent_mails(ctx)
+        .pop()
+        .expect("expected an email to be sent, none was")
+}
+
+/// Forgets the sent emails, e.g. the ones sent while seeding a test.
+///
+/// # Panics
+///
+/// When the context has no stub mailer.
+pub fn clear_mails(ctx: &AppContext) {
+    stub(ctx).clear();
+}
+
+/// Asserts an email was sent to the address, in its `To`, `Cc` or `Bcc`
+/// header, and returns the last one.
+///
+/// # Panics
+///
+/// When no email was sent to the address.
+#[track_caller]
+pub fn assert_mail_sent_to(ctx: &AppContext, address: &str) -> SentMail {
+    let mails = sent_mails(ctx);
+    if let Some(mail) = mails.iter().rev().find(|mail| mail.is_sent_to(address)) {
+        return mail.clone();
+    }
+    let recipients = mails
+        .iter()
+        .flat_map(|mail| mail.to.iter().chain(&mail.cc).chain(&mail.bcc))
+        .cloned()
+        .collect::<Vec<_>>();
+    panic!("expected an email sent to `{address}`, sent to: {recipients:?}");
+}
+
+/// Asserts no email was sent.
+///
+/// # Panics
+///
+/// When an email was sent.
+#[track_caller]
+pub fn assert_no_mail_sent(ctx: &AppContext) {
+    let mails = sent_mails(ctx);
+    assert!(
+        mails.is_empty(),
+        "expected no email to be sent, {} were sent with subjects: {:?}",
+        mails.len(),
+        mails.iter().map(|mail| &mail.subject).collect::<Vec<_>>()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        mailer::{Email, EmailSender},
+        tests_cfg,
+    };
+
+    #[tokio::test]
+    async fn can_assert_on_sent_mails() {
+        let mut ctx = tests_cfg::app::get_app_context().await;
+        let mailer = EmailSender::stub();
+        ctx.mailer = Some(mailer.clone());
+
+        assert_no_mail_sent(&ctx);
+
+        mailer
+            .mail(&Email {
+                from: Some("test@framework.com".to_string()),
+                to: "user1@framework.com".into(),
+                cc: "user2@framework.com".into(),
+                subject: "Welcome".to_string(),
+                text: "Welcome text".to_string(),
+                html: "<p>Welcome</p>".to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let mail = assert_mail_sent_to(&ctx, "user2@framework.com");
+        assert_eq!(mail.subject, "Welcome");
+        assert_eq!(last_mail(&ctx).html, "<p>Welcome</p>");
+        assert!(last_mail(&ctx).message_id.is_some());
+
+        clear_mails(&ctx);
+        assert_no_mail_sent(&ctx);
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "expected an email sent to `user3@framework.com`")]
+    async fn fails_when_not_sent_to() {
+        let mut ctx = tests_cfg::app::get_app_context().await;
+        ctx.mailer = Some(EmailSender::stub());
+        assert_mail_sent_to(&ctx, "user3@framework.com");
+    }
+}
@@ -43,6 +43,7 @@ pub fn test_config() -> Config {
             mode: config::WorkerMode::ForegroundBlocking,
         },
         mailer: None,
+        storage: None,
         initializers: None,
         settings: None,
         scheduler: Some(scheduler::Config {
@@ -25,7 +25,7 @@ impl AuthMailer {
             ctx,
             &welcome,
             mailer::Args {
-                to: user.email.to_string(),
+                to: user.email.clone().into(),
                 locals: json!({
                   "name": user.name,
                   "verifyToken": user.email_verification_token,
@@ -49,7 +49,7 @@ impl AuthMailer {
             ctx,
             &forgot,
             mailer::Args {
-                to: user.email.to_string(),
+                to: user.email.clone().into(),
                 locals: json!({
                   "name": user.name,
                   "resetToken": user.reset_token,
@@ -456,3 +456,131 @@ async fn powered_by_header(#[case] ident: Option<String>) {
 
     handle.abort();
 }
+
+#[rstest]
+#[case(true)]
+#[case(false)]
+#[tokio::test]
+#[serial]
+async fn response_cache(#[case] enable: bool) {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    #[allow(clippy::items_after_statements)]
+    async fn action() -> Result<Response> {
+        let calls = CALLS.fetch_add(1, Ordering::SeqCst) + 1;
+        format::render().text(&format!("call {calls}"))
+    }
+
+    CALLS.store(0, Ordering::SeqCst);
+
+    let mut ctx: AppContext = tests_cfg::app::get_app_context().await;
+    ctx.config.server.middlewares.response_cache = Some(middleware::response_cache::Config {
+        enable,
+        ..Default::default()
+    });
+
+    let handle = infra_cfg::server::start_with_route(ctx.clone(), "/", get(action)).await;
+
+    let first = reqwest::get(infra_cfg::server::get_base_url())
+        .await
+        .expect("valid response");
+    assert_eq!(first.text().await.expect("response text"), "call 1");
+
+    let second = reqwest::get(infra_cfg::server::get_base_url())
+        .await
+        .expect("valid response");
+    if enable {
+        assert_eq!(second.text().await.expect("response text"), "call 1");
+    } else {
+        assert_eq!(second.text().await.expect("response text"), "call 2");
+    }
+
+    middleware::response_cache::invalidate_path(&ctx, "/")
+        .await
+        .expect("invalidate path");
+
+    let third = reqwest::get(infra_cfg::server::get_base_url())
+        .await
+        .expect("valid response");
+    if enable {
+        assert_eq!(third.text().await.expect("response text"), "call 2");
+    } else {
+        assert_eq!(third.text().await.expect("response text"), "call 3");
+    }
+
+    handle.abort();
+}
+
+#[rstest]
+#[case(false)]
+#[case(true)]
+#[tokio::test]
+#[serial]
+async fn response_cache_does_not_share_authorized_responses(#[case] vary_on_authorization: bool) {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    #[allow(clippy::items_after_statements)]
+    async fn action(headers: axum::http::HeaderMap) -> Result<Response> {
+        let calls = CALLS.fetch_add(1, Ordering::SeqCst) + 1;
+        let user = headers
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        format::render().text(&format!("{user} call {calls}"))
+    }
+
+    CALLS.store(0, Ordering::SeqCst);
+
+    let mut ctx: AppContext = tests_cfg::app::get_app_context().await;
+    let mut vary_headers = vec!["accept".to_string()];
+    if vary_on_authorization {
+        vary_headers.push("authorization".to_string());
+    }
+    ctx.config.server.middlewares.response_cache = Some(middleware::response_cache::Config {
+        enable: true,
+        vary_headers,
+        ..Default::default()
+    });
+
+    let handle = infra_cfg::server::start_with_route(ctx.clone(), "/", get(action)).await;
+
+    let client = reqwest::Client::new();
+    let get_as = |token: &'static str| {
+        client
+            .get(infra_cfg::server::get_base_url())
+            .header("authorization", format!("Bearer {token}"))
+            .send()
+    };
+
+    let alice = get_as("alice").await.expect("valid response");
+    assert_eq!(
+        alice.text().await.expect("response text"),
+        "Bearer alice call 1"
+    );
+
+    let bob = get_as("bob").await.expect("valid response");
+    assert_eq!(
+        bob.text().await.expect("response text"),
+        "Bearer bob call 2"
+    );
+
+    let alice = get_as("alice").await.expect("valid response");
+    if vary_on_authorization {
+        assert_eq!(
+            alice.text().await.expect("response text"),
+            "Bearer alice call 1"
+        );
+    } else {
+        assert_eq!(
+            alice.text().await.expect("response text"),
+            "Bearer alice call 3"
+        );
+    }
+
+    handle.abort();
+}
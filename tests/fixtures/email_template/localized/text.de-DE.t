@@ -0,0 +1 @@
+Hallo {{ name }} ({{ lang }})
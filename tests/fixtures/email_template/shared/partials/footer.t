@@ -0,0 +1 @@
+<footer>Sent to {{ name }}</footer>
\ No newline at end of file